@@ -17,12 +17,17 @@ const FIB_CODE: &str = r#"
     fib(xyz);
 "#;
 
-fn compile(n: u8) -> FunctionMut {
-    let mut compiler = Compiler::new(
-        CompilerOptions::new(brane_dsl::Lang::BraneScript),
-        PackageIndex::empty(),
-    );
-
+fn compile(
+    n: u8,
+    optimize: bool,
+) -> FunctionMut {
+    let options = if optimize {
+        CompilerOptions::optimized(brane_dsl::Lang::BraneScript)
+    } else {
+        CompilerOptions::new(brane_dsl::Lang::BraneScript)
+    };
+
+    let mut compiler = Compiler::new(options, PackageIndex::empty());
     compiler.compile(FIB_CODE.replace("xyz", &format!("{}", n))).unwrap()
 }
 
@@ -32,24 +37,16 @@ async fn run(f: FunctionMut) {
 }
 
 fn from_elem(c: &mut Criterion) {
-    c.bench_function("fib 5", |b| {
-        b.to_async(FuturesExecutor).iter(|| run(compile(5)));
-    });
-    c.bench_function("fib 10", move |b| {
-        b.to_async(FuturesExecutor).iter(|| run(compile(10)));
-    });
-    c.bench_function("fib 15", move |b| {
-        b.to_async(FuturesExecutor).iter(|| run(compile(15)));
-    });
-    c.bench_function("fib 20", move |b| {
-        b.to_async(FuturesExecutor).iter(|| run(compile(20)));
-    });
-    c.bench_function("fib 25", move |b| {
-        b.to_async(FuturesExecutor).iter(|| run(compile(25)));
-    });
-    c.bench_function("fib 30", move |b| {
-        b.to_async(FuturesExecutor).iter(|| run(compile(30)));
-    });
+    for n in [5, 10, 15, 20, 25, 30] {
+        let mut group = c.benchmark_group(format!("fib {}", n));
+        group.bench_function("unoptimized", move |b| {
+            b.to_async(FuturesExecutor).iter(|| run(compile(n, false)));
+        });
+        group.bench_function("optimized", move |b| {
+            b.to_async(FuturesExecutor).iter(|| run(compile(n, true)));
+        });
+        group.finish();
+    }
 }
 
 criterion_group!(benches, from_elem);
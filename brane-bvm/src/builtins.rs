@@ -1,19 +1,27 @@
 use crate::objects::{Class, Object};
 use crate::{
-    executor::{ServiceState, VmExecutor},
+    executor::{ServiceState, VmExecutor, EXCLUDE_CLASS, LABELS_CLASS},
     stack::Slot,
 };
+use anyhow::Result;
 use broom::Heap;
 use fnv::FnvHashMap;
 use specifications::common::Value;
+use specifications::errors::JobFailure;
+use std::collections::HashMap;
 
 const BUILTIN_PRINT_NAME: &str = "print";
 const BUILTIN_PRINT_CODE: u8 = 0x01;
 
-const BUILTIN_WAIT_UNTIL_STARTED_CODE: u8 = 0x02;
-const BUILTIN_WAIT_UNTIL_DONE_CODE: u8 = 0x03;
+pub const BUILTIN_WAIT_UNTIL_STARTED_NAME: &str = "waitUntilStarted";
+pub const BUILTIN_WAIT_UNTIL_STARTED_CODE: u8 = 0x02;
+pub const BUILTIN_WAIT_UNTIL_DONE_NAME: &str = "waitUntilDone";
+pub const BUILTIN_WAIT_UNTIL_DONE_CODE: u8 = 0x03;
+pub const BUILTIN_STOP_SERVICE_NAME: &str = "stopService";
+pub const BUILTIN_STOP_SERVICE_CODE: u8 = 0x04;
 
 const BUILTIN_SERVICE_NAME: &str = "Service";
+const BUILTIN_ERROR_NAME: &str = "Error";
 
 ///
 ///
@@ -26,8 +34,46 @@ pub fn register(
     let service = heap.insert(class(BUILTIN_SERVICE_NAME.to_string())).into_handle();
     globals.insert(BUILTIN_SERVICE_NAME.to_string(), Slot::Object(service));
 
+    let error = heap.insert(error_class()).into_handle();
+    globals.insert(BUILTIN_ERROR_NAME.to_string(), Slot::Object(error));
+
+    // Location constraints (see `on` statements).
+    let labels = heap.insert(class(LABELS_CLASS.to_string())).into_handle();
+    globals.insert(LABELS_CLASS.to_string(), Slot::Object(labels));
+
+    let exclude = heap.insert(class(EXCLUDE_CLASS.to_string())).into_handle();
+    globals.insert(EXCLUDE_CLASS.to_string(), Slot::Object(exclude));
+
     // Functions
     globals.insert(String::from(BUILTIN_PRINT_NAME), Slot::BuiltIn(BUILTIN_PRINT_CODE));
+
+    // Services (also available as methods, e.g. `service.waitUntilStarted()`).
+    globals.insert(
+        String::from(BUILTIN_WAIT_UNTIL_STARTED_NAME),
+        Slot::BuiltIn(BUILTIN_WAIT_UNTIL_STARTED_CODE),
+    );
+    globals.insert(
+        String::from(BUILTIN_WAIT_UNTIL_DONE_NAME),
+        Slot::BuiltIn(BUILTIN_WAIT_UNTIL_DONE_CODE),
+    );
+    globals.insert(
+        String::from(BUILTIN_STOP_SERVICE_NAME),
+        Slot::BuiltIn(BUILTIN_STOP_SERVICE_CODE),
+    );
+}
+
+///
+///
+///
+pub fn service_method(method: &str) -> Option<Slot> {
+    let code = match method {
+        BUILTIN_WAIT_UNTIL_STARTED_NAME => BUILTIN_WAIT_UNTIL_STARTED_CODE,
+        BUILTIN_WAIT_UNTIL_DONE_NAME => BUILTIN_WAIT_UNTIL_DONE_CODE,
+        "stop" => BUILTIN_STOP_SERVICE_CODE,
+        _ => return None,
+    };
+
+    Some(Slot::BuiltIn(code))
 }
 
 ///
@@ -36,10 +82,60 @@ pub fn register(
 fn class(name: String) -> Object {
     Object::Class(Class {
         name,
+        properties: Default::default(),
+        defaults: Default::default(),
+        methods: Default::default(),
+    })
+}
+
+///
+///
+///
+fn error_class() -> Object {
+    let mut properties = FnvHashMap::default();
+    properties.insert(String::from("reason"), String::from("string"));
+    properties.insert(String::from("message"), String::from("string"));
+    properties.insert(String::from("code"), String::from("integer?"));
+    properties.insert(String::from("stderr"), String::from("string?"));
+
+    let mut defaults = FnvHashMap::default();
+    defaults.insert(String::from("code"), Value::Unit);
+    defaults.insert(String::from("stderr"), Value::Unit);
+
+    Object::Class(Class {
+        name: BUILTIN_ERROR_NAME.to_string(),
+        properties,
+        defaults,
         methods: Default::default(),
     })
 }
 
+///
+///
+///
+pub fn error(error: &anyhow::Error) -> Value {
+    // Job failures carry details reported by branelet, other errors only a message.
+    let failure = match error.downcast_ref::<JobFailure>() {
+        Some(failure) => failure.clone(),
+        None => JobFailure::new(String::from("error"), format!("{:#}", error)),
+    };
+
+    let mut properties = HashMap::new();
+    properties.insert(String::from("reason"), Value::Unicode(failure.reason));
+    properties.insert(String::from("message"), Value::Unicode(failure.message));
+    if let Some(code) = failure.exit_code {
+        properties.insert(String::from("code"), Value::Integer(code as i64));
+    }
+    if let Some(stderr) = failure.stderr {
+        properties.insert(String::from("stderr"), Value::Unicode(stderr));
+    }
+
+    Value::Struct {
+        data_type: BUILTIN_ERROR_NAME.to_string(),
+        properties,
+    }
+}
+
 ///
 ///
 ///
@@ -47,8 +143,7 @@ pub async fn call<E>(
     builtin: u8,
     arguments: Vec<Value>,
     executor: &E,
-    _location: Option<String>,
-) -> Value
+) -> Result<Value>
 where
     E: VmExecutor,
 {
@@ -58,40 +153,36 @@ where
             let text = value.to_string();
 
             // Delegate printing to executor.
-            executor.stdout(text).await.unwrap();
-
-            Value::Unit
+            executor.stdout(text).await?;
         }
         BUILTIN_WAIT_UNTIL_STARTED_CODE => {
-            let instance = arguments.first().unwrap();
-            if let Value::Struct { properties, .. } = arguments.first().unwrap() {
-                let identifier = properties
-                    .get("identifier")
-                    .expect("Missing `identifier` property.")
-                    .to_string();
-                executor.wait_until(identifier, ServiceState::Started).await.unwrap();
-            } else {
-                dbg!(&instance);
-                unreachable!();
-            }
-
-            Value::Unit
+            let identifier = service_identifier(&arguments)?;
+            executor.wait_until(identifier, ServiceState::Started).await?;
         }
         BUILTIN_WAIT_UNTIL_DONE_CODE => {
-            let instance = arguments.first().unwrap();
-            if let Value::Struct { properties, .. } = arguments.first().unwrap() {
-                let identifier = properties
-                    .get("identifier")
-                    .expect("Missing `identifier` property.")
-                    .to_string();
-                executor.wait_until(identifier, ServiceState::Done).await.unwrap();
-            } else {
-                dbg!(&instance);
-                unreachable!();
-            }
-
-            Value::Unit
+            let identifier = service_identifier(&arguments)?;
+            executor.wait_until(identifier, ServiceState::Done).await?;
+        }
+        BUILTIN_STOP_SERVICE_CODE => {
+            let identifier = service_identifier(&arguments)?;
+            executor.stop_service(identifier).await?;
         }
         _ => unreachable!(),
     }
+
+    Ok(Value::Unit)
+}
+
+///
+///
+///
+fn service_identifier(arguments: &[Value]) -> Result<String> {
+    match arguments.first() {
+        Some(Value::Struct { data_type, properties }) if data_type == BUILTIN_SERVICE_NAME => properties
+            .get("identifier")
+            .map(|identifier| identifier.to_string())
+            .ok_or_else(|| anyhow!("Missing `identifier` property.")),
+        Some(value) => bail!("Expected a service, but got '{}'.", value.data_type()),
+        None => bail!("Expected a service, but got nothing."),
+    }
 }
@@ -1,4 +1,4 @@
-use crate::objects::{self, Class, Object};
+use crate::objects::{self, Array, Class, Instance, Object};
 use crate::stack::Slot;
 use crate::Function;
 use anyhow::Result;
@@ -7,30 +7,50 @@ use bytes::{BufMut, Bytes, BytesMut};
 use fnv::FnvHashMap;
 use specifications::common::{Bytecode, SpecClass, SpecFunction, Value};
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fmt::Write;
 
 pub mod opcodes {
     pub const OP_ADD: u8 = 0x01;
     pub const OP_AND: u8 = 0x02;
     pub const OP_ARRAY: u8 = 0x03;
+    pub const OP_ARRAY_LONG: u8 = 0x2D;
     pub const OP_CALL: u8 = 0x04;
     pub const OP_CLASS: u8 = 0x05;
+    pub const OP_CLASS_LONG: u8 = 0x2E;
     pub const OP_CONSTANT: u8 = 0x06;
+    pub const OP_CONSTANT_LONG: u8 = 0x2F;
+    pub const OP_CONST_MINUS_ONE: u8 = 0x28;
+    pub const OP_CONST_MINUS_TWO: u8 = 0x29;
+    pub const OP_CONST_ONE: u8 = 0x2A;
+    pub const OP_CONST_TWO: u8 = 0x2B;
+    pub const OP_CONST_ZERO: u8 = 0x2C;
     pub const OP_DEFINE_GLOBAL: u8 = 0x07;
+    pub const OP_DEFINE_GLOBAL_LONG: u8 = 0x30;
     pub const OP_DIVIDE: u8 = 0x08;
     pub const OP_DOT: u8 = 0x09;
+    pub const OP_DOT_LONG: u8 = 0x3F;
     pub const OP_EQUAL: u8 = 0x0A;
     pub const OP_FALSE: u8 = 0x0B;
     pub const OP_GET_GLOBAL: u8 = 0x0C;
+    pub const OP_GET_GLOBAL_LONG: u8 = 0x31;
     pub const OP_GET_LOCAL: u8 = 0x0D;
+    pub const OP_GET_LOCAL_LONG: u8 = 0x32;
     pub const OP_GET_METHOD: u8 = 0x26;
+    pub const OP_GET_METHOD_LONG: u8 = 0x33;
     pub const OP_GET_PROPERTY: u8 = 0x27;
+    pub const OP_GET_PROPERTY_LONG: u8 = 0x34;
+    pub const OP_GET_PROPERTY_SAFE: u8 = 0x40;
+    pub const OP_GET_PROPERTY_SAFE_LONG: u8 = 0x41;
     pub const OP_GREATER: u8 = 0x0E;
+    pub const OP_IMPL: u8 = 0x38;
     pub const OP_IMPORT: u8 = 0x0F;
+    pub const OP_IMPORT_LONG: u8 = 0x35;
     pub const OP_INDEX: u8 = 0x10;
     pub const OP_JUMP: u8 = 0x11;
     pub const OP_JUMP_BACK: u8 = 0x12;
     pub const OP_JUMP_IF_FALSE: u8 = 0x13;
+    pub const OP_JUMP_IF_UNIT: u8 = 0x39;
     pub const OP_LESS: u8 = 0x14;
     pub const OP_LOC: u8 = 0x25;
     pub const OP_LOC_POP: u8 = 0x15;
@@ -41,20 +61,78 @@ pub mod opcodes {
     pub const OP_NOT: u8 = 0x1A;
     pub const OP_OR: u8 = 0x1B;
     pub const OP_PARALLEL: u8 = 0x1C;
+    pub const OP_PARAM: u8 = 0x3A;
     pub const OP_POP: u8 = 0x1D;
     pub const OP_POP_N: u8 = 0x1E;
+    pub const OP_RETRY_POP: u8 = 0x3E;
+    pub const OP_RETRY_PUSH: u8 = 0x3D;
     pub const OP_RETURN: u8 = 0x1F;
     pub const OP_SET_GLOBAL: u8 = 0x20;
+    pub const OP_SET_GLOBAL_LONG: u8 = 0x36;
     pub const OP_SET_LOCAL: u8 = 0x21;
+    pub const OP_SET_LOCAL_LONG: u8 = 0x37;
     pub const OP_SUBSTRACT: u8 = 0x22;
     pub const OP_TRUE: u8 = 0x23;
+    pub const OP_TRY: u8 = 0x3B;
+    pub const OP_TRY_END: u8 = 0x3C;
     pub const OP_UNIT: u8 = 0x24;
+
+    ///
+    ///
+    ///
+    pub fn operands_len(opcode: u8) -> usize {
+        match opcode {
+            OP_JUMP | OP_JUMP_BACK | OP_JUMP_IF_FALSE | OP_JUMP_IF_UNIT | OP_TRY => 2,
+            OP_ARRAY_LONG
+            | OP_CLASS_LONG
+            | OP_CONSTANT_LONG
+            | OP_DEFINE_GLOBAL_LONG
+            | OP_DOT_LONG
+            | OP_GET_GLOBAL_LONG
+            | OP_GET_LOCAL_LONG
+            | OP_GET_METHOD_LONG
+            | OP_GET_PROPERTY_LONG
+            | OP_GET_PROPERTY_SAFE_LONG
+            | OP_IMPORT_LONG
+            | OP_SET_GLOBAL_LONG
+            | OP_SET_LOCAL_LONG => 2,
+            OP_ARRAY | OP_CALL | OP_CLASS | OP_CONSTANT | OP_DEFINE_GLOBAL | OP_DOT | OP_GET_GLOBAL | OP_GET_LOCAL
+            | OP_GET_METHOD | OP_GET_PROPERTY | OP_GET_PROPERTY_SAFE | OP_IMPORT | OP_NEW | OP_PARALLEL | OP_POP_N
+            | OP_SET_GLOBAL | OP_SET_LOCAL => 1,
+            _ => 0,
+        }
+    }
+
+    ///
+    ///
+    ///
+    pub fn long_variant(opcode: u8) -> Option<u8> {
+        let long = match opcode {
+            OP_ARRAY => OP_ARRAY_LONG,
+            OP_CLASS => OP_CLASS_LONG,
+            OP_CONSTANT => OP_CONSTANT_LONG,
+            OP_DEFINE_GLOBAL => OP_DEFINE_GLOBAL_LONG,
+            OP_DOT => OP_DOT_LONG,
+            OP_GET_GLOBAL => OP_GET_GLOBAL_LONG,
+            OP_GET_LOCAL => OP_GET_LOCAL_LONG,
+            OP_GET_METHOD => OP_GET_METHOD_LONG,
+            OP_GET_PROPERTY => OP_GET_PROPERTY_LONG,
+            OP_GET_PROPERTY_SAFE => OP_GET_PROPERTY_SAFE_LONG,
+            OP_IMPORT => OP_IMPORT_LONG,
+            OP_SET_GLOBAL => OP_SET_GLOBAL_LONG,
+            OP_SET_LOCAL => OP_SET_LOCAL_LONG,
+            _ => return None,
+        };
+
+        Some(long)
+    }
 }
 
 #[derive(Clone)]
 pub struct ClassMut {
     pub name: String,
     pub properties: HashMap<String, String>,
+    pub defaults: HashMap<String, Value>,
     pub methods: HashMap<String, FunctionMut>,
 }
 
@@ -62,11 +140,13 @@ impl ClassMut {
     pub fn new(
         name: String,
         properties: HashMap<String, String>,
+        defaults: HashMap<String, Value>,
         methods: HashMap<String, FunctionMut>,
     ) -> Self {
         Self {
             name,
             properties,
+            defaults,
             methods,
         }
     }
@@ -77,37 +157,39 @@ impl ClassMut {
     pub fn freeze(
         self,
         heap: &mut Heap<Object>,
-    ) -> Class {
+    ) -> Result<Class> {
         let methods = self
             .methods
             .into_iter()
             .map(|(k, v)| {
-                let function = v.freeze(heap);
+                let function = v.freeze(heap)?;
                 let handle = heap.insert(Object::Function(function)).into_handle();
                 let slot = Slot::Object(handle);
 
-                (k, slot)
+                Ok((k, slot))
             })
-            .collect();
+            .collect::<Result<_>>()?;
 
-        Class {
+        Ok(Class {
             name: self.name,
+            properties: self.properties.into_iter().collect(),
+            defaults: self.defaults.into_iter().collect(),
             methods,
-        }
+        })
     }
 }
 
 impl From<SpecClass> for ClassMut {
     fn from(f: SpecClass) -> Self {
         let methods = f.methods.iter().map(|(k, v)| (k.clone(), v.clone().into())).collect();
-        Self::new(f.name, f.properties, methods)
+        Self::new(f.name, f.properties, f.defaults, methods)
     }
 }
 
 impl From<ClassMut> for SpecClass {
     fn from(f: ClassMut) -> Self {
         let methods = f.methods.iter().map(|(k, v)| (k.clone(), v.clone().into())).collect();
-        Self::new(f.name, f.properties, methods)
+        Self::new(f.name, f.properties, f.defaults, methods)
     }
 }
 
@@ -167,8 +249,8 @@ impl FunctionMut {
     pub fn freeze(
         self,
         heap: &mut Heap<Object>,
-    ) -> objects::Function {
-        Function::new(self.name, self.arity, self.chunk.freeze(heap))
+    ) -> Result<objects::Function> {
+        Ok(Function::new(self.name, self.arity, self.chunk.freeze(heap)?))
     }
 }
 
@@ -211,6 +293,21 @@ impl Chunk {
                     constant_instruction("OP_CONSTANT", self, offset, &mut result);
                     skip = 1;
                 }
+                OP_CONST_MINUS_ONE => {
+                    writeln!(result, "OP_CONST_MINUS_ONE")?;
+                }
+                OP_CONST_MINUS_TWO => {
+                    writeln!(result, "OP_CONST_MINUS_TWO")?;
+                }
+                OP_CONST_ONE => {
+                    writeln!(result, "OP_CONST_ONE")?;
+                }
+                OP_CONST_TWO => {
+                    writeln!(result, "OP_CONST_TWO")?;
+                }
+                OP_CONST_ZERO => {
+                    writeln!(result, "OP_CONST_ZERO")?;
+                }
                 OP_ADD => {
                     writeln!(result, "OP_ADD")?;
                 }
@@ -266,6 +363,15 @@ impl Chunk {
                 OP_LOC => {
                     writeln!(result, "OP_LOC")?;
                 }
+                OP_IMPL => {
+                    writeln!(result, "OP_IMPL")?;
+                }
+                OP_PARAM => {
+                    writeln!(result, "OP_PARAM")?;
+                }
+                OP_TRY_END => {
+                    writeln!(result, "OP_TRY_END")?;
+                }
                 OP_INDEX => {
                     writeln!(result, "OP_INDEX")?;
                 }
@@ -275,6 +381,12 @@ impl Chunk {
                 OP_LOC_POP => {
                     writeln!(result, "OP_LOC_POP")?;
                 }
+                OP_RETRY_PUSH => {
+                    writeln!(result, "OP_RETRY_PUSH")?;
+                }
+                OP_RETRY_POP => {
+                    writeln!(result, "OP_RETRY_POP")?;
+                }
                 OP_DOT => {
                     constant_instruction("OP_DOT", self, offset, &mut result);
                     skip = 1;
@@ -299,10 +411,18 @@ impl Chunk {
                     jump_instruction("OP_JUMP_IF_FALSE", 1, self, offset, &mut result);
                     skip = 2;
                 }
+                OP_JUMP_IF_UNIT => {
+                    jump_instruction("OP_JUMP_IF_UNIT", 1, self, offset, &mut result);
+                    skip = 2;
+                }
                 OP_JUMP => {
                     jump_instruction("OP_JUMP", 1, self, offset, &mut result);
                     skip = 2;
                 }
+                OP_TRY => {
+                    jump_instruction("OP_TRY", 1, self, offset, &mut result);
+                    skip = 2;
+                }
                 OP_JUMP_BACK => {
                     jump_instruction("OP_JUMP_BACK", -1, self, offset, &mut result);
                     skip = 2;
@@ -327,6 +447,10 @@ impl Chunk {
                     constant_instruction("OP_GET_PROPERTY", self, offset, &mut result);
                     skip = 1;
                 }
+                OP_GET_PROPERTY_SAFE => {
+                    constant_instruction("OP_GET_PROPERTY_SAFE", self, offset, &mut result);
+                    skip = 1;
+                }
                 OP_SET_GLOBAL => {
                     byte_instruction("OP_SET_GLOBAL", self, offset, &mut result);
                     skip = 1;
@@ -343,7 +467,59 @@ impl Chunk {
                     constant_instruction("OP_IMPORT", self, offset, &mut result);
                     skip = 1;
                 }
-                0x00 | 0x28..=u8::MAX => {
+                OP_ARRAY_LONG => {
+                    word_instruction("OP_ARRAY_LONG", self, offset, &mut result);
+                    skip = 2;
+                }
+                OP_CLASS_LONG => {
+                    constant_long_instruction("OP_CLASS_LONG", self, offset, &mut result);
+                    skip = 2;
+                }
+                OP_CONSTANT_LONG => {
+                    constant_long_instruction("OP_CONSTANT_LONG", self, offset, &mut result);
+                    skip = 2;
+                }
+                OP_DEFINE_GLOBAL_LONG => {
+                    constant_long_instruction("OP_DEFINE_GLOBAL_LONG", self, offset, &mut result);
+                    skip = 2;
+                }
+                OP_DOT_LONG => {
+                    constant_long_instruction("OP_DOT_LONG", self, offset, &mut result);
+                    skip = 2;
+                }
+                OP_GET_GLOBAL_LONG => {
+                    constant_long_instruction("OP_GET_GLOBAL_LONG", self, offset, &mut result);
+                    skip = 2;
+                }
+                OP_GET_LOCAL_LONG => {
+                    word_instruction("OP_GET_LOCAL_LONG", self, offset, &mut result);
+                    skip = 2;
+                }
+                OP_GET_METHOD_LONG => {
+                    constant_long_instruction("OP_GET_METHOD_LONG", self, offset, &mut result);
+                    skip = 2;
+                }
+                OP_GET_PROPERTY_LONG => {
+                    constant_long_instruction("OP_GET_PROPERTY_LONG", self, offset, &mut result);
+                    skip = 2;
+                }
+                OP_GET_PROPERTY_SAFE_LONG => {
+                    constant_long_instruction("OP_GET_PROPERTY_SAFE_LONG", self, offset, &mut result);
+                    skip = 2;
+                }
+                OP_IMPORT_LONG => {
+                    constant_long_instruction("OP_IMPORT_LONG", self, offset, &mut result);
+                    skip = 2;
+                }
+                OP_SET_GLOBAL_LONG => {
+                    constant_long_instruction("OP_SET_GLOBAL_LONG", self, offset, &mut result);
+                    skip = 2;
+                }
+                OP_SET_LOCAL_LONG => {
+                    word_instruction("OP_SET_LOCAL_LONG", self, offset, &mut result);
+                    skip = 2;
+                }
+                0x00 | 0x42..=u8::MAX => {
                     unreachable!()
                 }
             }
@@ -394,6 +570,23 @@ fn constant_instruction(
     }
 }
 
+///
+///
+///
+fn constant_long_instruction(
+    name: &str,
+    chunk: &Chunk,
+    offset: usize,
+    result: &mut String,
+) {
+    let constant = u16::from_be_bytes([chunk.code[offset + 1], chunk.code[offset + 2]]);
+    write!(result, "{:<16} {:4} | ", name, constant).unwrap();
+
+    if let Some(value) = chunk.constants.get(constant as usize) {
+        writeln!(result, "{:?}", value).unwrap();
+    }
+}
+
 ///
 ///
 ///
@@ -407,6 +600,19 @@ fn byte_instruction(
     writeln!(result, "{:<16} {:4} | ", name, slot).unwrap();
 }
 
+///
+///
+///
+fn word_instruction(
+    name: &str,
+    chunk: &Chunk,
+    offset: usize,
+    result: &mut String,
+) {
+    let slot = u16::from_be_bytes([chunk.code[offset + 1], chunk.code[offset + 2]]);
+    writeln!(result, "{:<16} {:4} | ", name, slot).unwrap();
+}
+
 #[derive(Clone, Debug)]
 pub struct ChunkMut {
     pub code: BytesMut,
@@ -439,60 +645,17 @@ impl ChunkMut {
     pub fn freeze(
         self,
         heap: &mut Heap<Object>,
-    ) -> Chunk {
+    ) -> Result<Chunk> {
         let constants = self
             .constants
             .into_iter()
-            .map(|c| match c {
-                Value::Boolean(b) => match b {
-                    true => Slot::True,
-                    false => Slot::False,
-                },
-                Value::Integer(i) => Slot::Integer(i),
-                Value::Real(r) => Slot::Real(r),
-                Value::Function(f) => {
-                    let f = FunctionMut::from(f);
+            .map(|c| freeze_constant(c, heap))
+            .collect::<Result<_>>()?;
 
-                    let function = Object::Function(f.freeze(heap));
-                    let handle = heap.insert(function).into_handle();
-
-                    Slot::Object(handle)
-                }
-                Value::Unicode(s) => {
-                    let string = Object::String(s);
-                    let handle = heap.insert(string).into_handle();
-
-                    Slot::Object(handle)
-                }
-                Value::Class(c) => {
-                    let mut methods = FnvHashMap::default();
-
-                    for (name, method) in c.methods.clone().into_iter() {
-                        let method_mut: FunctionMut = method.into();
-                        let method = Object::Function(method_mut.freeze(heap));
-
-                        let handle = heap.insert(method).into_handle();
-                        methods.insert(name, Slot::Object(handle));
-                    }
-
-                    let class = Class { name: c.name, methods };
-
-                    let class = Object::Class(class);
-                    let handle = heap.insert(class).into_handle();
-
-                    Slot::Object(handle)
-                }
-                a => {
-                    dbg!(&a);
-                    todo!();
-                }
-            })
-            .collect();
-
-        Chunk {
+        Ok(Chunk {
             code: self.code.freeze(),
             constants,
-        }
+        })
     }
 
     ///
@@ -533,9 +696,132 @@ impl ChunkMut {
     pub fn add_constant(
         &mut self,
         value: Value,
-    ) -> u8 {
+    ) -> usize {
         self.constants.push(value);
 
-        (self.constants.len() as u8) - 1
+        self.constants.len() - 1
+    }
+
+    ///
+    ///
+    ///
+    pub fn write_operand(
+        &mut self,
+        opcode: u8,
+        operand: usize,
+    ) -> Result<()> {
+        // Only use the long variant if the operand doesn't fit in a single byte.
+        if let Ok(operand) = u8::try_from(operand) {
+            self.write_pair(opcode, operand);
+            return Ok(());
+        }
+
+        let long = opcodes::long_variant(opcode);
+        match (long, u16::try_from(operand)) {
+            (Some(long), Ok(operand)) => {
+                self.write(long);
+                self.write_bytes(&operand.to_be_bytes()[..]);
+
+                Ok(())
+            }
+            (Some(_), Err(_)) => bail!("Operand {} exceeds the maximum of {}.", operand, u16::MAX),
+            (None, _) => bail!("Operand {} exceeds the maximum of {}.", operand, u8::MAX),
+        }
     }
 }
+
+///
+///
+///
+fn freeze_constant(
+    constant: Value,
+    heap: &mut Heap<Object>,
+) -> Result<Slot> {
+    let slot = match constant {
+        Value::Boolean(b) => match b {
+            true => Slot::True,
+            false => Slot::False,
+        },
+        Value::Integer(i) => Slot::Integer(i),
+        Value::Real(r) => Slot::Real(r),
+        Value::Unit => Slot::Unit,
+        Value::Function(f) => {
+            let f = FunctionMut::from(f);
+
+            let function = Object::Function(f.freeze(heap)?);
+            let handle = heap.insert(function).into_handle();
+
+            Slot::Object(handle)
+        }
+        Value::FunctionExt(f) => {
+            let handle = heap.insert(Object::FunctionExt(f)).into_handle();
+
+            Slot::Object(handle)
+        }
+        Value::Unicode(s) => {
+            let string = Object::String(s);
+            let handle = heap.insert(string).into_handle();
+
+            Slot::Object(handle)
+        }
+        Value::Array { data_type, entries } => {
+            let elements = entries
+                .into_iter()
+                .map(|e| freeze_constant(e, heap))
+                .collect::<Result<_>>()?;
+
+            let mut array = Array::new(elements);
+            array.element_type = data_type;
+
+            let handle = heap.insert(Object::Array(array)).into_handle();
+
+            Slot::Object(handle)
+        }
+        Value::Class(c) => {
+            let mut methods = FnvHashMap::default();
+
+            for (name, method) in c.methods.clone().into_iter() {
+                let method_mut: FunctionMut = method.into();
+                let method = Object::Function(method_mut.freeze(heap)?);
+
+                let handle = heap.insert(method).into_handle();
+                methods.insert(name, Slot::Object(handle));
+            }
+
+            let class = Class {
+                name: c.name,
+                properties: c.properties.into_iter().collect(),
+                defaults: c.defaults.into_iter().collect(),
+                methods,
+            };
+
+            let class = Object::Class(class);
+            let handle = heap.insert(class).into_handle();
+
+            Slot::Object(handle)
+        }
+        Value::Struct { data_type, properties } => {
+            // Constants have no access to globals, so the instance gets a class of its own (like built-ins).
+            let class = Class {
+                name: data_type,
+                properties: Default::default(),
+                defaults: Default::default(),
+                methods: Default::default(),
+            };
+            let class = heap.insert(Object::Class(class)).into_handle();
+
+            let properties = properties
+                .into_iter()
+                .map(|(name, value)| Ok((name, freeze_constant(value, heap)?)))
+                .collect::<Result<_>>()?;
+
+            let instance = Object::Instance(Instance::new(class, properties));
+            let handle = heap.insert(instance).into_handle();
+
+            Slot::Object(handle)
+        }
+        Value::Pointer { variable, .. } => bail!("Pointer to '{}' can't be used as a constant.", variable),
+    };
+
+    Ok(slot)
+}
@@ -3,14 +3,163 @@ use std::collections::HashMap;
 use anyhow::Result;
 use async_trait::async_trait;
 use specifications::common::{FunctionExt, Value};
+use std::fmt;
+use std::time::Duration;
+
+/// Name of the built-in class that holds a label selector, e.g. `on { region: "eu" }`.
+pub const LABELS_CLASS: &str = "Labels";
+/// Name of the built-in class that holds excluded locations, e.g. `on not "site-b"`.
+pub const EXCLUDE_CLASS: &str = "Exclude";
 
 #[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ServiceState {
     Created = 1,
     Started = 2,
     Done = 3,
 }
 
+///
+///
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    ///
+    ///
+    ///
+    pub fn new(
+        retries: u32,
+        backoff: Duration,
+    ) -> Self {
+        Self { retries, backoff }
+    }
+
+    ///
+    ///
+    ///
+    pub fn delay(
+        &self,
+        attempt: u32,
+    ) -> Duration {
+        // Exponential backoff: wait twice as long after each failed attempt.
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.backoff.saturating_mul(factor)
+    }
+}
+
+///
+///
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LocationConstraint {
+    /// Locations to choose from, any location if empty.
+    pub locations: Vec<String>,
+    /// Labels a location must have.
+    pub labels: HashMap<String, String>,
+    /// Locations to avoid.
+    pub excluded: Vec<String>,
+}
+
+impl LocationConstraint {
+    ///
+    ///
+    ///
+    pub fn new(
+        locations: Vec<String>,
+        labels: HashMap<String, String>,
+        excluded: Vec<String>,
+    ) -> Self {
+        Self {
+            locations,
+            labels,
+            excluded,
+        }
+    }
+
+    ///
+    ///
+    ///
+    pub fn from_value(value: &Value) -> Result<Self> {
+        let constraint = match value {
+            Value::Unicode(_) | Value::Array { .. } => Self::new(location_names(value)?, HashMap::new(), vec![]),
+            Value::Struct { data_type, properties } if data_type == LABELS_CLASS => {
+                let labels = properties.iter().map(|(k, v)| (k.clone(), v.to_string())).collect();
+                Self::new(vec![], labels, vec![])
+            }
+            Value::Struct { data_type, properties } if data_type == EXCLUDE_CLASS => {
+                let excluded = properties.get("locations").map(location_names).transpose()?;
+                Self::new(vec![], HashMap::new(), excluded.unwrap_or_default())
+            }
+            value => bail!(
+                "Expected a location, list of locations, or labels, but got '{}'.",
+                value.data_type()
+            ),
+        };
+
+        Ok(constraint)
+    }
+
+    ///
+    ///
+    ///
+    pub fn location(&self) -> Option<String> {
+        // Only a single location without further constraints needs no planning.
+        if self.locations.len() == 1 && self.labels.is_empty() && self.excluded.is_empty() {
+            self.locations.first().cloned()
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for LocationConstraint {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        if let Some(location) = self.location() {
+            return write!(f, "{}", location);
+        }
+
+        let mut constraints = vec![];
+        if !self.locations.is_empty() {
+            constraints.push(format!("any of [{}]", self.locations.join(", ")));
+        }
+        if !self.labels.is_empty() {
+            let mut labels: Vec<String> = self.labels.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+            labels.sort();
+            constraints.push(format!("labels {{{}}}", labels.join(", ")));
+        }
+        if !self.excluded.is_empty() {
+            constraints.push(format!("not [{}]", self.excluded.join(", ")));
+        }
+
+        write!(f, "{}", constraints.join(", "))
+    }
+}
+
+///
+///
+///
+fn location_names(value: &Value) -> Result<Vec<String>> {
+    match value {
+        Value::Unicode(location) => Ok(vec![location.clone()]),
+        Value::Array { entries, .. } => entries
+            .iter()
+            .map(|entry| match entry {
+                Value::Unicode(location) => Ok(location.clone()),
+                entry => bail!("Expected a location name, but got '{}'.", entry.data_type()),
+            })
+            .collect(),
+        value => bail!("Expected a location name, but got '{}'.", value.data_type()),
+    }
+}
+
 #[async_trait]
 pub trait VmExecutor {
     ///
@@ -20,7 +169,8 @@ pub trait VmExecutor {
         &self,
         call: FunctionExt,
         arguments: HashMap<String, Value>,
-        location: Option<String>,
+        location: Option<LocationConstraint>,
+        retry: RetryPolicy,
     ) -> Result<Value>;
 
     ///
@@ -55,6 +205,14 @@ pub trait VmExecutor {
         service: String,
         state: ServiceState,
     ) -> Result<()>;
+
+    ///
+    ///
+    ///
+    async fn stop_service(
+        &self,
+        service: String,
+    ) -> Result<()>;
 }
 
 #[derive(Clone)]
@@ -75,7 +233,8 @@ impl VmExecutor for NoExtExecutor {
         &self,
         _: FunctionExt,
         _: HashMap<String, Value>,
-        _: Option<String>,
+        _: Option<LocationConstraint>,
+        _: RetryPolicy,
     ) -> Result<Value> {
         bail!("External function calls not supported.");
     }
@@ -126,4 +285,14 @@ impl VmExecutor for NoExtExecutor {
     ) -> Result<()> {
         bail!("External function calls not supported.");
     }
+
+    ///
+    ///
+    ///
+    async fn stop_service(
+        &self,
+        _: String,
+    ) -> Result<()> {
+        bail!("External function calls not supported.");
+    }
 }
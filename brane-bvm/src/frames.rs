@@ -66,4 +66,49 @@ impl CallFrame {
             constant
         }
     }
+
+    ///
+    ///
+    ///
+    pub fn read_constant_long(&mut self) -> Option<&Slot> {
+        let index = self.read_u16();
+
+        unsafe {
+            let function = self.function.get_unchecked().as_function().unwrap();
+            function.chunk.constants.get(index as usize)
+        }
+    }
+}
+
+///
+///
+///
+#[derive(Copy, Clone, Debug)]
+pub struct Handler {
+    pub frames: usize,
+    pub ip: usize,
+    pub locations: usize,
+    pub retries: usize,
+    pub stack_len: usize,
+}
+
+impl Handler {
+    ///
+    ///
+    ///
+    pub fn new(
+        frames: usize,
+        ip: usize,
+        locations: usize,
+        retries: usize,
+        stack_len: usize,
+    ) -> Self {
+        Self {
+            frames,
+            ip,
+            locations,
+            retries,
+            stack_len,
+        }
+    }
 }
@@ -2,7 +2,7 @@ use crate::bytecode::{ClassMut, FunctionMut};
 use crate::{bytecode::Chunk, stack::Slot};
 use broom::prelude::*;
 use fnv::FnvHashMap;
-use specifications::common::FunctionExt;
+use specifications::common::{FunctionExt, Value};
 
 #[derive(Debug)]
 pub enum Object {
@@ -88,6 +88,8 @@ impl Trace<Object> for Array {
 #[derive(Clone, Debug)]
 pub struct Class {
     pub name: String,
+    pub properties: FnvHashMap<String, String>,
+    pub defaults: FnvHashMap<String, Value>,
     pub methods: FnvHashMap<String, Slot>,
 }
 
@@ -114,7 +116,8 @@ impl Class {
 
         ClassMut {
             name: self.name,
-            properties: Default::default(),
+            properties: self.properties.into_iter().collect(),
+            defaults: self.defaults.into_iter().collect(),
             methods,
         }
     }
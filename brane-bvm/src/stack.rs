@@ -76,7 +76,9 @@ impl Slot {
             }
             Value::Class(c) => {
                 let class: ClassMut = c.into();
-                let class = class.freeze(heap);
+                let class = class
+                    .freeze(heap)
+                    .unwrap_or_else(|error| panic!("Class can't be loaded: {}", error));
 
                 let handle = heap.insert(Object::Class(class)).into_handle();
                 Slot::Object(handle)
@@ -93,6 +95,20 @@ impl Slot {
                     .as_object()
                     .unwrap();
 
+                // Outputs may omit optional properties, these are filled in with their defaults.
+                if let Some(Object::Class(class)) = heap.get(i_class) {
+                    let missing: Vec<(String, Value)> = class
+                        .defaults
+                        .iter()
+                        .filter(|(name, _)| !i_properties.contains_key(*name))
+                        .map(|(name, default)| (name.clone(), default.clone()))
+                        .collect();
+
+                    for (name, default) in missing {
+                        i_properties.insert(name, Slot::from_value(default, globals, heap));
+                    }
+                }
+
                 let instance = Instance::new(i_class, i_properties);
                 let instance = Object::Instance(instance);
                 let handle = heap.insert(instance).into_handle();
@@ -385,6 +401,14 @@ impl Stack {
         }
     }
 
+    ///
+    ///
+    ///
+    #[inline]
+    pub fn peek_unit(&self) -> bool {
+        matches!(self.inner.last().expect("Expecting a non-empty stack."), Slot::Unit)
+    }
+
     ///
     ///
     ///
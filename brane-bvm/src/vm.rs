@@ -1,12 +1,13 @@
 use std::cmp::max;
+use std::time::Duration;
 
-use crate::frames::CallFrame;
+use crate::frames::{CallFrame, Handler};
 use crate::objects::Class;
 use crate::stack::{Slot, Stack};
 use crate::{
     builtins,
     bytecode::{opcodes::*, FunctionMut},
-    executor::VmExecutor,
+    executor::{LocationConstraint, RetryPolicy, VmExecutor},
     objects::Object,
     objects::{Array, Instance},
 };
@@ -29,6 +30,11 @@ pub struct VmOptions {
     ///
     ///
     pub global_return_halts: bool,
+
+    ///
+    ///
+    ///
+    pub retry: RetryPolicy,
 }
 
 #[derive(Clone, Default, Debug)]
@@ -85,13 +91,17 @@ pub struct Vm<E>
 where
     E: VmExecutor + Clone + Send + Sync,
 {
+    error: Option<Value>,
     executor: E,
     frames: SmallVec<[CallFrame; 64]>,
     globals: FnvHashMap<String, Slot>,
+    handlers: Vec<Handler>,
     heap: Heap<Object>,
     locations: Vec<Handle<Object>>,
     package_index: PackageIndex,
     options: VmOptions,
+    params: FnvHashMap<String, Value>,
+    retries: Vec<(usize, RetryPolicy)>,
     stack: Stack,
 }
 
@@ -146,13 +156,17 @@ where
         builtins::register(&mut globals, &mut heap);
 
         Self {
+            error: None,
             executor,
             frames,
             globals,
+            handlers: Default::default(),
             heap,
             locations,
             package_index,
             options,
+            params: Default::default(),
+            retries: Default::default(),
             stack,
         }
     }
@@ -197,6 +211,16 @@ where
         )
     }
 
+    ///
+    ///
+    ///
+    pub fn set_params(
+        &mut self,
+        params: FnvHashMap<String, Value>,
+    ) {
+        self.params = params;
+    }
+
     ///
     ///
     ///
@@ -221,12 +245,27 @@ where
             panic!("VM not in a state to accept main function.");
         }
 
-        let function = Object::Function(function.freeze(&mut self.heap));
-        let handle = self.heap.insert(function).into_handle();
+        // A function that can't be loaded is reported like an uncaught error.
+        match function.freeze(&mut self.heap) {
+            Ok(function) => {
+                let handle = self.heap.insert(Object::Function(function)).into_handle();
 
-        self.stack.push_object(handle);
-        self.call(0).await;
-        self.run().await;
+                self.stack.push_object(handle);
+                self.call(0).await;
+                self.run().await;
+            }
+            Err(error) => self.error = Some(builtins::error(&error)),
+        }
+
+        // An uncaught error aborts the script, but leaves the VM usable (e.g. for the next REPL input).
+        if let Some(error) = self.take_error() {
+            if let Err(error) = self.executor.stderr(format!("Uncaught error: {}", error)).await {
+                error!("{:?}", error);
+            }
+
+            self.reset();
+            return;
+        }
 
         // For REPLs
         if self.options.clear_after_main {
@@ -248,8 +287,14 @@ where
 
         self.options.global_return_halts = true;
 
-        let function = Object::Function(function.freeze(&mut self.heap));
-        let handle = self.heap.insert(function).into_handle();
+        let function = match function.freeze(&mut self.heap) {
+            Ok(function) => function,
+            Err(error) => {
+                self.error = Some(builtins::error(&error));
+                return Value::Unit;
+            }
+        };
+        let handle = self.heap.insert(Object::Function(function)).into_handle();
 
         self.stack.push_object(handle);
         self.call(0).await;
@@ -262,6 +307,24 @@ where
         }
     }
 
+    ///
+    ///
+    ///
+    pub fn take_error(&mut self) -> Option<Value> {
+        self.error.take()
+    }
+
+    ///
+    ///
+    ///
+    fn reset(&mut self) {
+        self.frames.clear();
+        self.handlers.clear();
+        self.locations.clear();
+        self.retries.clear();
+        self.stack.clear();
+    }
+
     ///
     ///
     ///
@@ -270,7 +333,7 @@ where
         arity: u8,
     ) {
         let frame_last = self.stack.len();
-        let frame_first = frame_last - (arity + 1) as usize;
+        let frame_first = frame_last - (arity as usize + 1);
 
         let function = self.stack.get(frame_first).as_object().expect("");
         if let Some(Object::Function(_f)) = self.heap.get(function) {
@@ -297,25 +360,44 @@ where
             match *instruction {
                 OP_ADD => self.op_add(),
                 OP_AND => self.op_and(),
-                OP_ARRAY => self.op_array(),
+                OP_ARRAY => self.op_array(false),
+                OP_ARRAY_LONG => self.op_array(true),
                 OP_CALL => self.op_call().await,
-                OP_CLASS => self.op_class(),
-                OP_CONSTANT => self.op_constant(),
-                OP_DEFINE_GLOBAL => self.op_define_global(),
+                OP_CLASS => self.op_class(false),
+                OP_CLASS_LONG => self.op_class(true),
+                OP_CONSTANT => self.op_constant(false),
+                OP_CONSTANT_LONG => self.op_constant(true),
+                OP_CONST_MINUS_ONE => self.stack.push_integer(-1),
+                OP_CONST_MINUS_TWO => self.stack.push_integer(-2),
+                OP_CONST_ONE => self.stack.push_integer(1),
+                OP_CONST_TWO => self.stack.push_integer(2),
+                OP_CONST_ZERO => self.stack.push_integer(0),
+                OP_DEFINE_GLOBAL => self.op_define_global(false),
+                OP_DEFINE_GLOBAL_LONG => self.op_define_global(true),
                 OP_DIVIDE => self.op_divide(),
-                OP_DOT => self.op_dot(),
+                OP_DOT => self.op_dot(false),
+                OP_DOT_LONG => self.op_dot(true),
                 OP_EQUAL => self.op_equal(),
                 OP_FALSE => self.op_false(),
-                OP_GET_GLOBAL => self.op_get_global(),
-                OP_GET_LOCAL => self.op_get_local(),
-                OP_GET_METHOD => self.op_get_method(),
-                OP_GET_PROPERTY => self.op_get_property(),
+                OP_GET_GLOBAL => self.op_get_global(false),
+                OP_GET_GLOBAL_LONG => self.op_get_global(true),
+                OP_GET_LOCAL => self.op_get_local(false),
+                OP_GET_LOCAL_LONG => self.op_get_local(true),
+                OP_GET_METHOD => self.op_get_method(false),
+                OP_GET_METHOD_LONG => self.op_get_method(true),
+                OP_GET_PROPERTY => self.op_get_property(false, false),
+                OP_GET_PROPERTY_LONG => self.op_get_property(true, false),
+                OP_GET_PROPERTY_SAFE => self.op_get_property(false, true),
+                OP_GET_PROPERTY_SAFE_LONG => self.op_get_property(true, true),
                 OP_GREATER => self.op_greater(),
-                OP_IMPORT => self.op_import(),
+                OP_IMPL => self.op_impl(),
+                OP_IMPORT => self.op_import(false),
+                OP_IMPORT_LONG => self.op_import(true),
                 OP_INDEX => self.op_index(),
                 OP_JUMP => self.op_jump(),
                 OP_JUMP_BACK => self.op_jump_back(),
                 OP_JUMP_IF_FALSE => self.op_jump_if_false(),
+                OP_JUMP_IF_UNIT => self.op_jump_if_unit(),
                 OP_LESS => self.op_less(),
                 OP_LOC => self.op_loc(),
                 OP_LOC_POP => self.op_loc_pop(),
@@ -326,8 +408,11 @@ where
                 OP_NOT => self.op_not(),
                 OP_OR => self.op_or(),
                 OP_PARALLEL => self.op_parallel(),
+                OP_PARAM => self.op_param(),
                 OP_POP => self.op_pop(),
                 OP_POP_N => self.op_pop_n(),
+                OP_RETRY_POP => self.op_retry_pop(),
+                OP_RETRY_PUSH => self.op_retry_push(),
                 OP_RETURN => {
                     self.op_return();
 
@@ -335,10 +420,14 @@ where
                         break;
                     }
                 }
-                OP_SET_GLOBAL => self.op_set_global(false),
-                OP_SET_LOCAL => self.op_set_local(),
+                OP_SET_GLOBAL => self.op_set_global(false, false),
+                OP_SET_GLOBAL_LONG => self.op_set_global(false, true),
+                OP_SET_LOCAL => self.op_set_local(false),
+                OP_SET_LOCAL_LONG => self.op_set_local(true),
                 OP_SUBSTRACT => self.op_substract(),
                 OP_TRUE => self.op_true(),
+                OP_TRY => self.op_try(),
+                OP_TRY_END => self.op_try_end(),
                 OP_UNIT => self.op_unit(),
                 x => {
                     println!("Unkown opcode: {}", x);
@@ -346,6 +435,11 @@ where
                 }
             }
 
+            // See `throw`, the error is handled by the caller of `run`.
+            if self.error.is_some() {
+                break;
+            }
+
             // INVESTIGATE: this appears to cause a deadlock (?).
             // debug!("Sending stack to client.");
             // self.executor.debug(format!("{}", self.stack)).await.unwrap();
@@ -365,6 +459,38 @@ where
         self.frame().read_u8()
     }
 
+    ///
+    ///
+    ///
+    #[inline]
+    fn read_constant(
+        &mut self,
+        long: bool,
+    ) -> Slot {
+        let constant = if long {
+            self.frame().read_constant_long()
+        } else {
+            self.frame().read_constant()
+        };
+
+        *constant.expect("Failed to read constant.")
+    }
+
+    ///
+    ///
+    ///
+    #[inline]
+    fn read_operand(
+        &mut self,
+        long: bool,
+    ) -> usize {
+        if long {
+            self.frame().read_u16() as usize
+        } else {
+            *self.frame().read_u8().expect("Failed to read byte.") as usize
+        }
+    }
+
     ///
     ///
     ///
@@ -435,8 +561,11 @@ where
     ///
     ///
     #[inline]
-    pub fn op_array(&mut self) {
-        let n = *self.frame().read_u8().expect("");
+    pub fn op_array(
+        &mut self,
+        long: bool,
+    ) {
+        let n = self.read_operand(long);
         let elements: Vec<Slot> = (0..n).map(|_| self.stack.pop()).rev().collect();
 
         let array = Object::Array(Array::new(elements));
@@ -452,21 +581,32 @@ where
     pub async fn op_call(&mut self) {
         let arity = *self.frame().read_u8().expect("");
         let frame_last = self.stack.len();
-        let frame_first = frame_last - (arity + 1) as usize;
+        let frame_first = frame_last - (arity as usize + 1);
 
         let function = self.stack.get(frame_first);
         let location = self
             .locations
             .last()
-            .map(|l| self.heap.get(l).unwrap())
-            .map(|l| l.as_string().cloned().unwrap());
+            .map(|l| Slot::Object(*l).into_value(&self.heap))
+            .map(|l| LocationConstraint::from_value(&l))
+            .transpose();
 
         let value = match function {
             Slot::BuiltIn(code) => {
                 let function = *code;
                 let arguments = self.arguments(arity);
 
-                builtins::call(function, arguments, &self.executor, location).await
+                match builtins::call(function, arguments, &self.executor).await {
+                    Ok(value) => value,
+                    Err(error) => {
+                        error!("{:?}", error);
+
+                        // Like external functions, failed built-ins can be caught by the script.
+                        let error = builtins::error(&error);
+                        self.throw(error);
+                        return;
+                    }
+                }
             }
             Slot::Object(handle) => match self.heap.get(handle).expect("") {
                 Object::Function(_) => {
@@ -482,15 +622,30 @@ where
                         .map(|(p, a)| (p.name.clone(), a))
                         .collect();
 
+                    // An invalid location can be caught by the script, like a failed function.
+                    let location = match location {
+                        Ok(location) => location,
+                        Err(error) => {
+                            let error = builtins::error(&error);
+                            self.throw(error);
+                            return;
+                        }
+                    };
+
+                    let retry = self.retry_policy();
                     let function_name = function.name.clone();
-                    match self.executor.call(function, arguments, location).await {
+                    match self.executor.call(function, arguments, location, retry).await {
                         Ok(value) => {
                             debug!("Value from function '{}' (external): \n{:#?}", function_name, value);
                             value
                         }
-                        Err(e) => {
-                            error!("{:?}", e);
-                            panic!("External function failed");
+                        Err(error) => {
+                            error!("{:?}", error);
+
+                            // Failed external functions can be caught by the script.
+                            let error = builtins::error(&error);
+                            self.throw(error);
+                            return;
                         }
                     }
                 }
@@ -517,8 +672,11 @@ where
     ///
     ///
     #[inline]
-    pub fn op_class(&mut self) {
-        let class = *self.frame().read_constant().expect("");
+    pub fn op_class(
+        &mut self,
+        long: bool,
+    ) {
+        let class = self.read_constant(long);
         self.stack.push(class);
     }
 
@@ -526,8 +684,11 @@ where
     ///
     ///
     #[inline]
-    pub fn op_constant(&mut self) {
-        let constant = *self.frame().read_constant().expect("Failed to read constant");
+    pub fn op_constant(
+        &mut self,
+        long: bool,
+    ) {
+        let constant = self.read_constant(long);
 
         self.stack.push(constant);
     }
@@ -536,8 +697,11 @@ where
     ///
     ///
     #[inline]
-    pub fn op_define_global(&mut self) {
-        self.op_set_global(true);
+    pub fn op_define_global(
+        &mut self,
+        long: bool,
+    ) {
+        self.op_set_global(true, long);
     }
 
     ///
@@ -561,25 +725,14 @@ where
     ///
     ///
     #[inline]
-    pub fn op_dot(&mut self) {
-        let instance = self.stack.pop().as_object().expect("expecting object.");
-        let property = self
-            .frame()
-            .read_constant()
-            .expect("expecting constant.")
-            .as_object()
-            .expect("expecting object.");
-
-        if let Some(Object::Instance(instance)) = self.heap.get(instance) {
-            if let Some(Object::String(property)) = self.heap.get(property) {
-                let value = *instance.properties.get(property).expect("expecting property.");
-                self.stack.push(value);
-
-                return;
-            }
-        }
+    pub fn op_dot(
+        &mut self,
+        long: bool,
+    ) {
+        let instance = self.stack.pop();
+        let property = self.read_constant(long).as_object().expect("expecting object.");
 
-        panic!("invalid");
+        self.get_property(instance, property, false);
     }
 
     ///
@@ -605,8 +758,11 @@ where
     ///
     ///
     #[inline]
-    pub fn op_get_global(&mut self) {
-        let identifier = *self.frame().read_constant().expect("Failed to read constant.");
+    pub fn op_get_global(
+        &mut self,
+        long: bool,
+    ) {
+        let identifier = self.read_constant(long);
 
         if let Slot::Object(handle) = identifier {
             if let Some(Object::String(identifier)) = self.heap.get(handle) {
@@ -627,9 +783,12 @@ where
     ///
     ///
     #[inline]
-    pub fn op_get_local(&mut self) {
-        let index = *self.frame().read_u8().expect("Failed to read byte.");
-        let index = self.frame().stack_offset + index as usize;
+    pub fn op_get_local(
+        &mut self,
+        long: bool,
+    ) {
+        let index = self.read_operand(long);
+        let index = self.frame().stack_offset + index;
 
         self.stack.copy_push(index);
     }
@@ -638,26 +797,20 @@ where
     ///
     ///
     #[inline]
-    pub fn op_get_method(&mut self) {
+    pub fn op_get_method(
+        &mut self,
+        long: bool,
+    ) {
         let instance_slot = self.stack.pop();
         let instance = instance_slot.as_object().expect("expecting object.");
-        let method = self
-            .frame()
-            .read_constant()
-            .expect("expecting constant.")
-            .as_object()
-            .expect("expecting object.");
+        let method = self.read_constant(long).as_object().expect("expecting object.");
 
         if let Some(Object::Instance(instance)) = self.heap.get(instance) {
             if let Some(Object::String(method)) = self.heap.get(method) {
                 if let Some(Object::Class(class)) = self.heap.get(instance.class) {
                     let method = if class.name == *"Service" {
-                        match method.as_str() {
-                            // Quickfix :(
-                            "waitUntilStarted" => Slot::BuiltIn(0x02),
-                            "waitUntilDone" => Slot::BuiltIn(0x03),
-                            _ => panic!("expecting method."),
-                        }
+                        // Quickfix :(
+                        builtins::service_method(method).expect("expecting method.")
                     } else {
                         *class.methods.get(method).expect("expecting method.")
                     };
@@ -677,25 +830,65 @@ where
     ///
     ///
     #[inline]
-    pub fn op_get_property(&mut self) {
-        let instance = self.stack.pop().as_object().expect("expecting object.");
-        let property = self
-            .frame()
-            .read_constant()
-            .expect("expecting constant.")
-            .as_object()
-            .expect("expecting object.");
+    pub fn op_get_property(
+        &mut self,
+        long: bool,
+        safe: bool,
+    ) {
+        let instance = self.stack.pop();
+        let property = self.read_constant(long).as_object().expect("expecting object.");
 
-        if let Some(Object::Instance(instance)) = self.heap.get(instance) {
-            if let Some(Object::String(property)) = self.heap.get(property) {
-                let value = *instance.properties.get(property).expect("expecting property.");
-                self.stack.push(value);
+        self.get_property(instance, property, safe);
+    }
 
+    ///
+    ///
+    ///
+    fn get_property(
+        &mut self,
+        instance: Slot,
+        property: Handle<Object>,
+        safe: bool,
+    ) {
+        let property = match self.heap.get(property) {
+            Some(Object::String(property)) => property.clone(),
+            _ => panic!("Illegal property identifier"),
+        };
+
+        let instance = match instance.as_object().and_then(|i| self.heap.get(i)) {
+            Some(Object::Instance(instance)) => instance,
+            _ => {
+                let error = anyhow!(
+                    "Cannot read property '{}' of a value that is not an instance.",
+                    property
+                );
+                self.throw(builtins::error(&error));
                 return;
             }
+        };
+
+        if let Some(value) = instance.properties.get(&property) {
+            let value = *value;
+            self.stack.push(value);
+
+            return;
         }
 
-        panic!("invalid");
+        // A missing property is unit if it's optional, or if it's accessed with `?.`.
+        let (class, optional) = match self.heap.get(instance.class) {
+            Some(Object::Class(class)) => {
+                let optional = class.properties.get(&property).map(|t| t.ends_with('?'));
+                (class.name.clone(), optional.unwrap_or_default())
+            }
+            _ => (String::from("?"), false),
+        };
+
+        if safe || optional {
+            self.stack.push(Slot::Unit);
+        } else {
+            let error = anyhow!("Instance of class '{}' has no property '{}'.", class, property);
+            self.throw(builtins::error(&error));
+        }
     }
 
     ///
@@ -713,8 +906,40 @@ where
     ///
     ///
     #[inline]
-    pub fn op_import(&mut self) {
-        let p_name = self.frame().read_constant().expect("").as_object().expect("");
+    pub fn op_impl(&mut self) {
+        let extension = self.stack.pop().as_object().expect("expecting object.");
+        let (name, methods) = match self.heap.get(extension) {
+            Some(Object::Class(extension)) => (extension.name.clone(), extension.methods.clone()),
+            _ => panic!("Expecting a class."),
+        };
+
+        // Add the methods to the existing class, so existing instances also get them.
+        let class = match self.globals.get(&name).and_then(|c| c.as_object()) {
+            Some(class) => class,
+            None => {
+                let error = anyhow!("Cannot implement methods for unknown class '{}'.", name);
+                self.throw(builtins::error(&error));
+                return;
+            }
+        };
+
+        if let Some(Object::Class(class)) = self.heap.get_mut(class) {
+            class.methods.extend(methods);
+        } else {
+            let error = anyhow!("Cannot implement methods for '{}', as it is not a class.", name);
+            self.throw(builtins::error(&error));
+        }
+    }
+
+    ///
+    ///
+    ///
+    #[inline]
+    pub fn op_import(
+        &mut self,
+        long: bool,
+    ) {
+        let p_name = self.read_constant(long).as_object().expect("");
 
         if let Some(Object::String(p_name)) = self.heap.get(p_name) {
             let p_name = p_name.clone();
@@ -736,6 +961,8 @@ where
                         kind: kind.clone(),
                         version: package.version.clone(),
                         parameters: function.parameters.clone(),
+                        resources: function.resources.clone(),
+                        security: function.security.clone(),
                     };
 
                     let handle = self.heap.insert(Object::FunctionExt(function)).into_handle();
@@ -746,9 +973,32 @@ where
             }
 
             if let Some(types) = &package.types {
-                for t_name in types.keys() {
+                for (t_name, t_type) in types {
+                    let properties = t_type
+                        .properties
+                        .iter()
+                        .map(|p| match p.optional {
+                            Some(true) if !p.data_type.ends_with('?') => (p.name.clone(), format!("{}?", p.data_type)),
+                            _ => (p.name.clone(), p.data_type.clone()),
+                        })
+                        .collect();
+
+                    // Optional properties without a default value are left unit.
+                    let defaults = t_type
+                        .properties
+                        .iter()
+                        .filter_map(|p| match (&p.default, p.optional) {
+                            (Some(default), _) => Some((p.name.clone(), default.clone())),
+                            (None, Some(true)) => Some((p.name.clone(), Value::Unit)),
+                            (None, _) if p.data_type.ends_with('?') => Some((p.name.clone(), Value::Unit)),
+                            _ => None,
+                        })
+                        .collect();
+
                     let class = Class {
                         name: t_name.clone(),
+                        properties,
+                        defaults,
                         methods: Default::default(),
                     };
 
@@ -810,6 +1060,18 @@ where
         }
     }
 
+    ///
+    ///
+    ///
+    #[inline]
+    pub fn op_jump_if_unit(&mut self) {
+        if self.stack.peek_unit() {
+            self.op_jump();
+        } else {
+            self.frame().ip += 2;
+        }
+    }
+
     ///
     ///
     ///
@@ -901,7 +1163,50 @@ where
             }
         });
 
-        if let Some(Object::Class(_)) = self.heap.get(class) {
+        if let Some(Object::Class(c)) = self.heap.get(class) {
+            let name = c.name.clone();
+            let declared = c.properties.clone();
+            let defaults = c.defaults.clone();
+
+            // Classes without declared properties (e.g. built-ins) are left unchecked.
+            if !declared.is_empty() {
+                // Invalid instances can be caught by the script, like a failed function.
+                if let Some(unknown) = properties.keys().find(|p| !declared.contains_key(*p)) {
+                    let error = anyhow!("Class '{}' has no property '{}'.", name, unknown);
+                    self.throw(builtins::error(&error));
+                    return;
+                }
+
+                for (property, data_type) in &declared {
+                    if let Some(value) = properties.get(property) {
+                        let value = match self.check_type(*value, data_type) {
+                            Some(value) => value,
+                            None => {
+                                let error = anyhow!(
+                                    "Property '{}' of class '{}' expects a value of type '{}'.",
+                                    property,
+                                    name,
+                                    data_type
+                                );
+                                self.throw(builtins::error(&error));
+                                return;
+                            }
+                        };
+
+                        properties.insert(property.clone(), value);
+                    } else if let Some(default) = defaults.get(property) {
+                        let value = Slot::from_value(default.clone(), &self.globals, &mut self.heap);
+                        properties.insert(property.clone(), value);
+                    } else if data_type.ends_with('?') {
+                        properties.insert(property.clone(), Slot::Unit);
+                    } else {
+                        let error = anyhow!("Missing property '{}' for class '{}'.", property, name);
+                        self.throw(builtins::error(&error));
+                        return;
+                    }
+                }
+            }
+
             let instance = Instance::new(class, properties);
             let instance = self.heap.insert(Object::Instance(instance)).into_handle();
 
@@ -912,6 +1217,66 @@ where
         panic!("Invalid");
     }
 
+    ///
+    ///
+    ///
+    fn check_type(
+        &self,
+        value: Slot,
+        data_type: &str,
+    ) -> Option<Slot> {
+        // Optional types also accept unit, which represents the absence of a value.
+        if let Some(data_type) = data_type.strip_suffix('?') {
+            return match value {
+                Slot::Unit => Some(value),
+                value => self.check_type(value, data_type),
+            };
+        }
+
+        // Integers are accepted where a real is expected, but are converted.
+        let value = match (value, data_type) {
+            (Slot::Integer(i), "real") => Slot::Real(i as f64),
+            (Slot::ConstMinusTwo, "real") => Slot::Real(-2.0),
+            (Slot::ConstMinusOne, "real") => Slot::Real(-1.0),
+            (Slot::ConstZero, "real") => Slot::Real(0.0),
+            (Slot::ConstOne, "real") => Slot::Real(1.0),
+            (Slot::ConstTwo, "real") => Slot::Real(2.0),
+            (value, _) => value,
+        };
+
+        let valid = match (value, data_type) {
+            (_, "any") => true,
+            (Slot::True, "boolean") | (Slot::False, "boolean") => true,
+            (Slot::Integer(_), "integer") => true,
+            (Slot::ConstMinusTwo, "integer") | (Slot::ConstMinusOne, "integer") | (Slot::ConstZero, "integer") => true,
+            (Slot::ConstOne, "integer") | (Slot::ConstTwo, "integer") => true,
+            (Slot::Real(_), "real") => true,
+            (Slot::Unit, "unit") => true,
+            (Slot::Object(handle), data_type) => match self.heap.get(handle) {
+                Some(Object::String(_)) => data_type == "string",
+                Some(Object::Array(array)) => match data_type.strip_suffix("[]") {
+                    Some(element_type) => array
+                        .elements
+                        .iter()
+                        .all(|e| self.check_type(*e, element_type).is_some()),
+                    None => false,
+                },
+                Some(Object::Instance(instance)) => match self.heap.get(instance.class) {
+                    Some(Object::Class(class)) => class.name == data_type,
+                    _ => false,
+                },
+                _ => false,
+            },
+            _ => false,
+        };
+
+        if valid {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
     ///
     ///
     ///
@@ -954,7 +1319,10 @@ where
         } else {
             let executor = self.executor.clone();
             let package_index = self.package_index.clone();
-            let state = self.capture_state();
+            let mut state = self.capture_state();
+
+            // Branches don't share the retry blocks, but should respect the current policy.
+            state.options.retry = self.retry_policy();
 
             let results = branches
                 .into_par_iter()
@@ -963,9 +1331,25 @@ where
 
                     // TEMP: needed because the VM is not completely `send`.
                     let rt = Runtime::new().unwrap();
-                    rt.block_on(vm.anonymous(f))
+                    let value = rt.block_on(vm.anonymous(f));
+
+                    match vm.take_error() {
+                        Some(error) => Err(error),
+                        None => Ok(value),
+                    }
                 })
-                .collect::<Vec<_>>()
+                .collect::<Vec<_>>();
+
+            // An uncaught error in a branch is raised again, so it can be caught outside the parallel block.
+            let results = match results.into_iter().collect::<Result<Vec<_>, _>>() {
+                Ok(results) => results,
+                Err(error) => {
+                    self.throw(error);
+                    return;
+                }
+            };
+
+            let results = results
                 .into_iter()
                 .map(|v| Slot::from_value(v, &self.globals, &mut self.heap))
                 .collect();
@@ -979,6 +1363,50 @@ where
         self.stack.push_object(array);
     }
 
+    ///
+    ///
+    ///
+    #[inline]
+    pub fn op_param(&mut self) {
+        let name = self.stack.pop_object();
+        let data_type = self.stack.pop_object();
+        let default = self.stack.pop();
+
+        let name = self
+            .heap
+            .get(name)
+            .and_then(|n| n.as_string())
+            .cloned()
+            .expect("expecting string.");
+        let data_type = self
+            .heap
+            .get(data_type)
+            .and_then(|t| t.as_string())
+            .cloned()
+            .expect("expecting string.");
+
+        // Provided values take precedence over the declared default.
+        let value = if let Some(value) = self.params.get(&name).cloned() {
+            let value = Slot::from_value(value, &self.globals, &mut self.heap);
+            match self.check_type(value, &data_type) {
+                Some(value) => value,
+                None => {
+                    let error = anyhow!("Parameter '{}' expects a value of type '{}'.", name, data_type);
+                    self.throw(builtins::error(&error));
+                    return;
+                }
+            }
+        } else if !matches!(default, Slot::Unit) || data_type.ends_with('?') {
+            default
+        } else {
+            let error = anyhow!("Missing value for parameter '{}'.", name);
+            self.throw(builtins::error(&error));
+            return;
+        };
+
+        self.globals.insert(name, value);
+    }
+
     ///
     ///
     ///
@@ -1012,6 +1440,11 @@ where
             self.stack.clear_from(frame.stack_offset);
             self.stack.try_push(return_value);
         }
+
+        // Handlers and retry policies of the returning function no longer apply.
+        let frames = self.frames.len();
+        self.handlers.retain(|h| h.frames <= frames);
+        self.retries.retain(|(f, _)| *f <= frames);
     }
 
     ///
@@ -1021,8 +1454,9 @@ where
     pub fn op_set_global(
         &mut self,
         create_if_not_exists: bool,
+        long: bool,
     ) {
-        let identifier = *self.frame().read_constant().expect("Failed to read constant.");
+        let identifier = self.read_constant(long);
 
         let value = self.stack.pop();
 
@@ -1045,9 +1479,12 @@ where
     ///
     ///
     #[inline]
-    pub fn op_set_local(&mut self) {
-        let index = *self.frame().read_u8().expect("Failed to read byte.");
-        let index = self.frame().stack_offset + index as usize;
+    pub fn op_set_local(
+        &mut self,
+        long: bool,
+    ) {
+        let index = self.read_operand(long);
+        let index = self.frame().stack_offset + index;
 
         self.stack.copy_pop(index);
     }
@@ -1069,6 +1506,57 @@ where
         };
     }
 
+    ///
+    ///
+    ///
+    #[inline]
+    pub fn op_retry_pop(&mut self) {
+        self.retries.pop();
+    }
+
+    ///
+    ///
+    ///
+    #[inline]
+    pub fn op_retry_push(&mut self) {
+        let backoff = self.stack.pop();
+        let retries = self.stack.pop();
+
+        // Negative values don't make sense, treat them as zero.
+        let retries = match retries {
+            Slot::Integer(retries) => max(retries, 0) as u32,
+            _ => {
+                let error = anyhow!("The number of retries must be an integer.");
+                self.throw(builtins::error(&error));
+                return;
+            }
+        };
+        let backoff = match backoff {
+            Slot::Unit => self.options.retry.backoff,
+            Slot::Integer(backoff) => Duration::from_secs(max(backoff, 0) as u64),
+            Slot::Real(backoff) if backoff.is_finite() => Duration::from_secs_f64(backoff.max(0.0)),
+            _ => {
+                let error = anyhow!("The backoff of a retry must be a finite number of seconds.");
+                self.throw(builtins::error(&error));
+                return;
+            }
+        };
+
+        self.retries
+            .push((self.frames.len(), RetryPolicy::new(retries, backoff)));
+    }
+
+    ///
+    ///
+    ///
+    fn retry_policy(&self) -> RetryPolicy {
+        // The innermost retry block takes precedence over the default policy.
+        self.retries
+            .last()
+            .map(|(_, policy)| *policy)
+            .unwrap_or(self.options.retry)
+    }
+
     ///
     ///
     ///
@@ -1077,6 +1565,59 @@ where
         self.stack.push(Slot::True);
     }
 
+    ///
+    ///
+    ///
+    #[inline]
+    pub fn op_try(&mut self) {
+        let offset = self.frame().read_u16();
+        let ip = self.frame().ip + offset as usize;
+
+        let handler = Handler::new(
+            self.frames.len(),
+            ip,
+            self.locations.len(),
+            self.retries.len(),
+            self.stack.len(),
+        );
+        self.handlers.push(handler);
+    }
+
+    ///
+    ///
+    ///
+    #[inline]
+    pub fn op_try_end(&mut self) {
+        self.handlers.pop();
+    }
+
+    ///
+    ///
+    ///
+    fn throw(
+        &mut self,
+        error: Value,
+    ) {
+        let handler = match self.handlers.pop() {
+            Some(handler) => handler,
+            None => {
+                // Execution stops after the current instruction.
+                self.error = Some(error);
+                return;
+            }
+        };
+
+        // Unwind to where the handler was registered, then continue at the catch block.
+        self.frames.truncate(handler.frames);
+        self.locations.truncate(handler.locations);
+        self.retries.truncate(handler.retries);
+        self.stack.clear_from(handler.stack_len);
+        self.frame().ip = handler.ip;
+
+        let error = Slot::from_value(error, &self.globals, &mut self.heap);
+        self.stack.push(error);
+    }
+
     ///
     ///
     ///
@@ -0,0 +1,131 @@
+mod common;
+
+use brane_bvm::bytecode::opcodes::*;
+use brane_bvm::bytecode::{ChunkMut, FunctionMut};
+use broom::Heap;
+use specifications::common::Value;
+use std::collections::HashMap;
+
+fn opcodes(chunk: &ChunkMut) -> Vec<u8> {
+    let mut opcodes = vec![];
+
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        let opcode = chunk.code[offset];
+        opcodes.push(opcode);
+
+        offset += 1 + operands_len(opcode);
+    }
+
+    opcodes
+}
+
+fn wide_script(n: usize) -> String {
+    let mut script = String::from("class Point {\n    x: integer;\n}\n\n{\n");
+
+    // Every value is a separate constant, and every variable a separate local.
+    for i in 0..n {
+        script.push_str(&format!("    let v{} := {};\n", i, 1000 + i));
+    }
+
+    let last = n - 1;
+    script.push_str(&format!("    v{} := v{} + v0;\n", last, last));
+    script.push_str(&format!(
+        "    let values := [{}];\n",
+        (0..n).map(|i| format!("v{}", i)).collect::<Vec<_>>().join(", ")
+    ));
+    script.push_str(&format!("    let point := new Point {{ x := values[{}] }};\n", last));
+    script.push_str("    print(point.x);\n");
+    script.push_str("    print(values[0]);\n");
+    script.push_str("}\n");
+
+    script
+}
+
+#[test]
+fn wide_operands_are_generated() {
+    let function = common::compile(&wide_script(300), false).unwrap();
+
+    assert!(function.chunk.constants.len() > 256);
+
+    let opcodes = opcodes(&function.chunk);
+    for opcode in [
+        OP_CONSTANT_LONG,
+        OP_GET_LOCAL_LONG,
+        OP_SET_LOCAL_LONG,
+        OP_ARRAY_LONG,
+        OP_GET_GLOBAL_LONG,
+        OP_GET_PROPERTY_LONG,
+    ] {
+        assert!(opcodes.contains(&opcode), "missing opcode {:#04x}", opcode);
+    }
+}
+
+#[test]
+fn wide_operands_are_executed() {
+    let expected = vec![String::from("2299"), String::from("1000")];
+
+    assert_eq!(common::run(&wide_script(300), false).unwrap(), expected);
+    assert_eq!(common::run(&wide_script(300), true).unwrap(), expected);
+}
+
+#[test]
+fn jump_overflow_is_an_error() {
+    let mut script = String::from("let x := true;\nif (x) {\n");
+    for _ in 0..10000 {
+        script.push_str("    print(1);\n");
+    }
+    script.push_str("}\n");
+
+    let error = common::compile(&script, false).err().expect("expecting an error.");
+    assert!(error.to_string().contains("exceeds the maximum"), "{}", error);
+}
+
+#[test]
+fn constants_are_frozen() {
+    let mut properties = HashMap::new();
+    properties.insert(String::from("x"), Value::Integer(1));
+
+    let constants = vec![
+        Value::Unit,
+        Value::Array {
+            data_type: String::from("integer[]"),
+            entries: vec![Value::Integer(1), Value::Unit],
+        },
+        Value::Struct {
+            data_type: String::from("Point"),
+            properties,
+        },
+    ];
+
+    let mut chunk = ChunkMut::default();
+    for constant in &constants {
+        let constant = chunk.add_constant(constant.clone());
+        chunk.write_operand(OP_CONSTANT, constant).unwrap();
+    }
+
+    let mut heap = Heap::default();
+    let function = FunctionMut::main(chunk).freeze(&mut heap).unwrap();
+
+    for (slot, constant) in function.chunk.constants.iter().zip(constants) {
+        let value = slot.into_value(&heap);
+        assert_eq!(format!("{:?}", value), format!("{:?}", constant));
+    }
+}
+
+#[test]
+fn pointer_constants_are_an_error() {
+    let pointer = Value::Pointer {
+        data_type: String::from("integer"),
+        variable: String::from("x"),
+        secret: false,
+    };
+
+    let mut chunk = ChunkMut::default();
+    let constant = chunk.add_constant(pointer);
+    chunk.write_operand(OP_CONSTANT, constant).unwrap();
+
+    let mut heap = Heap::default();
+    let error = FunctionMut::main(chunk).freeze(&mut heap).unwrap_err();
+    assert!(error.to_string().contains("can't be used as a constant"), "{}", error);
+}
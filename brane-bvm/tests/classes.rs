@@ -0,0 +1,80 @@
+mod common;
+
+const POINT: &str = r#"
+    class Point {
+        x: real;
+        y: real := 0.0;
+
+        func norm(self) {
+            return self.x * self.x + self.y * self.y;
+        }
+    }
+"#;
+
+fn catch(statement: &str) -> Vec<String> {
+    let input = format!(
+        "{}\ntry {{\n{}\nprint(\"unreachable\");\n}} catch (error) {{\nprint(error.message);\n}}",
+        POINT, statement
+    );
+
+    common::run(&input, false).unwrap()
+}
+
+#[test]
+fn defaults_and_conversions_are_applied() {
+    let input = format!(
+        "{}\nlet p := new Point {{ x := 3 }};\nprint(p.x);\nprint(p.norm());",
+        POINT
+    );
+
+    assert_eq!(common::run(&input, false).unwrap(), vec!["3", "9"]);
+}
+
+#[test]
+fn unknown_property_is_an_error() {
+    let output = catch("let p := new Point { x := 1.0, z := 1.0 };");
+    assert_eq!(output, vec!["Class 'Point' has no property 'z'."]);
+}
+
+#[test]
+fn missing_property_is_an_error() {
+    let output = catch("let p := new Point { y := 1.0 };");
+    assert_eq!(output, vec!["Missing property 'x' for class 'Point'."]);
+}
+
+#[test]
+fn ill_typed_property_is_an_error() {
+    let output = catch("let p := new Point { x := \"one\" };");
+    assert_eq!(
+        output,
+        vec!["Property 'x' of class 'Point' expects a value of type 'real'."]
+    );
+}
+
+#[test]
+fn impl_of_unknown_class_is_an_error() {
+    let input = r#"
+        impl Unknown {
+            func f(self) {
+                return 1;
+            }
+        }
+        print("unreachable");
+    "#;
+
+    let executor = common::execute(input, false).unwrap();
+    assert!(executor.stdout.lock().unwrap().is_empty());
+
+    let stderr = executor.stderr.lock().unwrap();
+    assert!(stderr[0].contains("unknown class 'Unknown'"), "{}", stderr[0]);
+}
+
+#[test]
+fn impl_extends_existing_instances() {
+    let input = format!(
+        "{}\nlet p := new Point {{ x := 1.0 }};\nimpl Point {{\nfunc twice(self) {{\nreturn self.x * 2;\n}}\n}}\nprint(p.twice());",
+        POINT
+    );
+
+    assert_eq!(common::run(&input, false).unwrap(), vec!["2"]);
+}
@@ -0,0 +1,147 @@
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use brane_bvm::executor::{LocationConstraint, RetryPolicy, ServiceState, VmExecutor};
+use brane_bvm::vm::Vm;
+use brane_dsl::{Compiler, CompilerOptions, Lang};
+use fnv::FnvHashMap;
+use specifications::common::{Function, FunctionExt, Value};
+use specifications::package::{PackageIndex, PackageInfo};
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+#[allow(dead_code)]
+#[derive(Clone, Default)]
+pub struct TestExecutor {
+    pub retries: Arc<Mutex<Vec<RetryPolicy>>>,
+    pub stderr: Arc<Mutex<Vec<String>>>,
+    pub stdout: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl VmExecutor for TestExecutor {
+    async fn call(
+        &self,
+        _: FunctionExt,
+        _: HashMap<String, Value>,
+        _: Option<LocationConstraint>,
+        retry: RetryPolicy,
+    ) -> Result<Value> {
+        // Calls always fail, but record the retry policy they were made with.
+        self.retries.lock().unwrap().push(retry);
+        bail!("External function calls not supported.");
+    }
+
+    async fn debug(
+        &self,
+        _: String,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn stderr(
+        &self,
+        text: String,
+    ) -> Result<()> {
+        self.stderr.lock().unwrap().push(text);
+        Ok(())
+    }
+
+    async fn stdout(
+        &self,
+        text: String,
+    ) -> Result<()> {
+        self.stdout.lock().unwrap().push(text);
+        Ok(())
+    }
+
+    async fn wait_until(
+        &self,
+        _: String,
+        _: ServiceState,
+    ) -> Result<()> {
+        bail!("External function calls not supported.");
+    }
+
+    async fn stop_service(
+        &self,
+        _: String,
+    ) -> Result<()> {
+        bail!("External function calls not supported.");
+    }
+}
+
+#[allow(dead_code)]
+pub fn compile(
+    input: &str,
+    optimize: bool,
+) -> Result<brane_bvm::bytecode::FunctionMut> {
+    let options = if optimize {
+        CompilerOptions::optimized(Lang::BraneScript)
+    } else {
+        CompilerOptions::new(Lang::BraneScript)
+    };
+
+    let mut compiler = Compiler::new(options, package_index());
+    compiler.compile(input)
+}
+
+#[allow(dead_code)]
+pub fn package_index() -> PackageIndex {
+    // A single package, with a function that doesn't take arguments.
+    let mut functions = HashMap::new();
+    functions.insert(
+        String::from("fail"),
+        Function::new(vec![], None, String::from("unit"), None, None),
+    );
+
+    let package = PackageInfo::new(
+        String::from("test"),
+        String::from("1.0.0"),
+        String::new(),
+        false,
+        String::from("ecu"),
+        vec![],
+        Some(functions),
+        None,
+    );
+
+    PackageIndex::from_packages(vec![package]).unwrap()
+}
+
+#[allow(dead_code)]
+pub fn execute(
+    input: &str,
+    optimize: bool,
+) -> Result<TestExecutor> {
+    execute_with_params(input, optimize, FnvHashMap::default())
+}
+
+#[allow(dead_code)]
+pub fn execute_with_params(
+    input: &str,
+    optimize: bool,
+    params: FnvHashMap<String, Value>,
+) -> Result<TestExecutor> {
+    let function = compile(input, optimize)?;
+    let executor = TestExecutor::default();
+
+    // A panicking VM is reported as an error, so it can be compared as well.
+    let mut vm = Vm::new_with(executor.clone(), Some(package_index()), None);
+    vm.set_params(params);
+    panic::catch_unwind(AssertUnwindSafe(|| futures::executor::block_on(vm.main(function))))
+        .map_err(|_| anyhow!("VM panicked."))?;
+
+    Ok(executor)
+}
+
+#[allow(dead_code)]
+pub fn run(
+    input: &str,
+    optimize: bool,
+) -> Result<Vec<String>> {
+    let executor = execute(input, optimize)?;
+
+    let stdout = executor.stdout.lock().unwrap().clone();
+    Ok(stdout)
+}
@@ -0,0 +1,51 @@
+mod common;
+
+#[test]
+fn uncaught_error_aborts_script() {
+    let input = r#"
+        print("before");
+        waitUntilStarted(1);
+        print("after");
+    "#;
+
+    let executor = common::execute(input, false).unwrap();
+
+    assert_eq!(*executor.stdout.lock().unwrap(), vec![String::from("before")]);
+
+    let stderr = executor.stderr.lock().unwrap();
+    assert_eq!(stderr.len(), 1);
+    assert!(stderr[0].starts_with("Uncaught error: "), "{}", stderr[0]);
+}
+
+#[test]
+fn caught_error_continues_script() {
+    let input = r#"
+        try {
+            waitUntilStarted(1);
+            print("unreachable");
+        } catch (error) {
+            print(error.reason);
+        }
+        print("after");
+    "#;
+
+    assert_eq!(common::run(input, false).unwrap(), vec!["error", "after"]);
+}
+
+#[test]
+fn parallel_error_is_raised_again() {
+    let input = r#"
+        try {
+            let results := parallel [{
+                return 1;
+            }, {
+                waitUntilStarted(1);
+            }];
+            print("unreachable");
+        } catch (error) {
+            print(error.reason);
+        }
+    "#;
+
+    assert_eq!(common::run(input, false).unwrap(), vec!["error"]);
+}
@@ -0,0 +1,56 @@
+mod common;
+
+fn assert_same_output(input: &str) {
+    let optimized = common::run(input, true).map_err(|e| e.to_string());
+    let unoptimized = common::run(input, false).map_err(|e| e.to_string());
+
+    assert_eq!(optimized, unoptimized, "{}", input);
+}
+
+#[test]
+fn folded_division_by_zero_matches_vm() {
+    assert_same_output("print(1 / 0);");
+    assert_same_output("let x := 1; print(x / 0);");
+    assert_same_output("print(1.5 / 0);");
+    assert_same_output("let x := 1.5; print(x / 0);");
+    assert_same_output("print(-1 / 0.0);");
+
+    // Integer division by zero is left to the VM.
+    assert!(common::run("print(1 / 0);", true).is_err());
+}
+
+#[test]
+fn folded_mixed_operands_match_vm() {
+    assert_same_output("print(1 + 2.5);");
+    assert_same_output("print(3 - 1.5 * 2);");
+    assert_same_output("print(1 / 2.0);");
+    assert_same_output("print([1 == 1.0, 1 != 1.0, 1 < 1.5, 2 > 1.5, 1 <= 1.0, 2 >= 2.5]);");
+
+    // Comparisons with NaN.
+    assert_same_output("print([0.0 / 0 <= 1, 0.0 / 0 >= 1, 0.0 / 0 < 1, 0.0 / 0 > 1]);");
+    assert_eq!(
+        common::run("print(0.0 / 0 <= 1);", true).unwrap(),
+        vec![String::from("true")]
+    );
+}
+
+#[test]
+fn folded_logical_operators_keep_side_effects() {
+    let input = r#"
+        let calls := 0;
+        func f() {
+            calls := calls + 1;
+            return true;
+        }
+
+        let a := false & f();
+        let b := true | f();
+        print([a, b, calls]);
+    "#;
+
+    assert_same_output(input);
+    assert_eq!(
+        common::run(input, true).unwrap(),
+        common::run("print([false, true, 2]);", false).unwrap()
+    );
+}
@@ -0,0 +1,85 @@
+mod common;
+
+const RESULT: &str = r#"
+    class Result {
+        output: string?;
+        code: integer;
+    }
+"#;
+
+#[test]
+fn safe_navigation_on_unit_is_unit() {
+    let input = format!("{}\nlet r := unit;\nprint(r?.output);", RESULT);
+
+    assert_eq!(common::run(&input, false).unwrap(), vec!["unit"]);
+}
+
+#[test]
+fn safe_navigation_on_missing_property_is_unit() {
+    // Built-in classes don't declare their properties.
+    let input = r#"
+        let s := new Service { identifier := "abc" };
+        print(s?.identifier);
+        print(s?.missing);
+    "#;
+
+    assert_eq!(common::run(input, false).unwrap(), vec!["abc", "unit"]);
+}
+
+#[test]
+fn missing_property_is_an_error() {
+    let input = r#"
+        let s := new Service { identifier := "abc" };
+        try {
+            print(s.missing);
+        } catch (error) {
+            print(error.message);
+        }
+    "#;
+
+    assert_eq!(
+        common::run(input, false).unwrap(),
+        vec!["Instance of class 'Service' has no property 'missing'."]
+    );
+}
+
+#[test]
+fn unset_optional_property_is_unit() {
+    let input = format!(
+        "{}\nlet r := new Result {{ code := 0 }};\nprint(r.output);\nprint(r.output ?? \"none\");",
+        RESULT
+    );
+
+    assert_eq!(common::run(&input, false).unwrap(), vec!["unit", "none"]);
+}
+
+#[test]
+fn coalesce_only_evaluates_fallback_for_unit() {
+    let input = r#"
+        let calls := 0;
+        func fallback() {
+            calls := calls + 1;
+            return 2;
+        }
+
+        let a := unit;
+        let b := 1;
+        print(a ?? fallback());
+        print(b ?? fallback());
+        print(calls);
+    "#;
+
+    for optimize in [false, true] {
+        assert_eq!(common::run(input, optimize).unwrap(), vec!["2", "1", "1"]);
+    }
+}
+
+#[test]
+fn safe_navigation_with_coalesce() {
+    let input = format!(
+        "{}\nlet r := new Result {{ code := 0 }};\nlet n := unit;\nprint(n?.output ?? \"none\");\nprint(r?.output ?? \"none\");\nprint(r?.code ?? 1);",
+        RESULT
+    );
+
+    assert_eq!(common::run(&input, false).unwrap(), vec!["none", "none", "0"]);
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use brane_dsl::{Compiler, CompilerOptions, Lang};
+use fnv::FnvHashMap;
+use specifications::common::Value;
+use specifications::package::PackageIndex;
+use std::collections::HashMap;
+
+const INPUT: &str = r#"
+    param name: string;
+    param count: integer := 1;
+    param label: string?;
+
+    print(name);
+    print(count);
+    print(label ?? "none");
+"#;
+
+fn execute(params: Vec<(&str, Value)>) -> common::TestExecutor {
+    let params: FnvHashMap<String, Value> = params.into_iter().map(|(n, v)| (n.to_string(), v)).collect();
+    common::execute_with_params(INPUT, false, params).unwrap()
+}
+
+#[test]
+fn provided_and_default_values_are_used() {
+    let executor = execute(vec![("name", Value::Unicode(String::from("brane")))]);
+    assert_eq!(*executor.stdout.lock().unwrap(), vec!["brane", "1", "none"]);
+}
+
+#[test]
+fn ill_typed_parameter_is_an_error() {
+    let executor = execute(vec![
+        ("name", Value::Unicode(String::from("brane"))),
+        ("count", Value::Unicode(String::from("many"))),
+    ]);
+
+    assert!(executor.stdout.lock().unwrap().is_empty());
+
+    let stderr = executor.stderr.lock().unwrap();
+    assert_eq!(stderr.len(), 1);
+    assert!(
+        stderr[0].contains("Parameter 'count' expects a value of type 'integer'."),
+        "{}",
+        stderr[0]
+    );
+}
+
+#[test]
+fn missing_parameter_is_an_error() {
+    let executor = execute(vec![]);
+
+    assert!(executor.stdout.lock().unwrap().is_empty());
+
+    let stderr = executor.stderr.lock().unwrap();
+    assert_eq!(stderr.len(), 1);
+    assert!(
+        stderr[0].contains("Missing value for parameter 'name'."),
+        "{}",
+        stderr[0]
+    );
+}
+
+#[test]
+fn command_line_values_are_parsed_as_declared_type() {
+    let input = r#"
+        param code: string;
+        param count: integer;
+        param origin: Point;
+        param enabled: boolean?;
+    "#;
+
+    let mut compiler = Compiler::new(CompilerOptions::new(Lang::BraneScript), PackageIndex::empty());
+    compiler.compile(input).unwrap();
+
+    let values = vec![
+        ("code", "007"),
+        ("count", "7"),
+        ("origin", "{x: 1, y: 2.5}"),
+        ("enabled", "true"),
+    ];
+    let values = values
+        .into_iter()
+        .map(|(n, v)| (n.to_string(), Value::Unicode(v.to_string())))
+        .collect();
+
+    let params = compiler.bind_parameters(values).unwrap();
+    assert_eq!(params["code"], Value::Unicode(String::from("007")));
+    assert_eq!(params["count"], Value::Integer(7));
+    assert_eq!(params["origin"].data_type(), "Point");
+    assert_eq!(params["enabled"], Value::Boolean(true));
+
+    let mut values = HashMap::new();
+    values.insert(String::from("code"), Value::Unicode(String::from("007")));
+    values.insert(String::from("count"), Value::Unicode(String::from("yes")));
+    values.insert(String::from("origin"), Value::Unicode(String::from("{x: 1}")));
+    assert!(compiler.bind_parameters(values).is_err());
+}
+
+#[test]
+fn empty_arrays_get_the_declared_type() {
+    let value = Value::Unicode(String::from("[]")).coerce("integer[]").unwrap();
+    match value {
+        Value::Array { data_type, entries } => {
+            assert_eq!(data_type, "integer[]");
+            assert!(entries.is_empty());
+        }
+        value => panic!("Expecting an array, but got: {:?}", value),
+    }
+
+    let value = Value::Unicode(String::from("[]")).coerce("string[]?").unwrap();
+    assert_eq!(value.data_type(), "string[]");
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use brane_bvm::executor::RetryPolicy;
+use std::time::Duration;
+
+fn retries(input: &str) -> (Vec<RetryPolicy>, Vec<String>) {
+    let input = format!("import test;\n{}", input);
+    let executor = common::execute(&input, false).unwrap();
+
+    let retries = executor.retries.lock().unwrap().clone();
+    let stdout = executor.stdout.lock().unwrap().clone();
+    (retries, stdout)
+}
+
+#[test]
+fn innermost_policy_applies() {
+    let input = r#"
+        retry (1, 2) {
+            retry (3) {
+                try { fail(); } catch (error) { }
+            }
+            try { fail(); } catch (error) { }
+        }
+        try { fail(); } catch (error) { }
+    "#;
+
+    let (retries, _) = retries(input);
+    assert_eq!(
+        retries,
+        vec![
+            RetryPolicy::new(3, Duration::default()),
+            RetryPolicy::new(1, Duration::from_secs(2)),
+            RetryPolicy::default(),
+        ]
+    );
+}
+
+#[test]
+fn policy_ends_with_returning_function() {
+    let input = r#"
+        func attempt() {
+            retry (3, 1) {
+                return 1;
+            }
+        }
+
+        attempt();
+        try { fail(); } catch (error) { }
+    "#;
+
+    let (retries, _) = retries(input);
+    assert_eq!(retries, vec![RetryPolicy::default()]);
+}
+
+#[test]
+fn real_backoff_is_converted() {
+    let input = r#"
+        retry (2, 0.5) {
+            try { fail(); } catch (error) { }
+        }
+    "#;
+
+    let (retries, _) = retries(input);
+    assert_eq!(retries, vec![RetryPolicy::new(2, Duration::from_millis(500))]);
+}
+
+#[test]
+fn invalid_backoff_is_an_error() {
+    let input = r#"
+        try {
+            retry (2, "soon") {
+                fail();
+            }
+        } catch (error) {
+            print(error.message);
+        }
+        try { fail(); } catch (error) { }
+    "#;
+
+    let (retries, stdout) = retries(input);
+    assert_eq!(retries, vec![RetryPolicy::default()]);
+    assert_eq!(
+        stdout,
+        vec!["The backoff of a retry must be a finite number of seconds."]
+    );
+}
@@ -10,6 +10,7 @@ use url::Url;
 #[derive(Clone, Debug, Deserialize, Default)]
 pub struct InfrastructureDocument {
     locations: HashMap<String, Location>,
+    planner: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -23,6 +24,14 @@ pub enum Location {
         credentials: LocationCredentials,
         proxy_address: Option<String>,
         mount_dfs: Option<String>,
+        #[serde(default)]
+        security: SecurityPolicy,
+        #[serde(default)]
+        datasets: HashMap<String, Dataset>,
+        gpus: Option<u32>,
+        #[serde(default)]
+        labels: HashMap<String, String>,
+        storage: Option<Storage>,
     },
     Local {
         address: Option<String>,
@@ -31,6 +40,14 @@ pub enum Location {
         registry: String,
         proxy_address: Option<String>,
         mount_dfs: Option<String>,
+        #[serde(default)]
+        security: SecurityPolicy,
+        #[serde(default)]
+        datasets: HashMap<String, Dataset>,
+        gpus: Option<u32>,
+        #[serde(default)]
+        labels: HashMap<String, String>,
+        storage: Option<Storage>,
     },
     Vm {
         address: String,
@@ -40,6 +57,14 @@ pub enum Location {
         credentials: LocationCredentials,
         proxy_address: Option<String>,
         mount_dfs: Option<String>,
+        #[serde(default)]
+        security: SecurityPolicy,
+        #[serde(default)]
+        datasets: HashMap<String, Dataset>,
+        gpus: Option<u32>,
+        #[serde(default)]
+        labels: HashMap<String, String>,
+        storage: Option<Storage>,
     },
     Slurm {
         address: String,
@@ -49,6 +74,14 @@ pub enum Location {
         credentials: LocationCredentials,
         proxy_address: Option<String>,
         mount_dfs: Option<String>,
+        #[serde(default)]
+        security: SecurityPolicy,
+        #[serde(default)]
+        datasets: HashMap<String, Dataset>,
+        gpus: Option<u32>,
+        #[serde(default)]
+        labels: HashMap<String, String>,
+        storage: Option<Storage>,
     },
 }
 
@@ -70,6 +103,180 @@ impl Location {
             | Location::Local { registry, .. } => registry.clone(),
         }
     }
+
+    pub fn get_gpus(&self) -> u32 {
+        match self {
+            Location::Kube { gpus, .. }
+            | Location::Vm { gpus, .. }
+            | Location::Slurm { gpus, .. }
+            | Location::Local { gpus, .. } => gpus.unwrap_or_default(),
+        }
+    }
+
+    pub fn get_labels(&self) -> HashMap<String, String> {
+        match self {
+            Location::Kube { labels, .. }
+            | Location::Vm { labels, .. }
+            | Location::Slurm { labels, .. }
+            | Location::Local { labels, .. } => labels.clone(),
+        }
+    }
+
+    pub fn get_runtime(&self) -> String {
+        match self {
+            Location::Vm { runtime, .. } | Location::Slurm { runtime, .. } => runtime.to_lowercase(),
+            Location::Kube { .. } | Location::Local { .. } => String::from("docker"),
+        }
+    }
+
+    pub fn get_datasets(&self) -> HashMap<String, Dataset> {
+        match self {
+            Location::Kube { datasets, .. }
+            | Location::Vm { datasets, .. }
+            | Location::Slurm { datasets, .. }
+            | Location::Local { datasets, .. } => datasets.clone(),
+        }
+    }
+
+    pub fn get_security_policy(&self) -> SecurityPolicy {
+        match self {
+            Location::Kube { security, .. }
+            | Location::Vm { security, .. }
+            | Location::Slurm { security, .. }
+            | Location::Local { security, .. } => security.clone(),
+        }
+    }
+
+    pub fn get_storage(&self) -> Option<Storage> {
+        match self {
+            Location::Kube { storage, .. }
+            | Location::Vm { storage, .. }
+            | Location::Slurm { storage, .. }
+            | Location::Local { storage, .. } => storage.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Dataset {
+    pub source: String,
+    pub destination: String,
+    #[serde(default)]
+    pub kind: DatasetKind,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DatasetKind {
+    /// A path on the host (or node).
+    Bind,
+    /// A named volume, i.e. a Docker volume or a Kubernetes persistent volume claim.
+    Volume,
+}
+
+impl Default for DatasetKind {
+    fn default() -> Self {
+        DatasetKind::Bind
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Storage {
+    /// An S3-compatible endpoint (e.g., MinIO), or a `file://` directory.
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: Option<String>,
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+}
+
+impl Storage {
+    ///
+    ///
+    ///
+    pub fn resolve_secrets(
+        &self,
+        secrets: &Secrets,
+    ) -> Self {
+        let access_key = self.access_key.as_ref().map(|k| resolve_secret(k, secrets));
+        let secret_key = self.secret_key.as_ref().map(|k| resolve_secret(k, secrets));
+
+        Storage {
+            access_key,
+            secret_key,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SecurityPolicy {
+    pub allow_privileged: bool,
+    pub allowed_capabilities: Option<Vec<String>>,
+    pub allowed_devices: Option<Vec<String>>,
+}
+
+impl Default for SecurityPolicy {
+    fn default() -> Self {
+        // Without a policy, anything goes (as before security profiles existed).
+        SecurityPolicy {
+            allow_privileged: true,
+            allowed_capabilities: None,
+            allowed_devices: None,
+        }
+    }
+}
+
+impl SecurityPolicy {
+    ///
+    ///
+    ///
+    pub fn check(
+        &self,
+        capabilities: &[String],
+        devices: &[String],
+        privileged: bool,
+    ) -> Result<()> {
+        ensure!(
+            !privileged || self.allow_privileged,
+            "Privileged containers are not allowed at this location."
+        );
+
+        if let Some(allowed) = &self.allowed_capabilities {
+            let allowed: Vec<String> = allowed.iter().map(|c| normalize_capability(c)).collect();
+            for capability in capabilities {
+                ensure!(
+                    allowed.contains(&normalize_capability(capability)),
+                    "Capability '{}' is not allowed at this location.",
+                    capability
+                );
+            }
+        }
+
+        if let Some(allowed) = &self.allowed_devices {
+            for device in devices {
+                ensure!(
+                    allowed.contains(device),
+                    "Device '{}' is not allowed at this location.",
+                    device
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+///
+///
+///
+pub fn normalize_capability(capability: &str) -> String {
+    // Both 'SYS_ADMIN' and 'CAP_SYS_ADMIN' notations are common.
+    let capability = capability.to_uppercase();
+    capability.strip_prefix("CAP_").map(String::from).unwrap_or(capability)
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -99,16 +306,7 @@ impl LocationCredentials {
     ) -> Self {
         use LocationCredentials::*;
 
-        let resolve = |value: &String| {
-            // Try to resolve secret, but use the value as-is otherwise.
-            if let Some(value) = value.strip_prefix("s$") {
-                if let Ok(secret) = secrets.get(value) {
-                    return secret;
-                }
-            }
-
-            value.clone()
-        };
+        let resolve = |value: &String| resolve_secret(value, secrets);
 
         match self {
             Config { file } => {
@@ -141,6 +339,23 @@ impl LocationCredentials {
     }
 }
 
+///
+///
+///
+fn resolve_secret(
+    value: &str,
+    secrets: &Secrets,
+) -> String {
+    // Try to resolve secret, but use the value as-is otherwise.
+    if let Some(value) = value.strip_prefix("s$") {
+        if let Ok(secret) = secrets.get(value) {
+            return secret;
+        }
+    }
+
+    value.to_string()
+}
+
 #[derive(Clone, Debug)]
 pub struct Infrastructure {
     store: Store,
@@ -189,6 +404,20 @@ impl Infrastructure {
         }
     }
 
+    ///
+    ///
+    ///
+    pub fn get_planner(&self) -> Result<Option<String>> {
+        if let Store::File(store_file) = &self.store {
+            let infra_reader = BufReader::new(File::open(store_file)?);
+            let infra_document: InfrastructureDocument = serde_yaml::from_reader(infra_reader)?;
+
+            Ok(infra_document.planner)
+        } else {
+            unreachable!()
+        }
+    }
+
     ///
     ///
     ///
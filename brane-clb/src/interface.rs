@@ -53,4 +53,5 @@ pub enum CallbackKind {
     Finished = 5,
     Stopped = 6,
     Failed = 7,
+    Output = 8,
 }
@@ -1,4 +1,4 @@
-use crate::{docker, packages};
+use crate::{build_oci, docker, packages, ImageBuilder};
 use anyhow::{Context, Result};
 use console::style;
 use fs_extra::dir::CopyOptions;
@@ -19,6 +19,8 @@ const BRANELET_URL: &str = concat!(
     "/branelet"
 );
 
+const BASE_IMAGE: &str = "ubuntu:20.04";
+
 const JUICE_URL: &str =
     "https://github.com/juicedata/juicefs/releases/download/v0.12.1/juicefs-0.12.1-linux-amd64.tar.gz";
 
@@ -29,6 +31,8 @@ pub async fn handle(
     context: PathBuf,
     file: PathBuf,
     branelet_path: Option<PathBuf>,
+    juicefs_path: Option<PathBuf>,
+    builder: ImageBuilder,
     keep_files: bool,
 ) -> Result<()> {
     let context = fs::canonicalize(context)?;
@@ -38,8 +42,20 @@ pub async fn handle(
     let ecu_reader = BufReader::new(File::open(&ecu_file)?);
     let ecu_document = ContainerInfo::from_reader(ecu_reader)?;
 
+    // The OCI builder only assembles layers, it can't run commands inside the image.
+    if builder == ImageBuilder::Oci {
+        let dependencies = ecu_document.dependencies.as_ref().map(Vec::is_empty).unwrap_or(true);
+        let install = ecu_document.install.as_ref().map(Vec::is_empty).unwrap_or(true);
+        ensure!(
+            dependencies && install && ecu_document.build.is_none(),
+            "Packages with dependencies, install commands, or a build stage can only be built with the Docker builder."
+        );
+
+        warn!("The OCI builder doesn't install FUSE and iptables, the DFS and proxy need them in the base image.");
+    }
+
     // Prepare package directory
-    let dockerfile = generate_dockerfile(&ecu_document, branelet_path.is_some())?;
+    let dockerfile = generate_dockerfile(&ecu_document, branelet_path.is_some(), juicefs_path.is_some())?;
     let package_info = generate_package_info(&ecu_document)?;
     let package_dir = packages::get_package_dir(&package_info.name, Some(&package_info.version))?;
     prepare_directory(
@@ -47,6 +63,7 @@ pub async fn handle(
         &ecu_file,
         dockerfile,
         branelet_path,
+        juicefs_path,
         &context,
         &package_info,
         &package_dir,
@@ -54,9 +71,17 @@ pub async fn handle(
 
     debug!("Successfully prepared package directory.");
 
-    // Build Docker image
+    // Build Docker image, either with Docker or by assembling its layers directly.
     let tag = format!("{}:{}", package_info.name, package_info.version);
-    let result = build_docker_image(&package_dir, tag);
+    let result = if builder == ImageBuilder::Oci {
+        let base = ecu_document.base.clone().unwrap_or_else(|| String::from(BASE_IMAGE));
+        let environment = ecu_document.environment.clone().unwrap_or_default();
+        let exec = ecu_document.entrypoint.exec.as_str();
+
+        build_oci::build_image(&package_dir, tag, &base, &environment, Some(exec), BRANELET_URL, JUICE_URL).await
+    } else {
+        build_docker_image(&package_dir, tag)
+    };
 
     if result.is_ok() {
         println!(
@@ -65,20 +90,26 @@ pub async fn handle(
             style(&package_info.name).bold().cyan(),
         );
 
-        // Check if previous build is still loaded in Docker
-        let image_name = format!("{}:{}", package_info.name, package_info.version);
-        if let Err(e) = docker::remove_image(&image_name).await {
-            error!("Failed to remove previous image from docker:\n{:?}", e);
-        }
+        // Check if previous build is still loaded in Docker (the OCI builder doesn't use Docker).
+        if builder == ImageBuilder::Docker {
+            let image_name = format!("{}:{}", package_info.name, package_info.version);
+            if let Err(e) = docker::remove_image(&image_name).await {
+                error!("Failed to remove previous image from docker:\n{:?}", e);
+            }
 
-        let image_name = format!("localhost:5000/library/{}", image_name);
-        if let Err(e) = docker::remove_image(&image_name).await {
-            error!("Failed to remove previous image from docker:\n{:?}", e);
+            let image_name = format!("localhost:5000/library/{}", image_name);
+            if let Err(e) = docker::remove_image(&image_name).await {
+                error!("Failed to remove previous image from docker:\n{:?}", e);
+            }
         }
 
         // Remove all non-essential files.
         clean_directory(&package_dir, keep_files)?;
     } else {
+        if let Err(error) = result {
+            error!("{:?}", error);
+        }
+
         println!(
             "Failed to built version {} of container (ECU) package {}. See error output above.",
             style(&package_info.version).bold().cyan(),
@@ -108,7 +139,9 @@ fn generate_package_info(container_info: &ContainerInfo) -> Result<PackageInfo>
             String::from("unit")
         };
 
-        let function = Function::new(arguments, pattern, return_type);
+        // The security profile is declared per package, but kept per function (like resources).
+        let security = container_info.security();
+        let function = Function::new(arguments, pattern, return_type, action.resources.clone(), security);
         functions.insert(action_name.clone(), function);
     }
 
@@ -133,40 +166,56 @@ fn generate_package_info(container_info: &ContainerInfo) -> Result<PackageInfo>
 fn generate_dockerfile(
     ecu_document: &ContainerInfo,
     override_branelet: bool,
+    override_juicefs: bool,
 ) -> Result<String> {
     let mut contents = String::new();
     let base = ecu_document
         .base
         .clone()
-        .unwrap_or_else(|| String::from("ubuntu:20.04"));
+        .unwrap_or_else(|| String::from(BASE_IMAGE));
+    let package_manager = determine_package_manager(&base, &ecu_document.package_manager)?;
 
     // Add default heading
     writeln!(contents, "# Generated by Brane")?;
-    writeln!(contents, "FROM {}", base)?;
 
-    // Add environemt variables
-    if let Some(environment) = &ecu_document.environment {
-        for (key, value) in environment {
-            writeln!(contents, "ENV {}={}", key, value)?;
+    // Artifacts are built in a separate stage, so build tools don't end up in the package.
+    if let Some(build) = &ecu_document.build {
+        let build_base = build.base.clone().unwrap_or_else(|| base.clone());
+        let build_package_manager = match &build.base {
+            Some(build_base) => determine_package_manager(build_base, &None)?,
+            None => package_manager,
+        };
+
+        writeln!(contents, "FROM {} AS build", build_base)?;
+        write_environment(&mut contents, ecu_document)?;
+        if let Some(dependencies) = &build.dependencies {
+            write_dependencies(&mut contents, build_package_manager, dependencies)?;
         }
-    }
 
-    // Add dependencies
-    if base.starts_with("alpine") {
-        write!(contents, "RUN apk add --no-cache ")?;
-    } else {
-        write!(contents, "RUN apt-get update && DEBIAN_FRONTEND=noninteractive apt-get install -y --allow-change-held-packages --allow-downgrades ")?;
+        writeln!(contents, "WORKDIR /opt/wd")?;
+        writeln!(contents, "ADD wd.tar.gz /opt")?;
+        for command in &build.commands {
+            writeln!(contents, "RUN {}", command)?;
+        }
+        writeln!(contents)?;
     }
 
-    // Default dependencies
-    write!(contents, "fuse iptables ")?;
-
-    if let Some(dependencies) = &ecu_document.dependencies {
-        for dependency in dependencies {
-            write!(contents, "{} ", dependency)?;
-        }
+    writeln!(contents, "FROM {}", base)?;
+    write_environment(&mut contents, ecu_document)?;
+
+    // Add dependencies, including the default ones, as these change least often.
+    let mut dependencies = vec![String::from("fuse"), String::from("iptables")];
+    let package_dependencies = ecu_document.dependencies.clone().unwrap_or_default();
+    if package_manager == "micromamba" {
+        // Micromamba images are Debian-based, and don't run as root by default.
+        writeln!(contents, "USER root")?;
+        write_dependencies(&mut contents, "apt", &dependencies)?;
+        write_dependencies(&mut contents, package_manager, &package_dependencies)?;
+        writeln!(contents, "ENV PATH=\"${{MAMBA_ROOT_PREFIX}}/bin:${{PATH}}\"")?;
+    } else {
+        dependencies.extend(package_dependencies);
+        write_dependencies(&mut contents, package_manager, &dependencies)?;
     }
-    writeln!(contents)?;
 
     // Add default branelet
     if override_branelet {
@@ -176,27 +225,48 @@ fn generate_dockerfile(
     }
     writeln!(contents, "RUN chmod +x branelet")?;
 
-    writeln!(contents, "ADD {} juicefs.tar.gz", JUICE_URL)?;
+    // A local tarball is copied, as ADD would already extract it.
+    if override_juicefs {
+        writeln!(contents, "COPY juicefs.tar.gz juicefs.tar.gz")?;
+    } else {
+        writeln!(contents, "ADD {} juicefs.tar.gz", JUICE_URL)?;
+    }
     writeln!(
         contents,
         "RUN tar -xzf juicefs.tar.gz && rm juicefs.tar.gz && mkdir /data"
     )?;
 
+    writeln!(contents, "WORKDIR /opt/wd")?;
+
+    // With explicit install files, installation is cached across changes to other files.
+    let install = ecu_document.install.clone().unwrap_or_default();
+    if let Some(install_files) = &ecu_document.install_files {
+        if !install_files.is_empty() {
+            writeln!(contents, "ADD install.tar.gz /opt")?;
+        }
+
+        write_install(&mut contents, &install)?;
+    }
+
     // Copy files
     writeln!(contents, "COPY container.yml /container.yml")?;
     writeln!(contents, "ADD wd.tar.gz /opt")?;
-    writeln!(contents, "WORKDIR /opt/wd")?;
+
+    if let Some(build) = &ecu_document.build {
+        for artifact in &build.artifacts {
+            let artifact = Path::new("/opt/wd").join(artifact.trim_start_matches("./"));
+            writeln!(contents, "COPY --from=build {} {}", artifact.display(), artifact.display())?;
+        }
+    }
 
     // Set execute bit
     // TODO: validate path and canonicalize
     let exec = ecu_document.entrypoint.exec.clone();
     writeln!(contents, "RUN chmod +x /opt/wd/{}", exec)?;
 
-    // Add installation
-    if let Some(install) = &ecu_document.install {
-        for line in install {
-            writeln!(contents, "RUN {}", line)?;
-        }
+    // Otherwise, install commands have the complete working directory available.
+    if ecu_document.install_files.is_none() {
+        write_install(&mut contents, &install)?;
     }
 
     writeln!(contents, "ENTRYPOINT [\"/branelet\"]")?;
@@ -204,6 +274,107 @@ fn generate_dockerfile(
     Ok(contents)
 }
 
+///
+///
+///
+fn determine_package_manager(
+    base: &str,
+    package_manager: &Option<String>,
+) -> Result<&'static str> {
+    if let Some(package_manager) = package_manager {
+        return match package_manager.as_str() {
+            "apk" => Ok("apk"),
+            "apt" | "apt-get" => Ok("apt"),
+            "dnf" => Ok("dnf"),
+            "micromamba" => Ok("micromamba"),
+            _ => Err(anyhow!("Unsupported package manager: {}", package_manager)),
+        };
+    }
+
+    // The image name, without registry, organization, or tag.
+    let name = base.rsplit('/').next().unwrap_or_default();
+    let name = name.split(':').next().unwrap_or_default();
+
+    let package_manager = if base.contains("alpine") {
+        "apk"
+    } else if base.contains("micromamba") {
+        "micromamba"
+    } else if ["fedora", "centos", "rockylinux", "almalinux", "ubi8", "ubi9"].contains(&name) {
+        "dnf"
+    } else {
+        "apt"
+    };
+
+    Ok(package_manager)
+}
+
+///
+///
+///
+fn write_dependencies(
+    contents: &mut String,
+    package_manager: &str,
+    dependencies: &[String],
+) -> Result<()> {
+    if dependencies.is_empty() {
+        return Ok(());
+    }
+
+    let dependencies = dependencies.join(" ");
+
+    // Package indexes and caches are removed, they're of no use in the image.
+    match package_manager {
+        "apk" => writeln!(contents, "RUN apk add --no-cache {}", dependencies)?,
+        "dnf" => writeln!(contents, "RUN dnf install -y {} && dnf clean all", dependencies)?,
+        "micromamba" => writeln!(
+            contents,
+            "RUN micromamba install -y -n base -c conda-forge {} && micromamba clean --all --yes",
+            dependencies
+        )?,
+        _ => writeln!(
+            contents,
+            "RUN apt-get update && DEBIAN_FRONTEND=noninteractive apt-get install -y --allow-change-held-packages --allow-downgrades {} && rm -rf /var/lib/apt/lists/*",
+            dependencies
+        )?,
+    }
+
+    Ok(())
+}
+
+///
+///
+///
+fn write_environment(
+    contents: &mut String,
+    ecu_document: &ContainerInfo,
+) -> Result<()> {
+    if let Some(environment) = &ecu_document.environment {
+        // Sorted, otherwise the order (and thus the cache) changes between builds.
+        let mut environment: Vec<_> = environment.iter().collect();
+        environment.sort();
+
+        for (key, value) in environment {
+            writeln!(contents, "ENV {}={}", key, value)?;
+        }
+    }
+
+    Ok(())
+}
+
+///
+///
+///
+fn write_install(
+    contents: &mut String,
+    install: &[String],
+) -> Result<()> {
+    for line in install {
+        writeln!(contents, "RUN {}", line)?;
+    }
+
+    Ok(())
+}
+
 ///
 ///
 ///
@@ -212,6 +383,7 @@ fn prepare_directory(
     ecu_file: &Path,
     dockerfile: String,
     branelet_path: Option<PathBuf>,
+    juicefs_path: Option<PathBuf>,
     context: &Path,
     package_info: &PackageInfo,
     package_dir: &Path,
@@ -238,6 +410,11 @@ fn prepare_directory(
         fs::copy(fs::canonicalize(branelet_path)?, package_dir.join("branelet"))?;
     }
 
+    // Copy custom JuiceFS tarball to package directory
+    if let Some(juicefs_path) = juicefs_path {
+        fs::copy(fs::canonicalize(juicefs_path)?, package_dir.join("juicefs.tar.gz"))?;
+    }
+
     // Create the working directory and copy required files.
     let wd = package_dir.join("wd");
     if !wd.exists() {
@@ -273,6 +450,30 @@ fn prepare_directory(
         }
     }
 
+    // Install files are also archived separately (see `generate_dockerfile`).
+    let install_files: Vec<PathBuf> = ecu_document
+        .install_files
+        .iter()
+        .flatten()
+        .map(|file| Path::new("wd").join(file.trim_start_matches("./")))
+        .collect();
+
+    if !install_files.is_empty() {
+        let output = Command::new("tar")
+            .arg("-zcf")
+            .arg("install.tar.gz")
+            .args(&install_files)
+            .current_dir(&package_dir)
+            .output()
+            .expect("Couldn't run 'tar' command.");
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Failed to prepare install files archive, install files have to be part of the package files."
+            ));
+        }
+    }
+
     // Archive the working directory and remove the original.
     let output = Command::new("tar")
         .arg("-zcf")
@@ -312,7 +513,14 @@ fn clean_directory(
         return Ok(());
     }
 
-    let files = ["container.yml", "Dockerfile", "wd.tar.gz"];
+    let files = [
+        "container.yml",
+        "Dockerfile",
+        "wd.tar.gz",
+        "install.tar.gz",
+        "branelet",
+        "juicefs.tar.gz",
+    ];
     for file in files {
         let file = package_dir.join(file);
         if file.exists() {
@@ -1,4 +1,4 @@
-use crate::{docker, packages};
+use crate::{build_oci, docker, packages, ImageBuilder};
 use anyhow::{Context, Result};
 use brane_oas::{self, build};
 use console::style;
@@ -16,6 +16,8 @@ const BRANELET_URL: &str = concat!(
     "/branelet"
 );
 
+const BASE_IMAGE: &str = "alpine";
+
 const JUICE_URL: &str =
     "https://github.com/juicedata/juicefs/releases/download/v0.12.1/juicefs-0.12.1-linux-amd64.tar.gz";
 
@@ -26,6 +28,8 @@ pub async fn handle(
     context: PathBuf,
     file: PathBuf,
     branelet_path: Option<PathBuf>,
+    juicefs_path: Option<PathBuf>,
+    builder: ImageBuilder,
     keep_files: bool,
 ) -> Result<()> {
     let context = fs::canonicalize(context)?;
@@ -36,20 +40,28 @@ pub async fn handle(
     let oas_document = brane_oas::parse_oas_file(&oas_file)?;
 
     // Prepare package directory.
-    let dockerfile = generate_dockerfile(&oas_document, branelet_path.is_some())?;
+    let dockerfile = generate_dockerfile(&oas_document, branelet_path.is_some(), juicefs_path.is_some())?;
     let package_info = create_package_info(&oas_document)?;
     let package_dir = packages::get_package_dir(&package_info.name, Some(&package_info.version))?;
-    prepare_directory(&oas_file, dockerfile, branelet_path, &package_info, &package_dir)?;
+    prepare_directory(
+        &oas_file,
+        dockerfile,
+        branelet_path,
+        juicefs_path,
+        &package_info,
+        &package_dir,
+    )?;
 
     debug!("Successfully prepared package directory.");
 
-    // Build Docker image.
-    let tag = format!("{}:{}", package_info.name, package_info.version);
-    build_docker_image(&package_dir, tag)?;
-
-    // Build Docker image
+    // Build Docker image, either with Docker or by assembling its layers directly.
     let tag = format!("{}:{}", package_info.name, package_info.version);
-    let result = build_docker_image(&package_dir, tag);
+    let result = if builder == ImageBuilder::Oci {
+        let environment = Default::default();
+        build_oci::build_image(&package_dir, tag, BASE_IMAGE, &environment, None, BRANELET_URL, JUICE_URL).await
+    } else {
+        build_docker_image(&package_dir, tag)
+    };
 
     if result.is_ok() {
         println!(
@@ -58,16 +70,22 @@ pub async fn handle(
             style(&package_info.name).bold().cyan(),
         );
 
-        // Check if previous build is still loaded in Docker
-        let image_name = format!("{}:{}", package_info.name, package_info.version);
-        docker::remove_image(&image_name).await?;
+        // Check if previous build is still loaded in Docker (the OCI builder doesn't use Docker).
+        if builder == ImageBuilder::Docker {
+            let image_name = format!("{}:{}", package_info.name, package_info.version);
+            docker::remove_image(&image_name).await?;
 
-        let image_name = format!("localhost:5000/library/{}", image_name);
-        docker::remove_image(&image_name).await?;
+            let image_name = format!("localhost:5000/library/{}", image_name);
+            docker::remove_image(&image_name).await?;
+        }
 
         // Remove all non-essential files.
         clean_directory(&package_dir, keep_files)?;
     } else {
+        if let Err(error) = result {
+            error!("{:?}", error);
+        }
+
         println!(
             "Failed to built version {} of Web API (OAS) package {}. See error output above.",
             style(&package_info.version).bold().cyan(),
@@ -110,12 +128,13 @@ fn create_package_info(oas_document: &OpenAPI) -> Result<PackageInfo> {
 fn generate_dockerfile(
     _oas_document: &OpenAPI,
     override_branelet: bool,
+    override_juicefs: bool,
 ) -> Result<String> {
     let mut contents = String::new();
 
     // Add default heading
     writeln!(contents, "# Generated by Brane")?;
-    writeln!(contents, "FROM alpine")?;
+    writeln!(contents, "FROM {}", BASE_IMAGE)?;
 
     // Add dependencies
     writeln!(contents, "RUN apk add --no-cache iptables")?;
@@ -128,7 +147,12 @@ fn generate_dockerfile(
         writeln!(contents, "RUN chmod +x branelet")?;
     }
 
-    writeln!(contents, "ADD {} juicefs.tar.gz", JUICE_URL)?;
+    // A local tarball is copied, as ADD would already extract it.
+    if override_juicefs {
+        writeln!(contents, "COPY juicefs.tar.gz juicefs.tar.gz")?;
+    } else {
+        writeln!(contents, "ADD {} juicefs.tar.gz", JUICE_URL)?;
+    }
     writeln!(
         contents,
         "RUN tar -xzf juicefs.tar.gz && rm juicefs.tar.gz && mkdir /data"
@@ -149,6 +173,7 @@ fn prepare_directory(
     oas_file: &Path,
     dockerfile: String,
     branelet_path: Option<PathBuf>,
+    juicefs_path: Option<PathBuf>,
     package_info: &PackageInfo,
     package_dir: &Path,
 ) -> Result<()> {
@@ -170,6 +195,11 @@ fn prepare_directory(
         fs::copy(fs::canonicalize(branelet_path)?, package_dir.join("branelet"))?;
     }
 
+    // Copy custom JuiceFS tarball to package directory
+    if let Some(juicefs_path) = juicefs_path {
+        fs::copy(fs::canonicalize(juicefs_path)?, package_dir.join("juicefs.tar.gz"))?;
+    }
+
     // Create the working directory and copy required files.
     let wd = package_dir.join("wd");
     if !wd.exists() {
@@ -219,7 +249,7 @@ fn clean_directory(
         return Ok(());
     }
 
-    let files = ["Dockerfile", "wd.tar.gz"];
+    let files = ["Dockerfile", "wd.tar.gz", "branelet", "juicefs.tar.gz"];
     for file in files {
         let file = package_dir.join(file);
         if file.exists() {
@@ -0,0 +1,588 @@
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use reqwest::header::{ACCEPT, AUTHORIZATION, WWW_AUTHENTICATE};
+use reqwest::{Client, Response, StatusCode};
+use serde_json::{json, Value as JValue};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tar::{Archive, Builder, EntryType, Header};
+
+type Map<T> = std::collections::HashMap<String, T>;
+
+const DOCKER_HUB: &str = "registry-1.docker.io";
+const MANIFEST_TYPES: [&str; 4] = [
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.oci.image.manifest.v1+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+    "application/vnd.docker.distribution.manifest.v2+json",
+];
+const OCI_CONFIG: &str = "application/vnd.oci.image.config.v1+json";
+const OCI_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+const OCI_LAYER: &str = "application/vnd.oci.image.layer.v1.tar";
+const OCI_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+// Platform of the images that are built, the same as those built by Docker on Brane's hosts.
+const PLATFORM_ARCHITECTURE: &str = "amd64";
+const PLATFORM_OS: &str = "linux";
+const WORKING_DIR: &str = "/opt/wd";
+
+///
+///
+///
+pub async fn build_image(
+    package_dir: &Path,
+    tag: String,
+    base: &str,
+    environment: &Map<String>,
+    executable: Option<&str>,
+    branelet_url: &str,
+    juicefs_url: &str,
+) -> Result<()> {
+    debug!("Pulling base image '{}' from its registry.", base);
+    let (mut config, mut layers) = pull_image(base).await?;
+
+    // Vendored binaries were copied to the package directory, otherwise they're downloaded.
+    let branelet = read_or_download(&package_dir.join("branelet"), branelet_url).await?;
+    let juicefs = read_or_download(&package_dir.join("juicefs.tar.gz"), juicefs_url).await?;
+    let juicefs = extract_file(&juicefs[..], "juicefs").context("JuiceFS binary not found in its tarball.")?;
+
+    let mut runtime_layer = Builder::new(vec![]);
+    append_data(&mut runtime_layer, "branelet", &branelet[..], 0o755)?;
+    append_data(&mut runtime_layer, "juicefs", &juicefs[..], 0o755)?;
+    append_directory(&mut runtime_layer, "data")?;
+    layers.push(runtime_layer.into_inner()?);
+
+    let mut package_layer = Builder::new(vec![]);
+    let container_file = package_dir.join("container.yml");
+    if container_file.exists() {
+        append_data(&mut package_layer, "container.yml", &fs::read(container_file)?[..], 0o644)?;
+    }
+
+    // Same as 'ADD wd.tar.gz /opt' and setting the execute bit on the entrypoint.
+    let executable = executable.map(|e| Path::new("opt/wd").join(e.trim_start_matches("./")));
+    append_directory(&mut package_layer, "opt")?;
+    let mut wd = Archive::new(GzDecoder::new(File::open(package_dir.join("wd.tar.gz"))?));
+    for entry in wd.entries()? {
+        let mut entry = entry?;
+        let path = Path::new("opt").join(entry.path()?);
+        let mut header = entry.header().clone();
+        header.set_mtime(0);
+        if Some(&path) == executable.as_ref() {
+            header.set_mode(0o755);
+        }
+
+        package_layer.append_data(&mut header, &path, &mut entry)?;
+    }
+    layers.push(package_layer.into_inner()?);
+
+    configure_image(&mut config, environment, &layers[layers.len() - 2..])?;
+    write_archive(&package_dir.join("image.tar"), &tag, &config, &layers)?;
+
+    Ok(())
+}
+
+///
+///
+///
+async fn pull_image(image: &str) -> Result<(JValue, Vec<Vec<u8>>)> {
+    let (host, repository, reference) = parse_reference(image);
+    let mut registry = Registry::new(host, repository);
+
+    let mut manifest = registry.get_manifest(&reference).await?;
+
+    // Multi-platform images list a manifest per platform.
+    if let Some(manifests) = manifest["manifests"].as_array() {
+        let digest = manifests
+            .iter()
+            .find(|m| m["platform"]["os"] == PLATFORM_OS && m["platform"]["architecture"] == PLATFORM_ARCHITECTURE)
+            .and_then(|m| m["digest"].as_str())
+            .with_context(|| {
+                let platform = format!("{}/{}", PLATFORM_OS, PLATFORM_ARCHITECTURE);
+                format!("Image '{}' is not available for {}.", image, platform)
+            })?
+            .to_string();
+
+        manifest = registry.get_manifest(&digest).await?;
+    }
+
+    let config_digest = manifest["config"]["digest"].as_str().context("Image manifest has no config.")?;
+    let config = serde_json::from_slice(&registry.get_blob(config_digest).await?)?;
+
+    // Layers are stored uncompressed, so their digests equal the diff IDs in the config.
+    let mut layers = vec![];
+    for layer in manifest["layers"].as_array().context("Image manifest has no layers.")? {
+        let digest = layer["digest"].as_str().context("Image layer has no digest.")?;
+        let media_type = layer["mediaType"].as_str().unwrap_or_default();
+        let blob = registry.get_blob(digest).await?;
+
+        let layer = if media_type.ends_with("gzip") {
+            let mut layer = vec![];
+            GzDecoder::new(&blob[..]).read_to_end(&mut layer)?;
+            layer
+        } else if media_type.ends_with("tar") {
+            blob
+        } else {
+            bail!("Unsupported layer type '{}' in image '{}'.", media_type, image);
+        };
+
+        layers.push(layer);
+    }
+
+    Ok((config, layers))
+}
+
+///
+///
+///
+fn parse_reference(image: &str) -> (String, String, String) {
+    let (name, reference) = match image.rsplit_once('@') {
+        Some((name, digest)) => (name, digest.to_string()),
+        None => match image.rsplit_once(':') {
+            Some((name, tag)) if !tag.contains('/') => (name, tag.to_string()),
+            _ => (image, String::from("latest")),
+        },
+    };
+
+    // Like Docker, the first component is only a registry if it looks like a hostname.
+    match name.split_once('/') {
+        Some((host, repository)) if host.contains('.') || host.contains(':') || host == "localhost" => {
+            (host.to_string(), repository.to_string(), reference)
+        }
+        Some(_) => (DOCKER_HUB.to_string(), name.to_string(), reference),
+        None => (DOCKER_HUB.to_string(), format!("library/{}", name), reference),
+    }
+}
+
+struct Registry {
+    client: Client,
+    host: String,
+    repository: String,
+    token: Option<String>,
+}
+
+impl Registry {
+    ///
+    ///
+    ///
+    fn new(
+        host: String,
+        repository: String,
+    ) -> Self {
+        Registry {
+            client: Client::new(),
+            host,
+            repository,
+            token: None,
+        }
+    }
+
+    ///
+    ///
+    ///
+    async fn get_manifest(
+        &mut self,
+        reference: &str,
+    ) -> Result<JValue> {
+        let response = self.get(&format!("manifests/{}", reference), &MANIFEST_TYPES.join(", ")).await?;
+        Ok(response.json().await?)
+    }
+
+    ///
+    ///
+    ///
+    async fn get_blob(
+        &mut self,
+        digest: &str,
+    ) -> Result<Vec<u8>> {
+        let blob = self.get(&format!("blobs/{}", digest), "*/*").await?.bytes().await?.to_vec();
+        ensure!(sha256_digest(&blob) == digest, "Digest of blob '{}' doesn't match.", digest);
+
+        Ok(blob)
+    }
+
+    ///
+    ///
+    ///
+    async fn get(
+        &mut self,
+        path: &str,
+        accept: &str,
+    ) -> Result<Response> {
+        // Local registries (e.g., Brane's own) are usually served over plain HTTP.
+        let scheme = if self.host.starts_with("localhost") || self.host.starts_with("127.0.0.1") {
+            "http"
+        } else {
+            "https"
+        };
+        let url = format!("{}://{}/v2/{}/{}", scheme, self.host, self.repository, path);
+
+        let mut attempt = 0;
+        loop {
+            let mut request = self.client.get(&url).header(ACCEPT, accept);
+            if let Some(token) = &self.token {
+                request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+            }
+
+            let response = request.send().await?;
+            if response.status() == StatusCode::UNAUTHORIZED && attempt == 0 {
+                let challenge = response
+                    .headers()
+                    .get(WWW_AUTHENTICATE)
+                    .and_then(|c| c.to_str().ok())
+                    .context("Registry requires authentication, but doesn't say how.")?
+                    .to_string();
+
+                self.token = Some(self.authenticate(&challenge).await?);
+                attempt += 1;
+                continue;
+            }
+
+            return response
+                .error_for_status()
+                .with_context(|| format!("Failed to retrieve '{}' from registry '{}'.", path, self.host));
+        }
+    }
+
+    ///
+    ///
+    ///
+    async fn authenticate(
+        &self,
+        challenge: &str,
+    ) -> Result<String> {
+        // Only anonymous bearer tokens are supported, which suffices for public images.
+        let parameters = challenge
+            .strip_prefix("Bearer ")
+            .with_context(|| format!("Unsupported authentication challenge: {}", challenge))?;
+
+        let parameters: Map<String> = parameters
+            .split(',')
+            .filter_map(|p| p.split_once('='))
+            .map(|(k, v)| (k.trim().to_string(), v.trim_matches('"').to_string()))
+            .collect();
+
+        let realm = parameters.get("realm").context("Authentication challenge has no realm.")?;
+        let query: Vec<(&str, &String)> = ["service", "scope"]
+            .iter()
+            .filter_map(|k| parameters.get(*k).map(|v| (*k, v)))
+            .collect();
+
+        let response: JValue = self
+            .client
+            .get(realm)
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        response["token"]
+            .as_str()
+            .or_else(|| response["access_token"].as_str())
+            .map(String::from)
+            .context("Registry didn't return a token.")
+    }
+}
+
+///
+///
+///
+fn configure_image(
+    config: &mut JValue,
+    environment: &Map<String>,
+    layers: &[Vec<u8>],
+) -> Result<()> {
+    if !config["config"].is_object() {
+        config["config"] = json!({});
+    }
+
+    // Same as ENV, a variable that is set again replaces the previous value.
+    let mut env: Vec<JValue> = config["config"]["Env"].as_array().cloned().unwrap_or_default();
+    let mut variables: Vec<_> = environment.iter().collect();
+    variables.sort();
+    for (key, value) in variables {
+        let prefix = format!("{}=", key);
+        env.retain(|e| !e.as_str().unwrap_or_default().starts_with(&prefix));
+        env.push(json!(format!("{}={}", key, value)));
+    }
+
+    config["config"]["Env"] = json!(env);
+    config["config"]["WorkingDir"] = json!(WORKING_DIR);
+    config["config"]["Entrypoint"] = json!(["/branelet"]);
+    config["config"]["Cmd"] = JValue::Null;
+
+    let diff_ids = config["rootfs"]["diff_ids"]
+        .as_array_mut()
+        .context("Base image config has no diff IDs.")?;
+    for layer in layers {
+        diff_ids.push(json!(sha256_digest(layer)));
+    }
+
+    if let Some(history) = config["history"].as_array_mut() {
+        for _ in layers {
+            history.push(json!({ "created_by": "brane build --builder oci" }));
+        }
+    }
+
+    Ok(())
+}
+
+///
+///
+///
+fn write_archive(
+    path: &Path,
+    tag: &str,
+    config: &JValue,
+    layers: &[Vec<u8>],
+) -> Result<()> {
+    let mut archive = Builder::new(File::create(path)?);
+    append_directory(&mut archive, "blobs")?;
+    append_directory(&mut archive, "blobs/sha256")?;
+
+    let mut append_blob = |content: &[u8]| -> Result<(String, String)> {
+        let digest = sha256_digest(content);
+        let blob_path = format!("blobs/{}", digest.replacen(':', "/", 1));
+        append_data(&mut archive, &blob_path, content, 0o644)?;
+
+        Ok((digest, blob_path))
+    };
+
+    let config = serde_json::to_vec(config)?;
+    let (config_digest, config_path) = append_blob(&config)?;
+
+    let mut layer_descriptors = vec![];
+    let mut layer_paths = vec![];
+    for layer in layers {
+        let (digest, path) = append_blob(layer)?;
+        layer_descriptors.push(json!({ "mediaType": OCI_LAYER, "digest": digest, "size": layer.len() }));
+        layer_paths.push(path);
+    }
+
+    let manifest = serde_json::to_vec(&json!({
+        "schemaVersion": 2,
+        "mediaType": OCI_MANIFEST,
+        "config": { "mediaType": OCI_CONFIG, "digest": config_digest, "size": config.len() },
+        "layers": layer_descriptors,
+    }))?;
+    let (manifest_digest, _) = append_blob(&manifest)?;
+
+    // Both an OCI image layout and a Docker archive, so it can be loaded (and pushed) as before.
+    let index = json!({
+        "schemaVersion": 2,
+        "mediaType": OCI_INDEX,
+        "manifests": [{
+            "mediaType": OCI_MANIFEST,
+            "digest": manifest_digest,
+            "size": manifest.len(),
+            "annotations": { "org.opencontainers.image.ref.name": tag },
+        }],
+    });
+    let docker_manifest = json!([{ "Config": config_path, "RepoTags": [tag], "Layers": layer_paths }]);
+    let oci_layout = json!({ "imageLayoutVersion": "1.0.0" });
+
+    append_data(&mut archive, "index.json", &serde_json::to_vec(&index)?[..], 0o644)?;
+    append_data(&mut archive, "manifest.json", &serde_json::to_vec(&docker_manifest)?[..], 0o644)?;
+    append_data(&mut archive, "oci-layout", &serde_json::to_vec(&oci_layout)?[..], 0o644)?;
+
+    archive.finish()?;
+    Ok(())
+}
+
+///
+///
+///
+async fn read_or_download(
+    path: &Path,
+    url: &str,
+) -> Result<Vec<u8>> {
+    if path.exists() {
+        return Ok(fs::read(path)?);
+    }
+
+    debug!("Downloading {}", url);
+    let response = reqwest::get(url).await?.error_for_status()?;
+
+    Ok(response.bytes().await?.to_vec())
+}
+
+///
+///
+///
+fn extract_file(
+    tarball: &[u8],
+    name: &str,
+) -> Result<Vec<u8>> {
+    let mut archive = Archive::new(GzDecoder::new(tarball));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.file_name() == Some(name.as_ref()) {
+            let mut content = vec![];
+            entry.read_to_end(&mut content)?;
+
+            return Ok(content);
+        }
+    }
+
+    bail!("File '{}' not found in tarball.", name)
+}
+
+///
+///
+///
+fn append_data<W: std::io::Write, P: AsRef<Path>>(
+    builder: &mut Builder<W>,
+    path: P,
+    content: &[u8],
+    mode: u32,
+) -> Result<()> {
+    // Timestamps are left out, so that the same inputs result in the same image.
+    let mut header = Header::new_gnu();
+    header.set_entry_type(EntryType::Regular);
+    header.set_size(content.len() as u64);
+    header.set_mode(mode);
+    header.set_mtime(0);
+
+    builder.append_data(&mut header, path, content)?;
+    Ok(())
+}
+
+///
+///
+///
+fn append_directory<W: std::io::Write, P: AsRef<Path>>(
+    builder: &mut Builder<W>,
+    path: P,
+) -> Result<()> {
+    let mut header = Header::new_gnu();
+    header.set_entry_type(EntryType::Directory);
+    header.set_size(0);
+    header.set_mode(0o755);
+    header.set_mtime(0);
+
+    builder.append_data(&mut header, PathBuf::from(path.as_ref()), std::io::empty())?;
+    Ok(())
+}
+
+///
+///
+///
+fn sha256_digest(content: &[u8]) -> String {
+    format!("sha256:{}", hex::encode(Sha256::digest(content)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_reference() {
+        let reference = |host: &str, repository: &str, reference: &str| {
+            (host.to_string(), repository.to_string(), reference.to_string())
+        };
+
+        assert_eq!(parse_reference("ubuntu"), reference(DOCKER_HUB, "library/ubuntu", "latest"));
+        assert_eq!(parse_reference("ubuntu:20.04"), reference(DOCKER_HUB, "library/ubuntu", "20.04"));
+        assert_eq!(parse_reference("brane/brane-let:1.0"), reference(DOCKER_HUB, "brane/brane-let", "1.0"));
+        assert_eq!(parse_reference("localhost/image"), reference("localhost", "image", "latest"));
+        assert_eq!(
+            parse_reference("localhost:5000/image"),
+            reference("localhost:5000", "image", "latest")
+        );
+        assert_eq!(
+            parse_reference("localhost:5000/image:1.0"),
+            reference("localhost:5000", "image", "1.0")
+        );
+        assert_eq!(
+            parse_reference("ghcr.io/org/image@sha256:abc"),
+            reference("ghcr.io", "org/image", "sha256:abc")
+        );
+    }
+
+    #[test]
+    fn test_configure_image() {
+        let mut config = json!({
+            "config": { "Env": ["PATH=/usr/bin", "A=old"], "Cmd": ["bash"] },
+            "rootfs": { "type": "layers", "diff_ids": ["sha256:base"] },
+            "history": [{ "created_by": "base" }],
+        });
+
+        let mut environment = Map::new();
+        environment.insert(String::from("B"), String::from("b"));
+        environment.insert(String::from("A"), String::from("new"));
+
+        let layers = vec![b"runtime".to_vec(), b"package".to_vec()];
+        configure_image(&mut config, &environment, &layers).unwrap();
+
+        // Layers are added on top of the base image, in the same order.
+        assert_eq!(
+            config["rootfs"]["diff_ids"],
+            json!(["sha256:base", sha256_digest(b"runtime"), sha256_digest(b"package")])
+        );
+        assert_eq!(config["config"]["Env"], json!(["PATH=/usr/bin", "A=new", "B=b"]));
+        assert_eq!(config["config"]["WorkingDir"], json!(WORKING_DIR));
+        assert_eq!(config["config"]["Entrypoint"], json!(["/branelet"]));
+        assert_eq!(config["config"]["Cmd"], JValue::Null);
+        assert_eq!(config["history"].as_array().unwrap().len(), 3);
+
+        let mut config = json!({});
+        assert!(configure_image(&mut config, &environment, &layers).is_err());
+    }
+
+    #[test]
+    fn test_write_archive() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("image.tar");
+
+        let config = json!({ "rootfs": { "type": "layers", "diff_ids": [] } });
+        let layers = vec![b"first".to_vec(), b"second".to_vec()];
+        write_archive(&path, "test:1.0.0", &config, &layers).unwrap();
+
+        let mut files = Map::new();
+        let mut archive = Archive::new(File::open(&path).unwrap());
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let mut content = vec![];
+            entry.read_to_end(&mut content).unwrap();
+            files.insert(entry.path().unwrap().to_string_lossy().to_string(), content);
+        }
+
+        // Blobs are content-addressed.
+        let blobs: Vec<_> = files.keys().filter(|f| f.starts_with("blobs/sha256/")).collect();
+        assert_eq!(blobs.len(), 4);
+        for blob in blobs {
+            assert_eq!(sha256_digest(&files[blob]), blob.replacen("blobs/sha256/", "sha256:", 1));
+        }
+
+        let blob = |digest: &JValue| {
+            let digest = digest.as_str().unwrap();
+            files[&format!("blobs/{}", digest.replacen(':', "/", 1))].clone()
+        };
+        let layout: JValue = serde_json::from_slice(&files["oci-layout"]).unwrap();
+        assert_eq!(layout, json!({ "imageLayoutVersion": "1.0.0" }));
+
+        let index: JValue = serde_json::from_slice(&files["index.json"]).unwrap();
+        let descriptor = &index["manifests"][0];
+        assert_eq!(descriptor["mediaType"], json!(OCI_MANIFEST));
+        assert_eq!(descriptor["annotations"]["org.opencontainers.image.ref.name"], json!("test:1.0.0"));
+
+        let manifest: JValue = serde_json::from_slice(&blob(&descriptor["digest"])).unwrap();
+        assert_eq!(descriptor["size"], json!(blob(&descriptor["digest"]).len()));
+        assert_eq!(serde_json::from_slice::<JValue>(&blob(&manifest["config"]["digest"])).unwrap(), config);
+        assert_eq!(manifest["layers"].as_array().unwrap().len(), 2);
+        assert_eq!(blob(&manifest["layers"][0]["digest"]), b"first");
+        assert_eq!(blob(&manifest["layers"][1]["digest"]), b"second");
+        assert_eq!(manifest["layers"][0]["mediaType"], json!(OCI_LAYER));
+
+        // The same blobs are referenced by the Docker manifest.
+        let docker_manifest: JValue = serde_json::from_slice(&files["manifest.json"]).unwrap();
+        let layer_paths: Vec<_> = docker_manifest[0]["Layers"].as_array().unwrap().iter().collect();
+        assert_eq!(docker_manifest[0]["RepoTags"], json!(["test:1.0.0"]));
+        assert_eq!(files[layer_paths[0].as_str().unwrap()], b"first");
+        assert_eq!(files[layer_paths[1].as_str().unwrap()], b"second");
+        assert!(files.contains_key(docker_manifest[0]["Config"].as_str().unwrap()));
+    }
+}
@@ -0,0 +1,261 @@
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use git2::build::CheckoutBuilder;
+use git2::Repository;
+use std::fs::{self, File};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use tar::Archive;
+use tempfile::{tempdir, TempDir};
+
+const TARBALL_EXTENSIONS: [&str; 3] = [".tar.gz", ".tgz", ".tar"];
+
+///
+///
+///
+pub struct BuildContext {
+    pub path: PathBuf,
+    // Fetched contexts are removed once the build is done.
+    _temp_dir: Option<TempDir>,
+}
+
+impl BuildContext {
+    ///
+    ///
+    ///
+    pub fn new(
+        path: PathBuf,
+        temp_dir: Option<TempDir>,
+    ) -> Self {
+        BuildContext {
+            path,
+            _temp_dir: temp_dir,
+        }
+    }
+}
+
+///
+///
+///
+pub async fn resolve(
+    source: &str,
+    reference: Option<&str>,
+) -> Result<BuildContext> {
+    let is_url = source.contains("://") || source.starts_with("git@");
+    let is_tarball = TARBALL_EXTENSIONS.iter().any(|e| source.ends_with(e));
+
+    if !is_url {
+        let path = PathBuf::from(source);
+        ensure!(path.exists(), "Build context not found: {}", source);
+        ensure!(reference.is_none(), "A reference can only be used with a Git repository.");
+
+        if path.is_dir() {
+            return Ok(BuildContext::new(fs::canonicalize(path)?, None));
+        }
+
+        ensure!(is_tarball, "Build context is neither a directory nor a tarball: {}", source);
+        let archive = File::open(&path)?;
+        return unpack_tarball(archive, source);
+    }
+
+    if is_tarball && (source.starts_with("http://") || source.starts_with("https://")) {
+        ensure!(reference.is_none(), "A reference can only be used with a Git repository.");
+        debug!("Downloading build context from: {}", source);
+
+        let response = reqwest::get(source).await?.error_for_status()?;
+        let archive = Cursor::new(response.bytes().await?);
+
+        return unpack_tarball(archive, source);
+    }
+
+    clone_repository(source, reference)
+}
+
+///
+///
+///
+fn unpack_tarball<R: Read + 'static>(
+    archive: R,
+    source: &str,
+) -> Result<BuildContext> {
+    let temp_dir = tempdir()?;
+
+    // Only the extension tells if the tarball is compressed.
+    let archive: Box<dyn Read> = if source.ends_with(".tar") {
+        Box::new(archive)
+    } else {
+        Box::new(GzDecoder::new(archive))
+    };
+
+    Archive::new(archive)
+        .unpack(temp_dir.path())
+        .with_context(|| format!("Failed to unpack tarball: {}", source))?;
+
+    // Release tarballs (e.g., from GitHub) often contain a single top-level directory.
+    let path = single_directory(temp_dir.path())?.unwrap_or_else(|| temp_dir.path().to_path_buf());
+    debug!("Unpacked build context to: {:?}", path);
+
+    Ok(BuildContext::new(path, Some(temp_dir)))
+}
+
+///
+///
+///
+fn clone_repository(
+    url: &str,
+    reference: Option<&str>,
+) -> Result<BuildContext> {
+    let temp_dir = tempdir()?;
+
+    debug!("Cloning build context from: {}", url);
+    let repository =
+        Repository::clone(url, temp_dir.path()).with_context(|| format!("Failed to clone repository: {}", url))?;
+
+    if let Some(reference) = reference {
+        // Branches only exist as remote branches in a fresh clone, tags and commits are used as-is.
+        let object = repository
+            .revparse_single(&format!("origin/{}", reference))
+            .or_else(|_| repository.revparse_single(reference))
+            .with_context(|| format!("Reference '{}' not found in repository: {}", reference, url))?;
+
+        let commit = object.peel_to_commit()?;
+        repository.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().force()))?;
+        repository.set_head_detached(commit.id())?;
+
+        debug!("Checked out '{}' ({})", reference, commit.id());
+    }
+
+    Ok(BuildContext::new(temp_dir.path().to_path_buf(), Some(temp_dir)))
+}
+
+///
+///
+///
+fn single_directory(path: &Path) -> Result<Option<PathBuf>> {
+    let entries = fs::read_dir(path)?.collect::<Result<Vec<_>, _>>()?;
+    if let [entry] = &entries[..] {
+        if entry.file_type()?.is_dir() {
+            return Ok(Some(entry.path()));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use git2::{Oid, Signature};
+    use tar::Builder;
+
+    fn write_tarball(
+        path: &Path,
+        compressed: bool,
+        prefix: &str,
+    ) {
+        let file = File::create(path).unwrap();
+        let files = tempdir().unwrap();
+        fs::write(files.path().join("container.yml"), "name: test").unwrap();
+
+        if compressed {
+            let mut archive = Builder::new(GzEncoder::new(file, Compression::default()));
+            archive.append_dir_all(prefix, files.path()).unwrap();
+            archive.into_inner().unwrap().finish().unwrap();
+        } else {
+            let mut archive = Builder::new(file);
+            archive.append_dir_all(prefix, files.path()).unwrap();
+            archive.finish().unwrap();
+        }
+    }
+
+    fn commit(
+        repository: &Repository,
+        update_ref: Option<&str>,
+        content: &str,
+    ) -> Oid {
+        fs::write(repository.workdir().unwrap().join("container.yml"), content).unwrap();
+
+        let mut index = repository.index().unwrap();
+        index.add_path(Path::new("container.yml")).unwrap();
+        let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+
+        let signature = Signature::now("Brane", "brane@example.com").unwrap();
+        let parent = repository.head().ok().map(|h| h.peel_to_commit().unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+
+        repository
+            .commit(update_ref, &signature, &signature, content, &tree, &parents)
+            .unwrap()
+    }
+
+    fn read(context: &BuildContext) -> String {
+        fs::read_to_string(context.path.join("container.yml")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_resolve_directory() {
+        let directory = tempdir().unwrap();
+        fs::write(directory.path().join("container.yml"), "name: test").unwrap();
+
+        let context = resolve(directory.path().to_str().unwrap(), None).await.unwrap();
+        assert_eq!(context.path, fs::canonicalize(directory.path()).unwrap());
+        assert!(context._temp_dir.is_none());
+        assert_eq!(read(&context), "name: test");
+
+        assert!(resolve(directory.path().to_str().unwrap(), Some("main")).await.is_err());
+        assert!(resolve(directory.path().join("missing").to_str().unwrap(), None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_tarball() {
+        let directory = tempdir().unwrap();
+
+        // A single top-level directory is used as the context.
+        let tarball = directory.path().join("context.tar.gz");
+        write_tarball(&tarball, true, "package-1.0.0");
+        let context = resolve(tarball.to_str().unwrap(), None).await.unwrap();
+        assert!(context.path.ends_with("package-1.0.0"));
+        assert_eq!(read(&context), "name: test");
+
+        let tarball = directory.path().join("context.tar");
+        write_tarball(&tarball, false, ".");
+        let context = resolve(tarball.to_str().unwrap(), None).await.unwrap();
+        assert_eq!(read(&context), "name: test");
+
+        // Fetched contexts are removed together with the context.
+        let path = context.path.clone();
+        drop(context);
+        assert!(!path.exists());
+
+        let file = directory.path().join("container.yml");
+        fs::write(&file, "name: test").unwrap();
+        assert!(resolve(file.to_str().unwrap(), None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_git() {
+        let directory = tempdir().unwrap();
+        let repository = Repository::init(directory.path()).unwrap();
+
+        let first = commit(&repository, Some("HEAD"), "first");
+        repository
+            .tag_lightweight("v1", &repository.find_object(first, None).unwrap(), false)
+            .unwrap();
+        commit(&repository, Some("HEAD"), "second");
+
+        // A branch that isn't checked out in the source repository.
+        let branch = commit(&repository, None, "branch");
+        repository
+            .branch("feature", &repository.find_commit(branch).unwrap(), false)
+            .unwrap();
+
+        let url = format!("file://{}", directory.path().display());
+        assert_eq!(read(&resolve(&url, None).await.unwrap()), "second");
+        assert_eq!(read(&resolve(&url, Some("feature")).await.unwrap()), "branch");
+        assert_eq!(read(&resolve(&url, Some("v1")).await.unwrap()), "first");
+        assert_eq!(read(&resolve(&url, Some(&first.to_string())).await.unwrap()), "first");
+        assert!(resolve(&url, Some("missing")).await.is_err());
+    }
+}
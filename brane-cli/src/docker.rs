@@ -3,19 +3,20 @@ use anyhow::{Context as _, Result};
 use async_trait::async_trait;
 use bollard::container::{
     Config, CreateContainerOptions, LogOutput, LogsOptions, RemoveContainerOptions, StartContainerOptions,
-    WaitContainerOptions,
+    StopContainerOptions, WaitContainerOptions,
 };
 use bollard::errors::Error;
 use bollard::image::{CreateImageOptions, ImportImageOptions, RemoveImageOptions};
-use bollard::models::{DeviceRequest, HostConfig};
+use bollard::models::{DeviceMapping, DeviceRequest, HostConfig};
 use bollard::Docker;
-use brane_bvm::executor::VmExecutor;
+use brane_bvm::executor::{LocationConstraint, RetryPolicy, ServiceState, VmExecutor};
 use futures_util::stream::TryStreamExt;
 use futures_util::StreamExt;
 use hyper::Body;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use specifications::common::{FunctionExt, Value};
+use specifications::common::{FunctionExt, Resources, Security, Value};
+use specifications::container::SERVICE_READY_MARKER;
 use specifications::package::PackageInfo;
 use std::env;
 use std::path::PathBuf;
@@ -41,15 +42,15 @@ impl DockerExecutor {
     pub fn new(data: Option<PathBuf>) -> Self {
         Self { data }
     }
-}
 
-#[async_trait]
-impl VmExecutor for DockerExecutor {
-    async fn call(
+    ///
+    ///
+    ///
+    async fn attempt(
         &self,
         function: FunctionExt,
         arguments: HashMap<String, Value>,
-        location: Option<String>,
+        location: Option<LocationConstraint>,
     ) -> Result<Value> {
         let package_dir = packages::get_package_dir(&function.package, Some("latest"))?;
         let package_file = package_dir.join("package.yml");
@@ -95,7 +96,14 @@ impl VmExecutor for DockerExecutor {
             None
         };
 
-        let exec = ExecuteInfo::new(image, image_file, mounts, Some(command));
+        let exec = ExecuteInfo::new(
+            image,
+            image_file,
+            mounts,
+            Some(command),
+            function.resources.clone(),
+            function.security.clone(),
+        );
 
         if function.detached {
             let name = run(exec).await?;
@@ -121,6 +129,40 @@ impl VmExecutor for DockerExecutor {
             })
         }
     }
+}
+
+#[async_trait]
+impl VmExecutor for DockerExecutor {
+    async fn call(
+        &self,
+        function: FunctionExt,
+        arguments: HashMap<String, Value>,
+        location: Option<LocationConstraint>,
+        retry: RetryPolicy,
+    ) -> Result<Value> {
+        let mut attempt = 1;
+        loop {
+            match self
+                .attempt(function.clone(), arguments.clone(), location.clone())
+                .await
+            {
+                Err(error) if attempt <= retry.retries => {
+                    let delay = retry.delay(attempt);
+                    warn!(
+                        "Attempt {} of '{}' failed, retrying in {} seconds: {:#}",
+                        attempt,
+                        function.name,
+                        delay.as_secs(),
+                        error
+                    );
+
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
 
     ///
     ///
@@ -161,17 +203,60 @@ impl VmExecutor for DockerExecutor {
     async fn wait_until(
         &self,
         name: String,
-        state: brane_bvm::executor::ServiceState,
+        state: ServiceState,
     ) -> Result<()> {
-        if let brane_bvm::executor::ServiceState::Started = state {
-            return Ok(());
+        let docker = Docker::connect_with_local_defaults()?;
+
+        match state {
+            ServiceState::Created => {}
+            ServiceState::Started => {
+                // Without a callback channel, branelet reports readiness of a service on stdout.
+                let logs_options = Some(LogsOptions::<String> {
+                    follow: true,
+                    stdout: true,
+                    ..Default::default()
+                });
+
+                let logs = docker.logs(&name, logs_options);
+                futures_util::pin_mut!(logs);
+
+                let mut ready = false;
+                while let Some(log_output) = logs.try_next().await? {
+                    if let LogOutput::StdOut { message } = log_output {
+                        if String::from_utf8_lossy(&message)
+                            .lines()
+                            .any(|l| l.trim() == SERVICE_READY_MARKER)
+                        {
+                            ready = true;
+                            break;
+                        }
+                    }
+                }
+
+                ensure!(ready, "Service '{}' exited before it became ready.", name);
+            }
+            ServiceState::Done => {
+                docker
+                    .wait_container(&name, None::<WaitContainerOptions<String>>)
+                    .try_collect::<Vec<_>>()
+                    .await?;
+            }
         }
 
+        Ok(())
+    }
+
+    ///
+    ///
+    ///
+    async fn stop_service(
+        &self,
+        name: String,
+    ) -> Result<()> {
         let docker = Docker::connect_with_local_defaults()?;
-        docker
-            .wait_container(&name, None::<WaitContainerOptions<String>>)
-            .try_collect::<Vec<_>>()
-            .await?;
+
+        // Sends SIGTERM, branelet forwards it to the service and Docker kills it after its grace period.
+        docker.stop_container(&name, None::<StopContainerOptions>).await?;
 
         Ok(())
     }
@@ -203,6 +288,8 @@ pub struct ExecuteInfo {
     pub image: String,
     pub image_file: Option<PathBuf>,
     pub mounts: Option<Vec<String>>,
+    pub resources: Option<Resources>,
+    pub security: Option<Security>,
 }
 
 impl ExecuteInfo {
@@ -214,12 +301,16 @@ impl ExecuteInfo {
         image_file: Option<PathBuf>,
         mounts: Option<Vec<String>>,
         command: Option<Vec<String>>,
+        resources: Option<Resources>,
+        security: Option<Security>,
     ) -> Self {
         ExecuteInfo {
             command,
             image,
             image_file,
             mounts,
+            resources,
+            security,
         }
     }
 }
@@ -290,10 +381,12 @@ async fn create_and_start_container(
     let name = Uuid::new_v4().to_string().chars().take(8).collect::<String>();
     let create_options = CreateContainerOptions { name: &name };
 
-    let device_requests = if DOCKER_GPUS.as_str() != "" {
+    // Functions that specify a number of GPUs only get those, others get all or none.
+    let resources = exec.resources.clone().unwrap_or_default();
+    let device_requests = if DOCKER_GPUS.as_str() != "" || resources.gpus.is_some() {
         let device_request = DeviceRequest {
             driver: None,
-            count: Some(-1),
+            count: Some(resources.gpus.map(|gpus| gpus as i64).unwrap_or(-1)),
             device_ids: None,
             capabilities: Some(vec![vec![String::from("gpu")]]),
             options: None,
@@ -318,12 +411,30 @@ async fn create_and_start_container(
 
     binds.push(String::from("/var/run/docker.sock:/var/run/docker.sock"));
 
+    // Only grant what the package declares, unless explicitly running privileged.
+    let security = exec.security.clone().unwrap_or_default();
+    let devices = security
+        .devices
+        .unwrap_or_default()
+        .into_iter()
+        .map(|device| DeviceMapping {
+            path_on_host: Some(device.clone()),
+            path_in_container: Some(device),
+            cgroup_permissions: Some(String::from("rwm")),
+        })
+        .collect();
+    let privileged = DOCKER_PRIVILEGED.as_str() == "true" || security.privileged.unwrap_or_default();
+
     let host_config = HostConfig {
         binds: Some(binds),
         network_mode: Some(DOCKER_NETWORK.to_string()),
-        privileged: Some(DOCKER_PRIVILEGED.as_str() == "true"),
+        privileged: Some(privileged),
+        cap_add: security.capabilities,
+        devices: Some(devices),
         volumes_from,
         device_requests,
+        memory: resources.memory.map(|memory| (memory * 1024 * 1024) as i64),
+        nano_cpus: resources.cpus.map(|cpus| cpus as i64 * 1_000_000_000),
         ..Default::default()
     };
 
@@ -9,6 +9,8 @@ extern crate lazy_static;
 
 pub mod build_ecu;
 pub mod build_oas;
+pub mod build_oci;
+pub mod context;
 pub mod docker;
 pub mod packages;
 pub mod registry;
@@ -19,9 +21,11 @@ pub mod utils;
 
 use anyhow::Result;
 use semver::Version;
+use std::fmt;
 use std::io::Read;
 use std::path::PathBuf;
 use std::process::Command;
+use std::str::FromStr;
 use std::{
     fs::{self, File},
     path::Path,
@@ -29,6 +33,40 @@ use std::{
 
 const MIN_DOCKER_VERSION: &str = "19.0.0";
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageBuilder {
+    Docker,
+    Oci,
+}
+
+impl FromStr for ImageBuilder {
+    type Err = anyhow::Error;
+
+    fn from_str(builder: &str) -> Result<Self> {
+        let builder = match builder.to_lowercase().as_str() {
+            "docker" => ImageBuilder::Docker,
+            "oci" => ImageBuilder::Oci,
+            _ => bail!("Unknown image builder: {}", builder),
+        };
+
+        Ok(builder)
+    }
+}
+
+impl fmt::Display for ImageBuilder {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        let builder = match self {
+            ImageBuilder::Docker => "docker",
+            ImageBuilder::Oci => "oci",
+        };
+
+        write!(f, "{}", builder)
+    }
+}
+
 ///
 ///
 ///
@@ -2,7 +2,7 @@
 extern crate human_panic;
 
 use anyhow::Result;
-use brane_cli::{build_ecu, build_oas, packages, registry, repl, run, test};
+use brane_cli::{build_ecu, build_oas, context, packages, registry, repl, run, test, ImageBuilder};
 use dotenv::dotenv;
 use git2::Repository;
 use log::LevelFilter;
@@ -26,14 +26,33 @@ struct Cli {
 enum SubCommand {
     #[structopt(name = "build", about = "Build a package")]
     Build {
-        #[structopt(short, long, help = "Path to the directory to use as context", default_value = ".")]
-        context: PathBuf,
+        #[structopt(
+            short,
+            long,
+            help = "Directory, tarball (path or URL), or Git repository URL to use as context",
+            default_value = "."
+        )]
+        context: String,
+        #[structopt(
+            long = "ref",
+            help = "Branch, tag, or commit to build, if the context is a Git repository"
+        )]
+        reference: Option<String>,
         #[structopt(name = "FILE", help = "Path to the file to build, relative to the context")]
         file: PathBuf,
         #[structopt(short, long, help = "Kind of package: cwl, dsl, ecu or oas")]
         kind: Option<String>,
         #[structopt(short, long, help = "Path to the init binary to use (override Brane's binary)")]
         init: Option<PathBuf>,
+        #[structopt(long, help = "Path to the JuiceFS release tarball to use (override downloading it)")]
+        juicefs: Option<PathBuf>,
+        #[structopt(
+            long,
+            help = "Image builder: docker, or oci to build without a Docker daemon",
+            default_value = "docker",
+            possible_values = &["docker", "oci"]
+        )]
+        builder: ImageBuilder,
         #[structopt(long, help = "Don't delete build files")]
         keep_files: bool,
     },
@@ -122,12 +141,18 @@ enum SubCommand {
         data: Option<PathBuf>,
     },
 
-    #[structopt(name = "run", about = "Run a DSL script locally")]
+    #[structopt(name = "run", about = "Run a DSL script, locally or remotely")]
     Run {
         #[structopt(name = "FILE", help = "Path to the file to run")]
         file: PathBuf,
         #[structopt(short, long, help = "The directory to mount as /data")]
         data: Option<PathBuf>,
+        #[structopt(short, long = "param", help = "Value for a script parameter (name=value)")]
+        param: Vec<String>,
+        #[structopt(long = "params", help = "YAML file with values for the script parameters")]
+        params: Option<PathBuf>,
+        #[structopt(short, long, help = "Run the script on a remote driver")]
+        remote: Option<String>,
     },
 
     #[structopt(name = "test", about = "Test a package locally")]
@@ -203,20 +228,26 @@ async fn run(options: Cli) -> Result<()> {
     match options.sub_command {
         Build {
             context,
+            reference,
             file,
             kind,
             init,
+            juicefs,
+            builder,
             keep_files,
         } => {
+            // Fetched contexts are kept around until the build is done.
+            let context = context::resolve(&context, reference.as_deref()).await?;
+
             let kind = if let Some(kind) = kind {
                 kind.to_lowercase()
             } else {
-                brane_cli::determine_kind(&context, &file)?
+                brane_cli::determine_kind(&context.path, &file)?
             };
 
             match kind.as_str() {
-                "ecu" => build_ecu::handle(context, file, init, keep_files).await?,
-                "oas" => build_oas::handle(context, file, init, keep_files).await?,
+                "ecu" => build_ecu::handle(context.path.clone(), file, init, juicefs, builder, keep_files).await?,
+                "oas" => build_oas::handle(context.path.clone(), file, init, juicefs, builder, keep_files).await?,
                 _ => println!("Unsupported package kind: {}", kind),
             }
         }
@@ -249,8 +280,8 @@ async fn run(options: Cli) -> Result<()> {
             };
 
             match kind.as_str() {
-                "ecu" => build_ecu::handle(context, file, init, false).await?,
-                "oas" => build_oas::handle(context, file, init, false).await?,
+                "ecu" => build_ecu::handle(context, file, init, None, ImageBuilder::Docker, false).await?,
+                "oas" => build_oas::handle(context, file, init, None, ImageBuilder::Docker, false).await?,
                 _ => println!("Unsupported package kind: {}", kind),
             }
         }
@@ -288,8 +319,14 @@ async fn run(options: Cli) -> Result<()> {
         } => {
             repl::start(bakery, clear, remote, attach, data).await?;
         }
-        Run { file, data } => {
-            run::handle(file, data).await?;
+        Run {
+            file,
+            data,
+            param,
+            params,
+            remote,
+        } => {
+            run::handle(file, data, param, params, remote).await?;
         }
         Test { name, version, data } => {
             test::handle(name, version, data).await?;
@@ -1,5 +1,6 @@
 use crate::docker::DockerExecutor;
 use crate::packages;
+use crate::run::print_job_output;
 use anyhow::Result;
 use brane_bvm::vm::{Vm, VmOptions};
 use brane_drv::grpc::{CreateSessionRequest, DriverServiceClient, ExecuteRequest};
@@ -217,6 +218,7 @@ async fn remote_repl(
                 let request = ExecuteRequest {
                     uuid: session.clone(),
                     input: line.clone(),
+                    params: Default::default(),
                 };
 
                 let response = client.execute(request).await?;
@@ -238,6 +240,10 @@ async fn remote_repl(
                                 eprintln!("{}", stderr);
                             }
 
+                            if let Some(output) = reply.output {
+                                print_job_output(&output);
+                            }
+
                             if reply.close {
                                 break;
                             }
@@ -1,8 +1,13 @@
 use crate::{docker::DockerExecutor, packages};
-use anyhow::Result;
+use anyhow::{Context, Result};
 use brane_bvm::vm::Vm;
+use brane_drv::grpc::{CancelRequest, CreateSessionRequest, DriverServiceClient, ExecuteRequest, JobOutput};
 use brane_dsl::{Compiler, CompilerOptions, Lang};
-use std::fs;
+use console::style;
+use serde_json::Value as JValue;
+use specifications::common::Value;
+use std::collections::HashMap;
+use std::fs::{self, File};
 use std::path::PathBuf;
 
 ///
@@ -11,8 +16,16 @@ use std::path::PathBuf;
 pub async fn handle(
     file: PathBuf,
     data: Option<PathBuf>,
+    param: Vec<String>,
+    params: Option<PathBuf>,
+    remote: Option<String>,
 ) -> Result<()> {
     let source_code = fs::read_to_string(&file)?;
+    let params = parse_params(param, params)?;
+
+    if let Some(remote) = remote {
+        return remote_run(remote, source_code, params).await;
+    }
 
     let compiler_options = CompilerOptions::new(Lang::BraneScript);
     let package_index = packages::get_package_index()?;
@@ -22,9 +35,134 @@ pub async fn handle(
     let mut vm = Vm::new_with(executor, Some(package_index), None);
 
     match compiler.compile(source_code) {
-        Ok(function) => vm.main(function).await,
+        Ok(function) => {
+            let params = compiler.bind_parameters(params)?;
+            vm.set_params(params.into_iter().collect());
+            vm.main(function).await
+        }
         Err(error) => eprintln!("{:?}", error),
     }
 
     Ok(())
 }
+
+///
+///
+///
+async fn remote_run(
+    remote: String,
+    source_code: String,
+    params: HashMap<String, Value>,
+) -> Result<()> {
+    let mut client = DriverServiceClient::connect(remote).await?;
+    let session = client.create_session(CreateSessionRequest {}).await?.into_inner().uuid;
+
+    // The driver checks the parameters, once it has compiled the script.
+    let params = params
+        .into_iter()
+        .map(|(name, value)| Ok((name, serde_json::to_string(&value)?)))
+        .collect::<Result<_>>()?;
+
+    let request = ExecuteRequest {
+        uuid: session.clone(),
+        input: source_code,
+        params,
+    };
+
+    let mut stream = client.execute(request).await?.into_inner();
+    loop {
+        // Interrupting the run cancels the session's outstanding jobs on the remote instance.
+        let message = tokio::select! {
+            message = stream.message() => message,
+            _ = tokio::signal::ctrl_c() => {
+                let request = CancelRequest {
+                    uuid: session.clone(),
+                    job: None,
+                };
+
+                let cancelled = client.cancel(request).await?.into_inner().jobs;
+                eprintln!("\nCancelled {} job(s).", cancelled.len());
+                break;
+            }
+        };
+
+        match message {
+            Ok(Some(reply)) => {
+                if let Some(debug) = reply.debug {
+                    debug!("{}", debug);
+                }
+
+                if let Some(stdout) = reply.stdout {
+                    println!("{}", stdout);
+                }
+
+                if let Some(stderr) = reply.stderr {
+                    eprintln!("{}", stderr);
+                }
+
+                if let Some(output) = reply.output {
+                    print_job_output(&output);
+                }
+
+                if reply.close {
+                    break;
+                }
+            }
+            Err(status) => {
+                eprintln!("\n{}", status.message());
+                break;
+            }
+            Ok(None) => {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+///
+///
+///
+pub fn print_job_output(output: &JobOutput) {
+    // Prefix every line with the job, as the output of parallel jobs may interleave.
+    let prefix = style(format!("[{}]", output.job)).dim();
+    for line in &output.lines {
+        if output.stream == "stderr" {
+            eprintln!("{} {}", prefix, line);
+        } else {
+            println!("{} {}", prefix, line);
+        }
+    }
+}
+
+///
+///
+///
+fn parse_params(
+    param: Vec<String>,
+    params: Option<PathBuf>,
+) -> Result<HashMap<String, Value>> {
+    let mut values = HashMap::new();
+
+    if let Some(params) = params {
+        let file = File::open(&params).with_context(|| format!("Failed to open '{}'.", params.display()))?;
+        let params: HashMap<String, JValue> = serde_yaml::from_reader(file)?;
+
+        for (name, value) in params {
+            values.insert(name, Value::from_json(&value));
+        }
+    }
+
+    // Values on the command line take precedence over those in the file.
+    for param in param {
+        let (name, value) = param
+            .split_once('=')
+            .with_context(|| format!("Expecting a parameter as name=value, but got '{}'.", param))?;
+
+        // Values are parsed once the declared type is known, so e.g. `007` can remain a string.
+        values.insert(name.to_string(), Value::Unicode(value.to_string()));
+    }
+
+    Ok(values)
+}
@@ -60,6 +60,8 @@ pub async fn test_generic(
     let functions = package_info.functions.unwrap();
     let types = package_info.types.unwrap_or_default();
     let (function, arguments) = prompt_for_input(&functions, &types)?;
+    let resources = functions.get(&function).and_then(|f| f.resources.clone());
+    let security = functions.get(&function).and_then(|f| f.security.clone());
 
     let image = format!("{}:{}", package_info.name, package_info.version);
     let image_file = Some(package_dir.join("image.tar"));
@@ -88,7 +90,7 @@ pub async fn test_generic(
         None
     };
 
-    let exec = ExecuteInfo::new(image, image_file, mounts, Some(command));
+    let exec = ExecuteInfo::new(image, image_file, mounts, Some(command), resources, security);
 
     let (stdout, stderr) = docker::run_and_wait(exec).await?;
     debug!("stderr: {}", stderr);
@@ -1,9 +1,9 @@
 use crate::grpc;
 use anyhow::Result;
 use async_trait::async_trait;
-use brane_bvm::executor::VmExecutor;
+use brane_bvm::executor::{LocationConstraint, RetryPolicy, ServiceState, VmExecutor};
 use brane_cfg::Infrastructure;
-use brane_job::interface::{Command, CommandKind};
+use brane_job::interface::{Command, CommandKind, Constraint, Event, EventKind, Mount, MountKind, Resources, Security};
 use brane_shr::jobs::JobStatus;
 use bytes::BytesMut;
 use dashmap::DashMap;
@@ -16,15 +16,43 @@ use rdkafka::{
     util::Timeout,
 };
 use specifications::common::{FunctionExt, Value};
-use std::future::Future;
-use std::pin::Pin;
+use specifications::errors::{JobFailure, REASON_STOPPED, REASON_TIMEOUT};
 use std::sync::Arc;
-use std::task::{Context, Poll};
 use std::{collections::HashMap, time::Duration};
 use tokio::sync::mpsc::Sender;
+use tokio::sync::watch;
 use tonic::Status;
 use uuid::Uuid;
 
+///
+///
+///
+#[derive(Clone, Debug)]
+pub enum JobOutcome {
+    Finished(Value),
+    Failed(JobFailure),
+    Stopped,
+}
+
+impl JobOutcome {
+    ///
+    ///
+    ///
+    pub fn into_result(
+        self,
+        correlation_id: &str,
+    ) -> Result<Value> {
+        match self {
+            JobOutcome::Finished(value) => Ok(value),
+            JobOutcome::Failed(failure) => Err(failure.into()),
+            JobOutcome::Stopped => {
+                let message = format!("Job '{}' was cancelled.", correlation_id);
+                Err(JobFailure::new(REASON_STOPPED.to_string(), message).into())
+            }
+        }
+    }
+}
+
 ///
 ///
 ///
@@ -32,11 +60,17 @@ use uuid::Uuid;
 pub struct JobExecutor {
     pub client_tx: Sender<Result<grpc::ExecuteReply, Status>>,
     pub command_topic: String,
+    pub event_topic: String,
     pub producer: FutureProducer,
     pub session_uuid: String,
     pub states: Arc<DashMap<String, JobStatus>>,
-    pub results: Arc<DashMap<String, Value>>,
+    pub results: Arc<DashMap<String, JobOutcome>>,
     pub locations: Arc<DashMap<String, String>>,
+    pub jobs: Arc<DashMap<String, String>>,
+    pub session_jobs: Arc<DashMap<String, Vec<String>>>,
+    pub watchers: Arc<DashMap<String, watch::Sender<JobStatus>>>,
+    pub outputs: Arc<DashMap<String, Sender<Result<grpc::ExecuteReply, Status>>>>,
+    pub timeout: Option<Duration>,
     pub infra: Infrastructure,
 }
 
@@ -55,76 +89,166 @@ impl JobExecutor {
 
         identifier.to_lowercase()
     }
-}
 
-#[async_trait]
-impl VmExecutor for JobExecutor {
     ///
     ///
     ///
-    async fn call(
+    async fn wait_for_state(
         &self,
-        function: FunctionExt,
-        arguments: HashMap<String, Value>,
-        location: Option<String>,
+        correlation_id: &str,
+        receiver: watch::Receiver<JobStatus>,
+        at_least: JobStatus,
+        timeout: Option<Duration>,
+    ) -> Result<JobStatus> {
+        let status = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, wait_for(receiver, at_least)).await {
+                Ok(status) => status?,
+                Err(_) => {
+                    // Don't leave the job running, nobody is waiting for it anymore.
+                    if let Err(error) = self.stop(correlation_id).await {
+                        warn!("Failed to stop job '{}' after timeout: {:?}", correlation_id, error);
+                    }
+                    self.cleanup(correlation_id);
+
+                    let message = format!(
+                        "Job '{}' did not complete within {} seconds.",
+                        correlation_id,
+                        timeout.as_secs()
+                    );
+                    return Err(JobFailure::new(REASON_TIMEOUT.to_string(), message).into());
+                }
+            },
+            None => wait_for(receiver, at_least).await?,
+        };
+
+        Ok(status)
+    }
+
+    ///
+    ///
+    ///
+    async fn stop(
+        &self,
+        correlation_id: &str,
+    ) -> Result<()> {
+        let job_id = self.jobs.get(correlation_id).map(|j| j.clone());
+        let location = self.locations.get(correlation_id).map(|l| l.clone());
+
+        // Jobs can only be stopped once they're created.
+        if let (Some(job_id), Some(location)) = (job_id, location) {
+            let command = Command::new(
+                CommandKind::Stop,
+                Some(job_id),
+                Some(self.session_uuid.clone()),
+                Some(location),
+                None,
+                vec![],
+                None,
+                None,
+                None,
+                None,
+            );
+
+            send_command(&self.producer, &self.command_topic, correlation_id, command).await?;
+        }
+
+        Ok(())
+    }
+
+    ///
+    ///
+    ///
+    async fn attempt(
+        &self,
+        correlation_id: &str,
+        function: &FunctionExt,
+        arguments: &HashMap<String, Value>,
+        location: Option<LocationConstraint>,
     ) -> Result<Value> {
         let image = format!("{}:{}", function.package, function.version);
+        let resources = function
+            .resources
+            .as_ref()
+            .map(|r| Resources::new(r.cpus, r.gpus, r.memory, r.time));
+        let security = function.security.as_ref().map(|s| {
+            Security::new(
+                s.capabilities.clone().unwrap_or_default(),
+                s.devices.clone().unwrap_or_default(),
+                s.privileged.unwrap_or_default(),
+            )
+        });
+        let mounts = collect_mounts(arguments);
+
+        // A single location needs no planning, other constraints are resolved by the planner.
+        let constraint = location
+            .as_ref()
+            .filter(|c| c.location().is_none())
+            .map(|c| Constraint::new(c.locations.clone(), c.labels.clone(), c.excluded.clone()));
+        let location = location.and_then(|c| c.location());
+
         let command = vec![
             function.kind.to_string(),
             function.name.to_string(),
-            base64::encode(serde_json::to_string(&arguments)?),
+            base64::encode(serde_json::to_string(arguments)?),
         ];
 
-        let session_uuid = Uuid::parse_str(&self.session_uuid)?;
-        let session_uuid_simple = session_uuid.to_simple().to_string();
-
-        let random_id = self.get_random_identifier();
-        let correlation_id = format!("A{}R{}", &session_uuid_simple[..8], random_id);
-
         let command = Command::new(
             CommandKind::Create,
-            Some(correlation_id.clone()),
+            Some(correlation_id.to_string()),
             Some(self.session_uuid.clone()),
             location,
             Some(image),
             command,
-            None,
+            Some(mounts),
+            resources,
+            security,
+            constraint,
         );
 
-        let mut payload = BytesMut::with_capacity(64);
-        command.encode(&mut payload)?;
-
-        let message = FutureRecord::to(&self.command_topic)
-            .key(&correlation_id)
-            .payload(payload.to_bytes());
-
-        let timeout = Timeout::After(Duration::from_secs(5));
-        if self.producer.send(message, timeout).await.is_err() {
-            bail!("Failed to send command to '{}' topic.", self.command_topic);
+        // Register the job before sending the command, so no event can be missed.
+        let (sender, receiver) = watch::channel(JobStatus::Unknown);
+        self.watchers.insert(correlation_id.to_string(), sender);
+        self.states.insert(correlation_id.to_string(), JobStatus::Unknown);
+        self.outputs.insert(correlation_id.to_string(), self.client_tx.clone());
+        self.session_jobs
+            .entry(self.session_uuid.clone())
+            .or_default()
+            .push(correlation_id.to_string());
+
+        if let Err(error) = send_command(&self.producer, &self.command_topic, correlation_id, command).await {
+            self.cleanup(correlation_id);
+            return Err(error);
         }
 
         if function.detached {
-            // Wait until "created" (address known ?)
-            let created = WaitUntil {
-                at_least: JobStatus::Created,
-                correlation_id: correlation_id.clone(),
-                states: self.states.clone(),
-            };
-
             info!("Waiting until (detached) job '{}' is created...", correlation_id);
-            created.await;
+            let status = self
+                .wait_for_state(correlation_id, receiver, JobStatus::Created, None)
+                .await?;
+
+            // The event monitor takes over the bookkeeping once nobody is waiting.
+            self.watchers.remove(correlation_id);
+            self.outputs.remove(correlation_id);
+
+            // FAILED, STOPPED, and LOST states also satisfy the awaited state, as they come later.
+            if matches!(status, JobStatus::Failed | JobStatus::Stopped | JobStatus::Lost) {
+                let outcome = self.results.remove(correlation_id).map(|(_, o)| o);
+                self.cleanup(correlation_id);
+
+                outcome.unwrap_or(JobOutcome::Stopped).into_result(correlation_id)?;
+            }
             info!("OK, job '{}' has been created", correlation_id);
 
             let location = self
                 .locations
-                .get(&correlation_id)
+                .get(correlation_id)
                 .map(|s| s.clone())
                 .unwrap_or_default();
 
             let location = self.infra.get_location_metadata(location)?;
 
             let mut properties = HashMap::default();
-            properties.insert(String::from("identifier"), Value::Unicode(correlation_id));
+            properties.insert(String::from("identifier"), Value::Unicode(correlation_id.to_string()));
             properties.insert(String::from("address"), Value::Unicode(location.get_address()));
 
             Ok(Value::Struct {
@@ -132,21 +256,235 @@ impl VmExecutor for JobExecutor {
                 properties,
             })
         } else {
-            let finished = WaitUntil {
-                at_least: JobStatus::Finished,
-                correlation_id: correlation_id.clone(),
-                states: self.states.clone(),
+            // The wall-time of the function takes precedence over the default timeout.
+            let timeout = function
+                .resources
+                .as_ref()
+                .and_then(|r| r.time)
+                .map(Duration::from_secs)
+                .or(self.timeout);
+
+            info!("Waiting until job '{}' is done...", correlation_id);
+            self.wait_for_state(correlation_id, receiver, JobStatus::Finished, timeout)
+                .await?;
+            info!("OK, job '{}' is done", correlation_id);
+
+            // The outcome is always inserted before the state is updated.
+            let outcome = self.results.remove(correlation_id).map(|(_, o)| o);
+            self.cleanup(correlation_id);
+
+            let outcome = outcome.ok_or_else(|| anyhow!("Missing outcome for job '{}'.", correlation_id))?;
+            outcome.into_result(correlation_id)
+        }
+    }
+
+    ///
+    ///
+    ///
+    async fn record_retry(
+        &self,
+        correlation_id: &str,
+        location: &Option<LocationConstraint>,
+        attempt: u32,
+        error: &anyhow::Error,
+    ) -> Result<()> {
+        let order = attempt;
+        let key = format!("{}#{}", correlation_id, order);
+        let category = String::from("job");
+        let payload = format!("{:#}", error).into_bytes();
+        let event = Event::new(
+            EventKind::Retrying,
+            correlation_id.to_string(),
+            self.session_uuid.clone(),
+            location.as_ref().and_then(|l| l.location()).unwrap_or_default(),
+            category,
+            order,
+            Some(payload),
+            None,
+        );
+
+        let mut payload = BytesMut::with_capacity(64);
+        event.encode(&mut payload)?;
+
+        let message = FutureRecord::to(&self.event_topic)
+            .key(&key)
+            .payload(payload.to_bytes());
+
+        let timeout = Timeout::After(Duration::from_secs(5));
+        if self.producer.send(message, timeout).await.is_err() {
+            bail!("Failed to send event to '{}' topic.", self.event_topic);
+        }
+
+        Ok(())
+    }
+
+    ///
+    ///
+    ///
+    fn cleanup(
+        &self,
+        correlation_id: &str,
+    ) {
+        self.watchers.remove(correlation_id);
+        self.outputs.remove(correlation_id);
+        self.states.remove(correlation_id);
+        self.results.remove(correlation_id);
+        self.locations.remove(correlation_id);
+        self.jobs.remove(correlation_id);
+
+        if let Some(mut jobs) = self.session_jobs.get_mut(&self.session_uuid) {
+            jobs.retain(|j| j != correlation_id);
+        }
+    }
+}
+
+///
+///
+///
+pub async fn send_command(
+    producer: &FutureProducer,
+    topic: &str,
+    key: &str,
+    command: Command,
+) -> Result<()> {
+    let mut payload = BytesMut::with_capacity(64);
+    command.encode(&mut payload)?;
+
+    let message = FutureRecord::to(topic).key(key).payload(payload.to_bytes());
+
+    let timeout = Timeout::After(Duration::from_secs(5));
+    if producer.send(message, timeout).await.is_err() {
+        bail!("Failed to send command to '{}' topic.", topic);
+    }
+
+    Ok(())
+}
+
+///
+///
+///
+fn collect_mounts(arguments: &HashMap<String, Value>) -> Vec<Mount> {
+    let mut mounts = vec![];
+    for value in arguments.values() {
+        collect_value_mounts(value, &mut mounts);
+    }
+
+    mounts
+}
+
+///
+///
+///
+fn collect_value_mounts(
+    value: &Value,
+    mounts: &mut Vec<Mount>,
+) {
+    match value {
+        Value::Array { entries, .. } => {
+            for entry in entries {
+                collect_value_mounts(entry, mounts);
+            }
+        }
+        Value::Struct { data_type, properties } => match data_type.as_str() {
+            "Directory" | "File" => {
+                // Only local paths in a dataset can be mounted (see brane-job), other URLs are up to the package.
+                let path = match properties.get("url") {
+                    Some(Value::Unicode(url)) => url.strip_prefix("file://").unwrap_or(url),
+                    _ => return,
+                };
+                if !path.starts_with('/') || mounts.iter().any(|m| m.source == path) {
+                    return;
+                }
+
+                // Files are inputs, directories may also be used for output.
+                let read_only = data_type == "File";
+                mounts.push(Mount::new(MountKind::Bind, path, path, read_only));
+            }
+            _ => {
+                for property in properties.values() {
+                    collect_value_mounts(property, mounts);
+                }
+            }
+        },
+        _ => {}
+    }
+}
+
+///
+///
+///
+async fn wait_for(
+    mut receiver: watch::Receiver<JobStatus>,
+    at_least: JobStatus,
+) -> Result<JobStatus> {
+    loop {
+        let status = *receiver.borrow();
+        if status >= at_least {
+            return Ok(status);
+        }
+
+        // Only fails if the job is no longer tracked.
+        receiver
+            .changed()
+            .await
+            .map_err(|_| anyhow!("Stopped tracking job before it reached the {:?} state.", at_least))?;
+    }
+}
+
+#[async_trait]
+impl VmExecutor for JobExecutor {
+    ///
+    ///
+    ///
+    async fn call(
+        &self,
+        function: FunctionExt,
+        arguments: HashMap<String, Value>,
+        location: Option<LocationConstraint>,
+        retry: RetryPolicy,
+    ) -> Result<Value> {
+        let session_uuid = Uuid::parse_str(&self.session_uuid)?;
+        let session_uuid_simple = session_uuid.to_simple().to_string();
+
+        let mut attempt = 1;
+        loop {
+            // Every attempt is a new job, with its own correlation identifier.
+            let random_id = self.get_random_identifier();
+            let correlation_id = format!("A{}R{}", &session_uuid_simple[..8], random_id);
+
+            let error = match self
+                .attempt(&correlation_id, &function, &arguments, location.clone())
+                .await
+            {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
             };
 
-            info!("Waiting until job '{}' is finished...", correlation_id);
-            finished.await;
-            info!("OK, job '{}' has been finished", correlation_id);
+            // Cancelled jobs are not retried, as that's what the user asked for.
+            let stopped = error
+                .downcast_ref::<JobFailure>()
+                .map(|f| f.reason == REASON_STOPPED)
+                .unwrap_or_default();
 
-            let (_, value) = self.results.remove(&correlation_id).unwrap();
-            self.states.remove(&correlation_id);
+            if stopped || attempt > retry.retries {
+                return Err(error);
+            }
 
-            debug!("RESULT: {:?}", value);
-            Ok(value)
+            let delay = retry.delay(attempt);
+            warn!(
+                "Attempt {} of job '{}' failed, retrying in {} seconds: {:#}",
+                attempt,
+                correlation_id,
+                delay.as_secs(),
+                error
+            );
+
+            if let Err(error) = self.record_retry(&correlation_id, &location, attempt, &error).await {
+                warn!("Failed to record retry of job '{}': {:?}", correlation_id, error);
+            }
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
         }
     }
 
@@ -162,6 +500,7 @@ impl VmExecutor for JobExecutor {
             debug: Some(text),
             stderr: None,
             stdout: None,
+            output: None,
         };
 
         self.client_tx.send(Ok(reply)).await.map(|_| ()).map_err(|e| {
@@ -182,6 +521,7 @@ impl VmExecutor for JobExecutor {
             debug: None,
             stderr: Some(text),
             stdout: None,
+            output: None,
         };
 
         self.client_tx.send(Ok(reply)).await.map(|_| ()).map_err(|e| {
@@ -202,6 +542,7 @@ impl VmExecutor for JobExecutor {
             debug: None,
             stderr: None,
             stdout: Some(text),
+            output: None,
         };
 
         self.client_tx.send(Ok(reply)).await.map(|_| ()).map_err(|e| {
@@ -215,87 +556,77 @@ impl VmExecutor for JobExecutor {
     ///
     async fn wait_until(
         &self,
-        _service: String,
-        _state: brane_bvm::executor::ServiceState,
+        service: String,
+        state: ServiceState,
     ) -> Result<()> {
-        Ok(())
-    }
-}
+        let at_least = match state {
+            ServiceState::Created => JobStatus::Created,
+            ServiceState::Started => JobStatus::Started,
+            ServiceState::Done => JobStatus::Finished,
+        };
 
-///
-///
-///
-struct Wait {
-    correlation_id: String,
-    states: Arc<DashMap<String, JobStatus>>,
-    results: Arc<DashMap<String, Value>>,
-}
+        // Services that are no longer tracked are done already.
+        let status = match self.states.get(&service).map(|s| *s) {
+            Some(status) => status,
+            None if state == ServiceState::Done => return Ok(()),
+            None => bail!("Service '{}' is no longer running.", service),
+        };
 
-impl Future for Wait {
-    type Output = Value;
+        let (sender, receiver) = watch::channel(status);
+        self.watchers.insert(service.clone(), sender);
 
-    ///
-    ///
-    ///
-    fn poll(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-    ) -> Poll<Self::Output> {
-        let state = self.states.get(&self.correlation_id);
-        if let Some(state) = state {
-            let state = state.value();
-            match state {
-                JobStatus::Failed => {
-                    unimplemented!();
+        // The state may have changed before the watcher was in place.
+        match self.states.get(&service).map(|s| *s) {
+            Some(status) => {
+                if let Some(watcher) = self.watchers.get(&service) {
+                    let _ = watcher.send(status);
                 }
-                JobStatus::Finished => {
-                    let (_, value) = self.results.remove(&self.correlation_id).unwrap();
-                    self.states.remove(&self.correlation_id);
+            }
+            None => {
+                self.watchers.remove(&service);
+                ensure!(state == ServiceState::Done, "Service '{}' is no longer running.", service);
 
-                    debug!("Job finished! Returning result");
-                    return Poll::Ready(value);
-                }
-                JobStatus::Stopped => {
-                    unimplemented!();
-                }
-                _ => {}
+                return Ok(());
             }
         }
 
-        cx.waker().wake_by_ref();
-        Poll::Pending
-    }
-}
+        // Services run until they're stopped, thus waiting on them is not subject to a timeout.
+        info!("Waiting until service '{}' is {:?}...", service, state);
+        let status = self.wait_for_state(&service, receiver, at_least, None).await?;
 
-///
-///
-///
-struct WaitUntil {
-    at_least: JobStatus,
-    correlation_id: String,
-    states: Arc<DashMap<String, JobStatus>>,
-}
+        // The event monitor takes over the bookkeeping again, unless the service is done.
+        if status < JobStatus::Finished {
+            self.watchers.remove(&service);
+            return Ok(());
+        }
 
-impl Future for WaitUntil {
-    type Output = ();
+        let outcome = self.results.remove(&service).map(|(_, o)| o);
+        self.cleanup(&service);
+
+        match status {
+            JobStatus::Finished | JobStatus::Stopped if state == ServiceState::Done => Ok(()),
+            JobStatus::Finished => bail!("Service '{}' finished before it was started.", service),
+            _ => outcome
+                .unwrap_or(JobOutcome::Stopped)
+                .into_result(&service)
+                .map(|_| ()),
+        }
+    }
 
     ///
     ///
     ///
-    fn poll(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-    ) -> Poll<Self::Output> {
-        let state = self.states.get(&self.correlation_id);
-        if let Some(state) = state {
-            let state = state.value();
-
-            if state >= &self.at_least {
-                return Poll::Ready(());
-            }
+    async fn stop_service(
+        &self,
+        service: String,
+    ) -> Result<()> {
+        // Services that are no longer tracked are done already.
+        if !self.states.contains_key(&service) {
+            debug!("Service '{}' is not running, nothing to stop.", service);
+            return Ok(());
         }
 
-        cx.waker().wake_by_ref();
-        Poll::Pending
+        info!("Stopping service '{}'.", service);
+        self.stop(&service).await
     }
 }
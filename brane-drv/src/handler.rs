@@ -1,15 +1,19 @@
-use crate::executor::JobExecutor;
+use crate::executor::{self, JobExecutor, JobOutcome};
 use crate::{grpc, packages};
 use anyhow::Result;
+use brane_bvm::executor::RetryPolicy;
 use brane_bvm::vm::{Vm, VmOptions, VmState};
 use brane_cfg::Infrastructure;
 use brane_dsl::{Compiler, CompilerOptions, Lang};
+use brane_job::interface::{Command, CommandKind};
 use brane_shr::jobs::JobStatus;
 use dashmap::DashMap;
 use rdkafka::producer::FutureProducer;
 use specifications::common::Value;
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
 use uuid::Uuid;
@@ -17,12 +21,19 @@ use uuid::Uuid;
 #[derive(Clone)]
 pub struct DriverHandler {
     pub command_topic: String,
+    pub event_topic: String,
     pub graphql_url: String,
     pub producer: FutureProducer,
-    pub results: Arc<DashMap<String, Value>>,
+    pub results: Arc<DashMap<String, JobOutcome>>,
     pub sessions: Arc<DashMap<String, VmState>>,
     pub states: Arc<DashMap<String, JobStatus>>,
     pub locations: Arc<DashMap<String, String>>,
+    pub jobs: Arc<DashMap<String, String>>,
+    pub session_jobs: Arc<DashMap<String, Vec<String>>>,
+    pub watchers: Arc<DashMap<String, watch::Sender<JobStatus>>>,
+    pub outputs: Arc<DashMap<String, mpsc::Sender<Result<grpc::ExecuteReply, Status>>>>,
+    pub job_timeout: Option<Duration>,
+    pub job_retry: RetryPolicy,
     pub infra: Infrastructure,
 }
 
@@ -60,15 +71,22 @@ impl grpc::DriverService for DriverHandler {
         let executor = JobExecutor {
             client_tx: tx.clone(),
             command_topic: self.command_topic.clone(),
+            event_topic: self.event_topic.clone(),
             producer: self.producer.clone(),
             session_uuid: request.uuid.clone(),
             states: self.states.clone(),
             results: self.results.clone(),
             locations: self.locations.clone(),
+            jobs: self.jobs.clone(),
+            session_jobs: self.session_jobs.clone(),
+            watchers: self.watchers.clone(),
+            outputs: self.outputs.clone(),
+            timeout: self.job_timeout,
             infra: self.infra.clone(),
         };
 
         let vm_state = sessions.get(&request.uuid).as_deref().cloned();
+        let job_retry = self.job_retry;
         tokio::spawn(async move {
             let options = CompilerOptions::new(Lang::BraneScript);
             let mut compiler = Compiler::new(options, package_index.clone());
@@ -83,6 +101,23 @@ impl grpc::DriverService for DriverHandler {
                 }
             };
 
+            // Check the provided parameters against the ones declared by the input.
+            let params = request
+                .params
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), serde_json::from_str(value)?)))
+                .collect::<Result<HashMap<String, Value>>>()
+                .and_then(|params| compiler.bind_parameters(params));
+
+            let params = match params {
+                Ok(params) => params,
+                Err(error) => {
+                    let status = Status::invalid_argument(error.to_string());
+                    tx.send(Err(status)).await.unwrap();
+                    return;
+                }
+            };
+
             // Restore VM state corresponding to the session, if any.
             let mut vm = if let Some(vm_state) = vm_state {
                 debug!("Restore VM with state:\n{:?}", vm_state);
@@ -91,11 +126,14 @@ impl grpc::DriverService for DriverHandler {
                 debug!("No VM state to restore, creating new VM.");
                 let options = VmOptions {
                     clear_after_main: true,
+                    retry: job_retry,
                     ..Default::default()
                 };
                 Vm::new_with(executor, Some(package_index), Some(options))
             };
 
+            vm.set_params(params.into_iter().collect());
+
             // TEMP: needed because the VM is not completely `send`.
             futures::executor::block_on(vm.main(function));
             let vm_state = vm.capture_state();
@@ -104,4 +142,74 @@ impl grpc::DriverService for DriverHandler {
 
         Ok(Response::new(ReceiverStream::new(rx)))
     }
+
+    ///
+    ///
+    ///
+    async fn cancel(
+        &self,
+        request: Request<grpc::CancelRequest>,
+    ) -> Result<Response<grpc::CancelReply>, Status> {
+        let request = request.into_inner();
+        Uuid::parse_str(&request.uuid).map_err(|_| Status::invalid_argument("Invalid session UUID."))?;
+
+        // Only jobs that have been created, but are not yet done, can be cancelled.
+        let correlation_ids: Vec<String> = match self.session_jobs.get_mut(&request.uuid) {
+            Some(mut jobs) => {
+                // Detached jobs are forgotten by the event monitor, once they're done.
+                jobs.retain(|j| self.states.contains_key(j));
+                jobs.iter()
+                    .filter(|j| {
+                        self.states
+                            .get(*j)
+                            .map(|s| *s < JobStatus::Finished)
+                            .unwrap_or_default()
+                    })
+                    .filter(|j| request.job.as_ref().map(|job| job == *j).unwrap_or(true))
+                    .cloned()
+                    .collect()
+            }
+            None => vec![],
+        };
+
+        if let Some(job) = &request.job {
+            if correlation_ids.is_empty() {
+                return Err(Status::not_found(format!("No running job '{}' in session.", job)));
+            }
+        }
+
+        let mut cancelled = vec![];
+        for correlation_id in correlation_ids {
+            let job_id = self.jobs.get(&correlation_id).map(|j| j.clone());
+            let location = self.locations.get(&correlation_id).map(|l| l.clone());
+
+            let (job_id, location) = match (job_id, location) {
+                (Some(job_id), Some(location)) => (job_id, location),
+                _ => continue,
+            };
+
+            let command = Command::new(
+                CommandKind::Stop,
+                Some(job_id),
+                Some(request.uuid.clone()),
+                Some(location),
+                None,
+                vec![],
+                None,
+                None,
+                None,
+                None,
+            );
+
+            executor::send_command(&self.producer, &self.command_topic, &correlation_id, command)
+                .await
+                .map_err(|e| Status::unavailable(e.to_string()))?;
+
+            info!("Requested cancellation of job '{}'.", correlation_id);
+            cancelled.push(correlation_id);
+        }
+
+        let reply = grpc::CancelReply { jobs: cancelled };
+        Ok(Response::new(reply))
+    }
 }
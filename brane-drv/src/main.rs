@@ -1,7 +1,9 @@
 use anyhow::{bail, Context, Result};
+use brane_bvm::executor::RetryPolicy;
 use brane_bvm::vm::VmState;
 use brane_cfg::Infrastructure;
-use brane_drv::grpc::DriverServiceServer;
+use brane_drv::executor::JobOutcome;
+use brane_drv::grpc::{self, DriverServiceServer};
 use brane_drv::handler::DriverHandler;
 use brane_job::interface::{Event, EventKind};
 use brane_shr::jobs::JobStatus;
@@ -9,8 +11,8 @@ use clap::Parser;
 use dashmap::DashMap;
 use dotenv::dotenv;
 use futures::TryStreamExt;
-use log::info;
 use log::LevelFilter;
+use log::{debug, info, warn};
 use prost::Message as _;
 use rdkafka::{
     admin::{AdminClient, AdminOptions, NewTopic, TopicReplication},
@@ -21,9 +23,13 @@ use rdkafka::{
     ClientConfig, Message as _, Offset, TopicPartitionList,
 };
 use specifications::common::Value as SpecValue;
-use specifications::common::Value;
+use specifications::errors::{JobFailure, REASON_LOST};
+use specifications::output::JobOutput;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
 use tonic::transport::Server;
+use tonic::Status;
 
 #[derive(Parser)]
 #[clap(version = env!("CARGO_PKG_VERSION"))]
@@ -51,6 +57,15 @@ struct Opts {
     /// Infra metadata store
     #[clap(short, long, default_value = "./infra.yml", env = "INFRA")]
     infra: String,
+    /// Number of times a failed job is retried, unless overridden by the script
+    #[clap(long, default_value = "0", env = "JOB_RETRIES")]
+    job_retries: u32,
+    /// Seconds to wait before the first retry, doubled for every next one
+    #[clap(long, default_value = "10", env = "JOB_RETRY_BACKOFF")]
+    job_retry_backoff: u64,
+    /// Maximum duration of a job, in seconds, unless the function declares its wall-time
+    #[clap(short = 't', long, env = "JOB_TIMEOUT")]
+    job_timeout: Option<u64>,
 }
 
 #[tokio::main]
@@ -83,8 +98,12 @@ async fn main() -> Result<()> {
 
     // Start event monitor in the background.
     let states: Arc<DashMap<String, JobStatus>> = Arc::new(DashMap::new());
-    let results: Arc<DashMap<String, Value>> = Arc::new(DashMap::new());
+    let results: Arc<DashMap<String, JobOutcome>> = Arc::new(DashMap::new());
     let locations: Arc<DashMap<String, String>> = Arc::new(DashMap::new());
+    let jobs: Arc<DashMap<String, String>> = Arc::new(DashMap::new());
+    let session_jobs: Arc<DashMap<String, Vec<String>>> = Arc::new(DashMap::new());
+    let watchers: Arc<DashMap<String, watch::Sender<JobStatus>>> = Arc::new(DashMap::new());
+    let outputs: Arc<DashMap<String, mpsc::Sender<Result<grpc::ExecuteReply, Status>>>> = Arc::new(DashMap::new());
 
     tokio::spawn(start_event_monitor(
         opts.brokers.clone(),
@@ -93,18 +112,30 @@ async fn main() -> Result<()> {
         states.clone(),
         results.clone(),
         locations.clone(),
+        jobs.clone(),
+        watchers.clone(),
+        outputs.clone(),
     ));
 
     let graphql_url = opts.graphql_url.clone();
+    let job_timeout = opts.job_timeout.map(Duration::from_secs);
+    let job_retry = RetryPolicy::new(opts.job_retries, Duration::from_secs(opts.job_retry_backoff));
     let sessions: Arc<DashMap<String, VmState>> = Arc::new(DashMap::new());
     let handler = DriverHandler {
         command_topic,
+        event_topic: opts.event_topic.clone(),
         graphql_url,
         producer,
         results,
         sessions,
         states,
         locations,
+        jobs,
+        session_jobs,
+        watchers,
+        outputs,
+        job_timeout,
+        job_retry,
         infra,
     };
 
@@ -156,13 +187,17 @@ async fn ensure_topics(
 ///
 ///
 ///
+#[allow(clippy::too_many_arguments)]
 async fn start_event_monitor(
     brokers: String,
     group_id: String,
     topic: String,
     states: Arc<DashMap<String, JobStatus>>,
-    results: Arc<DashMap<String, Value>>,
+    results: Arc<DashMap<String, JobOutcome>>,
     locations: Arc<DashMap<String, String>>,
+    jobs: Arc<DashMap<String, String>>,
+    watchers: Arc<DashMap<String, watch::Sender<JobStatus>>>,
+    outputs: Arc<DashMap<String, mpsc::Sender<Result<grpc::ExecuteReply, Status>>>>,
 ) -> Result<()> {
     let consumer: StreamConsumer = ClientConfig::new()
         .set("group.id", group_id)
@@ -198,6 +233,9 @@ async fn start_event_monitor(
             let owned_states = states.clone();
             let owned_results = results.clone();
             let owned_locations = locations.clone();
+            let owned_jobs = jobs.clone();
+            let owned_watchers = watchers.clone();
+            let owned_outputs = outputs.clone();
 
             async move {
                 if let Some(payload) = owned_message.payload() {
@@ -208,37 +246,80 @@ async fn start_event_monitor(
                     let event_id: Vec<_> = event.identifier.split('-').collect();
                     let correlation_id = event_id.first().unwrap().to_string();
 
-                    match kind {
-                        EventKind::Created => {
-                            owned_states.insert(correlation_id.clone(), JobStatus::Created);
-                            owned_locations.insert(correlation_id, event.location.clone());
-                        }
-                        EventKind::Ready => {
-                            owned_states.insert(correlation_id, JobStatus::Ready);
+                    // Events of jobs that are not (or no longer) tracked can be ignored.
+                    if !owned_states.contains_key(&correlation_id) {
+                        debug!("Ignoring {} event of untracked job '{}'.", kind, correlation_id);
+                        return Ok(());
+                    }
+
+                    let status = match kind {
+                        EventKind::Planned => {
+                            // The location is only final once the job has been created.
+                            debug!("Planned job '{}' at location '{}'.", correlation_id, event.location);
+                            return Ok(());
                         }
-                        EventKind::Initialized => {
-                            owned_states.insert(correlation_id, JobStatus::Initialized);
+                        EventKind::Output => {
+                            // Output is relayed to the client waiting for the job, if any.
+                            let client = owned_outputs.get(&correlation_id).map(|c| c.clone());
+                            if let Some(client) = client {
+                                relay_output(&client, &correlation_id, &event.payload).await;
+                            }
+                            return Ok(());
                         }
-                        EventKind::Started => {
-                            owned_states.insert(correlation_id, JobStatus::Started);
+                        EventKind::Created => {
+                            owned_locations.insert(correlation_id.clone(), event.location.clone());
+                            owned_jobs.insert(correlation_id.clone(), event.identifier.clone());
+                            JobStatus::Created
                         }
+                        EventKind::Ready => JobStatus::Ready,
+                        EventKind::Initialized => JobStatus::Initialized,
+                        EventKind::Started => JobStatus::Started,
                         EventKind::Finished => {
                             let payload = String::from_utf8_lossy(&event.payload).to_string();
                             let value: SpecValue = serde_json::from_str(&payload).unwrap();
 
-                            // Using these two hashmaps is not ideal, they lock and we're dependend on polling (from call future).
-                            // NOTE: for now we have to make sure the results are inserted before the state becomes "finished" to prevent race conditions.
-                            owned_results.insert(correlation_id.clone(), value);
-                            owned_states.insert(correlation_id, JobStatus::Finished);
+                            // NOTE: outcomes are inserted before the state is updated, to prevent race conditions.
+                            owned_results.insert(correlation_id.clone(), JobOutcome::Finished(value));
+                            JobStatus::Finished
                         }
                         EventKind::Stopped => {
-                            owned_states.insert(correlation_id, JobStatus::Stopped);
+                            owned_results.insert(correlation_id.clone(), JobOutcome::Stopped);
+                            JobStatus::Stopped
                         }
                         EventKind::Failed => {
-                            owned_states.insert(correlation_id, JobStatus::Failed);
+                            let failure = JobFailure::from_payload(&event.payload);
+
+                            owned_results.insert(correlation_id.clone(), JobOutcome::Failed(failure));
+                            JobStatus::Failed
+                        }
+                        EventKind::Lost => {
+                            let message = format!("Job '{}' stopped sending heartbeats.", event.identifier);
+                            let failure = JobFailure::new(REASON_LOST.to_string(), message);
+
+                            owned_results.insert(correlation_id.clone(), JobOutcome::Failed(failure));
+                            JobStatus::Lost
                         }
                         _ => {
-                            unreachable!();
+                            // Other events (e.g., from brane-net) don't change the state of a job.
+                            debug!("Ignoring {} event of job '{}'.", kind, correlation_id);
+                            return Ok(());
+                        }
+                    };
+
+                    owned_states.insert(correlation_id.clone(), status);
+
+                    // Notify the waiting call, if any. Otherwise, forget about jobs that are done.
+                    match owned_watchers.get(&correlation_id) {
+                        Some(watcher) => {
+                            let _ = watcher.send(status);
+                        }
+                        None => {
+                            if status >= JobStatus::Finished {
+                                owned_states.remove(&correlation_id);
+                                owned_results.remove(&correlation_id);
+                                owned_locations.remove(&correlation_id);
+                                owned_jobs.remove(&correlation_id);
+                            }
                         }
                     }
                 }
@@ -250,3 +331,37 @@ async fn start_event_monitor(
 
     Ok(())
 }
+
+///
+///
+///
+async fn relay_output(
+    client: &mpsc::Sender<Result<grpc::ExecuteReply, Status>>,
+    correlation_id: &str,
+    payload: &[u8],
+) {
+    let output: JobOutput = match serde_json::from_slice(payload) {
+        Ok(output) => output,
+        Err(error) => {
+            warn!("Failed to decode output of job '{}': {:?}", correlation_id, error);
+            return;
+        }
+    };
+
+    let reply = grpc::ExecuteReply {
+        close: false,
+        debug: None,
+        stderr: None,
+        stdout: None,
+        output: Some(grpc::JobOutput {
+            job: correlation_id.to_string(),
+            stream: output.stream.to_string(),
+            lines: output.lines,
+        }),
+    };
+
+    // The client may have disconnected in the meantime, the job runs regardless.
+    if client.send(Ok(reply)).await.is_err() {
+        debug!("Failed to relay output of job '{}' to client.", correlation_id);
+    }
+}
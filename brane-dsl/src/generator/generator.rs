@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use crate::parser::ast::*;
 use anyhow::Result;
 use brane_bvm::bytecode::{opcodes::*, ChunkMut, FunctionMut};
-use specifications::common::{SpecClass, SpecFunction, Value};
+use specifications::common::{Parameter, SpecClass, SpecFunction, Value};
 
 #[derive(Debug, Clone)]
 pub struct Local {
@@ -19,12 +19,35 @@ pub fn compile(program: Program) -> Result<FunctionMut> {
     let mut locals = Vec::new();
 
     for stmt in program {
-        stmt_to_opcodes(stmt, &mut chunk, &mut locals, 0);
+        stmt_to_opcodes(stmt, &mut chunk, &mut locals, 0)?;
     }
 
     Ok(FunctionMut::main(chunk))
 }
 
+///
+///
+///
+pub fn parameters(program: &[Stmt]) -> Result<Vec<Parameter>> {
+    program
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::DeclareParam {
+                ident: Ident(ident),
+                class: Ident(class),
+                default,
+            } => Some((ident, class, default)),
+            _ => None,
+        })
+        .map(|(ident, class, default)| {
+            let default = default.clone().map(|d| default_to_value(ident, class, d)).transpose()?;
+            let optional = Some(class.ends_with('?'));
+
+            Ok(Parameter::new(ident.clone(), class.clone(), optional, default, None))
+        })
+        .collect()
+}
+
 ///
 ///
 ///
@@ -52,10 +75,17 @@ pub fn compile_function(
     }
 
     for stmt in block {
-        stmt_to_opcodes(stmt, &mut chunk, &mut locals, scope);
+        stmt_to_opcodes(stmt, &mut chunk, &mut locals, scope)?;
     }
     chunk.write_pair(OP_UNIT, OP_RETURN);
 
+    ensure!(
+        params.len() <= u8::MAX as usize,
+        "Function '{}' has too many parameters (max. {}).",
+        name,
+        u8::MAX
+    );
+
     let function = FunctionMut::new(name, params.len() as u8, chunk);
     Ok(function)
 }
@@ -68,60 +98,88 @@ pub fn stmt_to_opcodes(
     chunk: &mut ChunkMut,
     locals: &mut Vec<Local>,
     scope: i32,
-) {
+) -> Result<()> {
     match stmt {
         Stmt::Import {
             package: Ident(ident), ..
         } => {
             let import = chunk.add_constant(ident.into());
-            chunk.write_pair(OP_IMPORT, import);
+            chunk.write_operand(OP_IMPORT, import)?;
         }
         Stmt::DeclareClass {
             ident: Ident(ident),
             properties,
+            defaults,
             methods,
         } => {
-            let properties = properties.into_iter().map(|(Ident(k), Ident(v))| (k, v)).collect();
-            let methods: HashMap<String, SpecFunction> = methods
+            let properties: HashMap<String, String> =
+                properties.into_iter().map(|(Ident(k), Ident(v))| (k, v)).collect();
+            let defaults = defaults
                 .into_iter()
-                .map(|(Ident(k), stmt)| {
-                    if let Stmt::DeclareFunc {
-                        ident: Ident(ident),
-                        params,
-                        body,
-                    } = stmt
-                    {
-                        let method: FunctionMut = compile_function(body, 1, &params, ident).unwrap();
-                        let method: SpecFunction = method.into();
-
-                        (k, method)
-                    } else {
-                        unreachable!()
-                    }
+                .map(|(Ident(k), default)| {
+                    let value = default_to_value(&k, &properties[&k], default)?;
+                    Ok((k, value))
                 })
-                .collect();
+                .collect::<Result<_>>()?;
+            let methods = compile_methods(methods)?;
 
-            let class = Value::Class(SpecClass::new(ident.clone(), properties, methods));
+            let class = Value::Class(SpecClass::new(ident.clone(), properties, defaults, methods));
 
             let class = chunk.add_constant(class);
-            chunk.write_pair(OP_CLASS, class);
+            chunk.write_operand(OP_CLASS, class)?;
 
             let ident = chunk.add_constant(ident.into());
-            chunk.write_pair(OP_DEFINE_GLOBAL, ident);
+            chunk.write_operand(OP_DEFINE_GLOBAL, ident)?;
+        }
+        Stmt::DeclareImpl {
+            ident: Ident(ident),
+            methods,
+        } => {
+            // The methods are carried by an anonymous class, and merged into the existing one.
+            let methods = compile_methods(methods)?;
+            let extension = Value::Class(SpecClass::new(ident, Default::default(), Default::default(), methods));
+
+            let extension = chunk.add_constant(extension);
+            chunk.write_operand(OP_CLASS, extension)?;
+            chunk.write(OP_IMPL);
+        }
+        Stmt::DeclareParam {
+            ident: Ident(ident),
+            class: Ident(class),
+            default,
+        } => {
+            ensure!(scope == 0, "Parameter '{}' must be declared at the top level.", ident);
+
+            // The VM uses a provided value, or else falls back to the default.
+            match default {
+                Some(default) => {
+                    let default = default_to_value(&ident, &class, default)?;
+                    let default = chunk.add_constant(default);
+                    chunk.write_operand(OP_CONSTANT, default)?;
+                }
+                None => chunk.write(OP_UNIT),
+            }
+
+            let class = chunk.add_constant(class.into());
+            chunk.write_operand(OP_CONSTANT, class)?;
+
+            let ident = chunk.add_constant(ident.into());
+            chunk.write_operand(OP_CONSTANT, ident)?;
+            chunk.write(OP_PARAM);
         }
         Stmt::Assign(Ident(ident), expr) => {
             // ident must be an existing local or global.
-            expr_to_opcodes(expr, chunk, locals, scope);
+            expr_to_opcodes(expr, chunk, locals, scope)?;
 
             if let Some(index) = locals.iter().position(|l| l.name == ident) {
-                chunk.write_pair(OP_SET_LOCAL, index as u8);
+                chunk.write_operand(OP_SET_LOCAL, index)?;
             } else {
                 let ident = chunk.add_constant(ident.into());
-                chunk.write_pair(OP_SET_GLOBAL, ident);
+                chunk.write_operand(OP_SET_GLOBAL, ident)?;
             }
         }
         Stmt::LetAssign(Ident(ident), expr) => {
-            expr_to_opcodes(expr, chunk, locals, scope);
+            expr_to_opcodes(expr, chunk, locals, scope)?;
 
             // Don't put a local's name in the globals table.
             // Instead, just note that there's a local on the stack.
@@ -131,18 +189,18 @@ pub fn stmt_to_opcodes(
                     depth: scope,
                 };
                 locals.push(local);
-                return;
+                return Ok(());
             }
 
             let ident = chunk.add_constant(ident.into());
-            chunk.write_pair(OP_DEFINE_GLOBAL, ident);
+            chunk.write_operand(OP_DEFINE_GLOBAL, ident)?;
         }
         Stmt::Block(block) => {
             // Create a new scope (shadow).
             let scope = scope + 1;
 
             for stmt in block {
-                stmt_to_opcodes(stmt, chunk, locals, scope);
+                stmt_to_opcodes(stmt, chunk, locals, scope)?;
             }
 
             // Remove any locals created in this scope.
@@ -157,11 +215,7 @@ pub fn stmt_to_opcodes(
                 }
             }
 
-            match n {
-                0 => {}
-                1 => chunk.write(OP_POP),
-                n => chunk.write_pair(OP_POP_N, n),
-            }
+            write_pops(chunk, n);
         }
         Stmt::For {
             initializer,
@@ -171,11 +225,11 @@ pub fn stmt_to_opcodes(
         } => {
             let scope = scope + 1;
 
-            stmt_to_opcodes(*initializer, chunk, locals, scope);
+            stmt_to_opcodes(*initializer, chunk, locals, scope)?;
 
             let loop_start = chunk.code.len();
 
-            expr_to_opcodes(condition, chunk, locals, scope);
+            expr_to_opcodes(condition, chunk, locals, scope)?;
             // Now the result of the condition is on the stack.
 
             chunk.write(OP_JUMP_IF_FALSE);
@@ -185,29 +239,24 @@ pub fn stmt_to_opcodes(
 
             chunk.write(OP_POP);
             for stmt in consequent {
-                stmt_to_opcodes(stmt, chunk, locals, scope);
+                stmt_to_opcodes(stmt, chunk, locals, scope)?;
             }
 
             // Run incrementer statement
-            stmt_to_opcodes(*increment, chunk, locals, scope);
+            stmt_to_opcodes(*increment, chunk, locals, scope)?;
 
             // Emit loop
-            chunk.write(OP_JUMP_BACK);
-            let jump_back = (chunk.code.len() - loop_start + 2) as u16;
-            chunk.write_bytes(&jump_back.to_be_bytes()[..]);
+            write_jump_back(chunk, loop_start)?;
 
             // How much to jump if condition is false (exit).
-            let jump = (chunk.code.len() - plh_pos - 2) as u16;
-            let [first, second, ..] = jump.to_be_bytes();
-            chunk.code[plh_pos] = first;
-            chunk.code[plh_pos + 1] = second;
+            patch_jump(chunk, plh_pos)?;
 
             chunk.write(OP_POP);
         }
         Stmt::While { condition, consequent } => {
             let loop_start = chunk.code.len();
 
-            expr_to_opcodes(condition, chunk, locals, scope);
+            expr_to_opcodes(condition, chunk, locals, scope)?;
             // Now the result of the condition is on the stack.
 
             chunk.write(OP_JUMP_IF_FALSE);
@@ -216,18 +265,13 @@ pub fn stmt_to_opcodes(
             chunk.write_pair(0x00, 0x00);
 
             chunk.write(OP_POP);
-            stmt_to_opcodes(Stmt::Block(consequent), chunk, locals, scope);
+            stmt_to_opcodes(Stmt::Block(consequent), chunk, locals, scope)?;
 
             // Emit loop
-            chunk.write(OP_JUMP_BACK);
-            let jump_back = (chunk.code.len() - loop_start + 2) as u16;
-            chunk.write_bytes(&jump_back.to_be_bytes()[..]);
+            write_jump_back(chunk, loop_start)?;
 
             // How much to jump?
-            let jump = (chunk.code.len() - plh_pos - 2) as u16;
-            let [first, second, ..] = jump.to_be_bytes();
-            chunk.code[plh_pos] = first;
-            chunk.code[plh_pos + 1] = second;
+            patch_jump(chunk, plh_pos)?;
 
             chunk.write(OP_POP);
         }
@@ -236,7 +280,7 @@ pub fn stmt_to_opcodes(
             consequent,
             alternative,
         } => {
-            expr_to_opcodes(condition, chunk, locals, scope);
+            expr_to_opcodes(condition, chunk, locals, scope)?;
             // Now the result of the condition is on the stack.
 
             chunk.write(OP_JUMP_IF_FALSE);
@@ -245,7 +289,7 @@ pub fn stmt_to_opcodes(
             chunk.write_pair(0x00, 0x00);
 
             chunk.write(OP_POP);
-            stmt_to_opcodes(Stmt::Block(consequent), chunk, locals, scope);
+            stmt_to_opcodes(Stmt::Block(consequent), chunk, locals, scope)?;
 
             // For the else branch
             chunk.write(OP_JUMP);
@@ -254,24 +298,63 @@ pub fn stmt_to_opcodes(
             chunk.write_pair(0x00, 0x00);
 
             // How much to jump?
-            let jump = (chunk.code.len() - plh_pos - 2) as u16;
-            let [first, second, ..] = jump.to_be_bytes();
-            chunk.code[plh_pos] = first;
-            chunk.code[plh_pos + 1] = second;
+            patch_jump(chunk, plh_pos)?;
 
             chunk.write(OP_POP);
 
             if let Some(alternative) = alternative {
-                stmt_to_opcodes(Stmt::Block(alternative), chunk, locals, scope);
+                stmt_to_opcodes(Stmt::Block(alternative), chunk, locals, scope)?;
             }
 
-            let jump = (chunk.code.len() - else_jump_pos - 2) as u16;
-            let [first, second, ..] = jump.to_be_bytes();
-            chunk.code[else_jump_pos] = first;
-            chunk.code[else_jump_pos + 1] = second;
+            patch_jump(chunk, else_jump_pos)?;
+        }
+        Stmt::Try { block, ident, handler } => {
+            // The VM continues at the handler, with the error on the stack, if a call fails.
+            chunk.write(OP_TRY);
+            // Placeholders, we'll backpatch this later.
+            let handler_pos = chunk.code.len();
+            chunk.write_pair(0x00, 0x00);
+
+            stmt_to_opcodes(Stmt::Block(block), chunk, locals, scope)?;
+            chunk.write(OP_TRY_END);
+
+            // Skip the handler if no error occured.
+            chunk.write(OP_JUMP);
+            let end_pos = chunk.code.len();
+            chunk.write_pair(0x00, 0x00);
+
+            patch_jump(chunk, handler_pos)?;
+
+            // The error is a local of the handler's scope.
+            let Ident(ident) = ident;
+            let scope = scope + 1;
+            locals.push(Local {
+                name: ident,
+                depth: scope,
+            });
+
+            for stmt in handler {
+                stmt_to_opcodes(stmt, chunk, locals, scope)?;
+            }
+
+            // Remove any locals created in this scope, including the error.
+            let mut n = 0;
+            while let Some(local) = locals.pop() {
+                if local.depth >= scope {
+                    n += 1;
+                } else {
+                    // Oops, one to many, place it back.
+                    locals.push(local);
+                    break;
+                }
+            }
+
+            write_pops(chunk, n);
+
+            patch_jump(chunk, end_pos)?;
         }
         Stmt::Expr(expr) => {
-            expr_to_opcodes(expr, chunk, locals, scope);
+            expr_to_opcodes(expr, chunk, locals, scope)?;
             chunk.write(OP_POP);
         }
         Stmt::Property { .. } => {
@@ -279,7 +362,7 @@ pub fn stmt_to_opcodes(
         }
         Stmt::Return(expr) => {
             if let Some(expr) = expr {
-                expr_to_opcodes(expr, chunk, locals, scope);
+                expr_to_opcodes(expr, chunk, locals, scope)?;
             } else {
                 chunk.write(OP_UNIT)
             }
@@ -291,14 +374,14 @@ pub fn stmt_to_opcodes(
             params,
             body,
         } => {
-            let function: FunctionMut = compile_function(body, scope + 1, &params, ident.clone()).unwrap();
+            let function: FunctionMut = compile_function(body, scope + 1, &params, ident.clone())?;
             let function: SpecFunction = function.into();
 
             let function = chunk.add_constant(function.into());
-            chunk.write_pair(OP_CONSTANT, function);
+            chunk.write_operand(OP_CONSTANT, function)?;
 
             let ident = chunk.add_constant(ident.into());
-            chunk.write_pair(OP_DEFINE_GLOBAL, ident);
+            chunk.write_operand(OP_DEFINE_GLOBAL, ident)?;
         }
 
         // TODO: merge with block statement?
@@ -306,11 +389,11 @@ pub fn stmt_to_opcodes(
             // Create a new scope (shadow).
             // let scope = scope + 1;
 
-            expr_to_opcodes(location, chunk, locals, scope);
+            expr_to_opcodes(location, chunk, locals, scope)?;
             chunk.write(OP_LOC_PUSH);
 
             for stmt in block {
-                stmt_to_opcodes(stmt, chunk, locals, scope);
+                stmt_to_opcodes(stmt, chunk, locals, scope)?;
             }
 
             // Remove any locals created in this scope.
@@ -325,26 +408,43 @@ pub fn stmt_to_opcodes(
                 }
             }
 
-            match n {
-                0 => {}
-                1 => chunk.write(OP_POP),
-                n => chunk.write_pair(OP_POP_N, n),
-            }
+            write_pops(chunk, n);
 
             chunk.write(OP_LOC_POP);
         }
+        Stmt::Retry {
+            retries,
+            backoff,
+            block,
+        } => {
+            // Calls within the block are retried according to the policy on top of the VM's retry stack.
+            expr_to_opcodes(retries, chunk, locals, scope)?;
+            match backoff {
+                Some(backoff) => expr_to_opcodes(backoff, chunk, locals, scope)?,
+                None => chunk.write(OP_UNIT),
+            }
+            chunk.write(OP_RETRY_PUSH);
+
+            stmt_to_opcodes(Stmt::Block(block), chunk, locals, scope)?;
+            chunk.write(OP_RETRY_POP);
+        }
         Stmt::Parallel { let_assign, blocks } => {
-            let block_n = blocks.len() as u8;
+            let block_n = blocks.len();
             for block in blocks.into_iter().rev() {
-                let function = compile_function(vec![block], scope, &[], String::new()).unwrap();
+                let function = compile_function(vec![block], scope, &[], String::new())?;
                 let function: SpecFunction = function.into();
 
                 let function = chunk.add_constant(function.into());
 
-                chunk.write_pair(OP_CONSTANT, function);
+                chunk.write_operand(OP_CONSTANT, function)?;
             }
 
-            chunk.write_pair(OP_PARALLEL, block_n);
+            ensure!(
+                block_n <= u8::MAX as usize,
+                "Too many branches in parallel statement (max. {}).",
+                u8::MAX
+            );
+            chunk.write_pair(OP_PARALLEL, block_n as u8);
 
             if let Some(Ident(ident)) = let_assign {
                 // Don't put a local's name in the globals table.
@@ -355,16 +455,18 @@ pub fn stmt_to_opcodes(
                         depth: scope,
                     };
                     locals.push(local);
-                    return;
+                    return Ok(());
                 }
 
                 let ident = chunk.add_constant(ident.into());
-                chunk.write_pair(OP_DEFINE_GLOBAL, ident);
+                chunk.write_operand(OP_DEFINE_GLOBAL, ident)?;
             } else {
                 chunk.write(OP_POP);
             }
         }
     }
+
+    Ok(())
 }
 
 ///
@@ -375,7 +477,7 @@ pub fn expr_to_opcodes(
     chunk: &mut ChunkMut,
     locals: &mut Vec<Local>,
     scope: i32,
-) {
+) -> Result<()> {
     match expr {
         Expr::Binary {
             operator,
@@ -383,15 +485,31 @@ pub fn expr_to_opcodes(
             rhs_operand,
         } => {
             // Always evaluate LHS
-            expr_to_opcodes(*lhs_operand, chunk, locals, scope);
+            expr_to_opcodes(*lhs_operand, chunk, locals, scope)?;
             let rhs_operand = *rhs_operand;
 
-            if let BinOp::Dot = operator {
+            if let BinOp::Dot | BinOp::SafeDot = operator {
+                // With safe navigation, a unit LHS skips the access and is also the result.
+                let skip_pos = if let BinOp::SafeDot = operator {
+                    chunk.write(OP_JUMP_IF_UNIT);
+                    let plh_pos = chunk.code.len();
+                    chunk.write_pair(0x00, 0x00);
+
+                    Some(plh_pos)
+                } else {
+                    None
+                };
+
                 match &rhs_operand {
                     Expr::Ident(Ident(ident)) => {
+                        // A missing property is also unit with safe navigation.
+                        let opcode = match operator {
+                            BinOp::SafeDot => OP_GET_PROPERTY_SAFE,
+                            _ => OP_GET_PROPERTY,
+                        };
+
                         let property = chunk.add_constant(ident.clone().into());
-                        chunk.write_pair(OP_GET_PROPERTY, property);
-                        return;
+                        chunk.write_operand(opcode, property)?;
                     }
                     Expr::Call {
                         function: Ident(ident),
@@ -399,23 +517,45 @@ pub fn expr_to_opcodes(
                     } => {
                         // Put method on the stack.
                         let method = chunk.add_constant(ident.clone().into());
-                        chunk.write_pair(OP_GET_METHOD, method);
+                        chunk.write_operand(OP_GET_METHOD, method)?;
 
                         // Call method with arguments, implicitly pass self.
-                        let arguments_n = arguments.len() as u8 + 1;
+                        let arguments_n = arguments.len() + 1;
                         for argument in arguments.iter().skip(1) {
-                            expr_to_opcodes(argument.clone(), chunk, locals, scope);
+                            expr_to_opcodes(argument.clone(), chunk, locals, scope)?;
                         }
 
-                        chunk.write_pair(OP_CALL, arguments_n);
-
-                        return;
+                        write_call(chunk, arguments_n)?;
                     }
                     _ => unreachable!(),
                 }
+
+                if let Some(skip_pos) = skip_pos {
+                    patch_jump(chunk, skip_pos)?;
+                }
+
+                return Ok(());
+            }
+
+            if let BinOp::Coalesce = operator {
+                // Only evaluate the RHS if the LHS is unit.
+                chunk.write(OP_JUMP_IF_UNIT);
+                let unit_pos = chunk.code.len();
+                chunk.write_pair(0x00, 0x00);
+
+                chunk.write(OP_JUMP);
+                let end_pos = chunk.code.len();
+                chunk.write_pair(0x00, 0x00);
+
+                patch_jump(chunk, unit_pos)?;
+                chunk.write(OP_POP);
+                expr_to_opcodes(rhs_operand, chunk, locals, scope)?;
+                patch_jump(chunk, end_pos)?;
+
+                return Ok(());
             }
 
-            expr_to_opcodes(rhs_operand, chunk, locals, scope);
+            expr_to_opcodes(rhs_operand, chunk, locals, scope)?;
             match operator {
                 // Arithmetic
                 BinOp::Add => chunk.write(OP_ADD),
@@ -450,7 +590,7 @@ pub fn expr_to_opcodes(
             }
         }
         Expr::Unary { operator, operand } => {
-            expr_to_opcodes(*operand, chunk, locals, scope);
+            expr_to_opcodes(*operand, chunk, locals, scope)?;
             match operator {
                 UnOp::Neg => chunk.write(OP_NEGATE),
                 UnOp::Not => chunk.write(OP_NOT),
@@ -465,15 +605,15 @@ pub fn expr_to_opcodes(
                 },
                 Lit::Integer(integer) => {
                     let constant = chunk.add_constant(integer.into());
-                    chunk.write_pair(OP_CONSTANT, constant);
+                    chunk.write_operand(OP_CONSTANT, constant)?;
                 }
                 Lit::Real(real) => {
                     let constant = chunk.add_constant(real.into());
-                    chunk.write_pair(OP_CONSTANT, constant);
+                    chunk.write_operand(OP_CONSTANT, constant)?;
                 }
                 Lit::String(string) => {
                     let constant = chunk.add_constant(string.into());
-                    chunk.write_pair(OP_CONSTANT, constant);
+                    chunk.write_operand(OP_CONSTANT, constant)?;
                 }
                 Lit::Unit => {
                     chunk.write(OP_UNIT);
@@ -482,47 +622,54 @@ pub fn expr_to_opcodes(
         }
         Expr::Ident(Ident(ident)) => {
             if let Some(index) = locals.iter().position(|l| l.name == ident) {
-                chunk.write_pair(OP_GET_LOCAL, index as u8);
+                chunk.write_operand(OP_GET_LOCAL, index)?;
             } else {
                 let ident = chunk.add_constant(ident.into());
-                chunk.write_pair(OP_GET_GLOBAL, ident);
+                chunk.write_operand(OP_GET_GLOBAL, ident)?;
             }
         }
         Expr::Call { function, arguments } => {
-            expr_to_opcodes(Expr::Ident(function), chunk, locals, scope);
+            expr_to_opcodes(Expr::Ident(function), chunk, locals, scope)?;
 
-            let arguments_n = arguments.len() as u8;
+            let arguments_n = arguments.len();
             for argument in arguments {
-                expr_to_opcodes(argument, chunk, locals, scope);
+                expr_to_opcodes(argument, chunk, locals, scope)?;
             }
 
-            chunk.write_pair(OP_CALL, arguments_n);
+            write_call(chunk, arguments_n)?;
         }
         Expr::Instance { class, properties } => {
-            let properties_n = properties.len() as u8;
+            let properties_n = properties.len();
+            ensure!(
+                properties_n <= u8::MAX as usize,
+                "Too many properties in instance of '{}' (max. {}).",
+                class.0,
+                u8::MAX
+            );
+
             for property in properties {
                 if let Stmt::Assign(Ident(name), value) = property {
-                    expr_to_opcodes(value, chunk, locals, scope);
-                    expr_to_opcodes(Expr::Literal(Lit::String(name)), chunk, locals, scope);
+                    expr_to_opcodes(value, chunk, locals, scope)?;
+                    expr_to_opcodes(Expr::Literal(Lit::String(name)), chunk, locals, scope)?;
                 } else {
                     unreachable!();
                 }
             }
 
-            expr_to_opcodes(Expr::Ident(class), chunk, locals, scope);
-            chunk.write_pair(OP_NEW, properties_n);
+            expr_to_opcodes(Expr::Ident(class), chunk, locals, scope)?;
+            chunk.write_pair(OP_NEW, properties_n as u8);
         }
         Expr::Array(entries) => {
-            let entries_n = entries.len() as u8;
+            let entries_n = entries.len();
             for entry in entries.iter().rev() {
-                expr_to_opcodes(entry.clone(), chunk, locals, scope);
+                expr_to_opcodes(entry.clone(), chunk, locals, scope)?;
             }
 
-            chunk.write_pair(OP_ARRAY, entries_n);
+            chunk.write_operand(OP_ARRAY, entries_n)?;
         }
         Expr::Index { array, index } => {
-            expr_to_opcodes(*array, chunk, locals, scope);
-            expr_to_opcodes(*index, chunk, locals, scope);
+            expr_to_opcodes(*array, chunk, locals, scope)?;
+            expr_to_opcodes(*index, chunk, locals, scope)?;
 
             chunk.write(OP_INDEX);
         }
@@ -531,4 +678,163 @@ pub fn expr_to_opcodes(
             unreachable!()
         }
     }
+
+    Ok(())
+}
+
+///
+///
+///
+fn write_call(
+    chunk: &mut ChunkMut,
+    arguments_n: usize,
+) -> Result<()> {
+    ensure!(
+        arguments_n <= u8::MAX as usize,
+        "Too many arguments in function call (max. {}).",
+        u8::MAX
+    );
+
+    chunk.write_pair(OP_CALL, arguments_n as u8);
+    Ok(())
+}
+
+///
+///
+///
+fn write_pops(
+    chunk: &mut ChunkMut,
+    n: usize,
+) {
+    let mut n = n;
+    while n > 0 {
+        let m = n.min(u8::MAX as usize);
+        match m {
+            1 => chunk.write(OP_POP),
+            m => chunk.write_pair(OP_POP_N, m as u8),
+        }
+
+        n -= m;
+    }
+}
+
+///
+///
+///
+fn patch_jump(
+    chunk: &mut ChunkMut,
+    position: usize,
+) -> Result<()> {
+    let jump = chunk.code.len() - position - 2;
+    ensure!(
+        jump <= u16::MAX as usize,
+        "Jump of {} bytes exceeds the maximum of {}, try splitting up large blocks.",
+        jump,
+        u16::MAX
+    );
+
+    let [first, second] = (jump as u16).to_be_bytes();
+    chunk.code[position] = first;
+    chunk.code[position + 1] = second;
+
+    Ok(())
+}
+
+///
+///
+///
+fn write_jump_back(
+    chunk: &mut ChunkMut,
+    loop_start: usize,
+) -> Result<()> {
+    chunk.write(OP_JUMP_BACK);
+
+    let jump_back = chunk.code.len() - loop_start + 2;
+    ensure!(
+        jump_back <= u16::MAX as usize,
+        "Jump of {} bytes exceeds the maximum of {}, try splitting up large blocks.",
+        jump_back,
+        u16::MAX
+    );
+
+    chunk.write_bytes(&(jump_back as u16).to_be_bytes()[..]);
+    Ok(())
+}
+
+///
+///
+///
+fn compile_methods(methods: HashMap<Ident, Stmt>) -> Result<HashMap<String, SpecFunction>> {
+    methods
+        .into_iter()
+        .map(|(Ident(k), stmt)| {
+            if let Stmt::DeclareFunc {
+                ident: Ident(ident),
+                params,
+                body,
+            } = stmt
+            {
+                let method: FunctionMut = compile_function(body, 1, &params, ident)?;
+                let method: SpecFunction = method.into();
+
+                Ok((k, method))
+            } else {
+                unreachable!()
+            }
+        })
+        .collect()
+}
+
+///
+///
+///
+fn default_to_value(
+    ident: &str,
+    data_type: &str,
+    default: Expr,
+) -> Result<Value> {
+    // Defaults are stored with the class or parameter, so they must be known at compile time.
+    let literal = match default {
+        Expr::Literal(literal) => literal,
+        Expr::Unary {
+            operator: UnOp::Neg,
+            operand,
+        } => match *operand {
+            Expr::Literal(Lit::Integer(integer)) => Lit::Integer(-integer),
+            Expr::Literal(Lit::Real(real)) => Lit::Real(-real),
+            _ => bail!("Default value of '{}' must be a literal.", ident),
+        },
+        _ => bail!("Default value of '{}' must be a literal.", ident),
+    };
+
+    // Optional types may also default to unit.
+    let (data_type, optional) = match data_type.strip_suffix('?') {
+        Some(data_type) => (data_type, true),
+        None => (data_type, false),
+    };
+
+    let value = match (literal, data_type) {
+        (Lit::Unit, _) if optional => Value::Unit,
+        (Lit::Integer(integer), "real") => Value::Real(integer as f64),
+        (literal, data_type) => {
+            let literal_type = literal.data_type();
+            ensure!(
+                literal_type == data_type,
+                "Default value of '{}' is of type '{}', expected '{}'.",
+                ident,
+                literal_type,
+                data_type
+            );
+
+            match literal {
+                Lit::Boolean(boolean) => boolean.into(),
+                Lit::Integer(integer) => integer.into(),
+                Lit::Real(real) => real.into(),
+                Lit::String(string) => string.into(),
+                Lit::Unit => Value::Unit,
+            }
+        }
+    };
+
+    Ok(value)
 }
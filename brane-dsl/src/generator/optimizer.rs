@@ -0,0 +1,540 @@
+use crate::parser::ast::*;
+use brane_bvm::bytecode::{opcodes::*, ChunkMut, FunctionMut};
+use bytes::{BufMut, BytesMut};
+use specifications::common::{SpecFunction, Value};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+///
+///
+///
+pub fn optimize_program(program: Program) -> Program {
+    fold_block(program)
+}
+
+///
+///
+///
+fn fold_block(block: Block) -> Block {
+    let mut folded = Vec::with_capacity(block.len());
+    for stmt in block {
+        let returns = matches!(stmt, Stmt::Return(_));
+
+        match fold_stmt(stmt) {
+            Stmt::Block(block) if block.is_empty() => {}
+            stmt => folded.push(stmt),
+        }
+
+        // Anything after a return statement is unreachable.
+        if returns {
+            break;
+        }
+    }
+
+    folded
+}
+
+///
+///
+///
+fn fold_stmt(stmt: Stmt) -> Stmt {
+    match stmt {
+        Stmt::Assign(ident, expr) => Stmt::Assign(ident, fold_expr(expr)),
+        Stmt::Block(block) => Stmt::Block(fold_block(block)),
+        Stmt::DeclareClass {
+            ident,
+            properties,
+            defaults,
+            methods,
+        } => {
+            let defaults = defaults.into_iter().map(|(k, d)| (k, fold_expr(d))).collect();
+            let methods = methods.into_iter().map(|(k, m)| (k, fold_stmt(m))).collect();
+
+            Stmt::DeclareClass {
+                ident,
+                properties,
+                defaults,
+                methods,
+            }
+        }
+        Stmt::DeclareFunc { ident, params, body } => Stmt::DeclareFunc {
+            ident,
+            params,
+            body: fold_block(body),
+        },
+        Stmt::DeclareImpl { ident, methods } => {
+            let methods = methods.into_iter().map(|(k, m)| (k, fold_stmt(m))).collect();
+
+            Stmt::DeclareImpl { ident, methods }
+        }
+        Stmt::DeclareParam { ident, class, default } => Stmt::DeclareParam {
+            ident,
+            class,
+            default: default.map(fold_expr),
+        },
+        Stmt::Expr(expr) => match fold_expr(expr) {
+            // The result would be popped right away, without any side effects.
+            Expr::Literal(_) => Stmt::Block(vec![]),
+            expr => Stmt::Expr(expr),
+        },
+        Stmt::For {
+            initializer,
+            condition,
+            increment,
+            consequent,
+        } => Stmt::For {
+            initializer: Box::new(fold_stmt(*initializer)),
+            condition: fold_expr(condition),
+            increment: Box::new(fold_stmt(*increment)),
+            consequent: fold_block(consequent),
+        },
+        Stmt::If {
+            condition,
+            consequent,
+            alternative,
+        } => match fold_expr(condition) {
+            Expr::Literal(Lit::Boolean(true)) => Stmt::Block(fold_block(consequent)),
+            Expr::Literal(Lit::Boolean(false)) => Stmt::Block(alternative.map(fold_block).unwrap_or_default()),
+            condition => Stmt::If {
+                condition,
+                consequent: fold_block(consequent),
+                alternative: alternative.map(fold_block),
+            },
+        },
+        Stmt::LetAssign(ident, expr) => Stmt::LetAssign(ident, fold_expr(expr)),
+        Stmt::On { location, block } => Stmt::On {
+            location: fold_expr(location),
+            block: fold_block(block),
+        },
+        Stmt::Parallel { let_assign, blocks } => Stmt::Parallel {
+            let_assign,
+            blocks: blocks.into_iter().map(fold_stmt).collect(),
+        },
+        Stmt::Retry {
+            retries,
+            backoff,
+            block,
+        } => Stmt::Retry {
+            retries: fold_expr(retries),
+            backoff: backoff.map(fold_expr),
+            block: fold_block(block),
+        },
+        Stmt::Return(expr) => Stmt::Return(expr.map(fold_expr)),
+        Stmt::Try { block, ident, handler } => Stmt::Try {
+            block: fold_block(block),
+            ident,
+            handler: fold_block(handler),
+        },
+        Stmt::While { condition, consequent } => match fold_expr(condition) {
+            Expr::Literal(Lit::Boolean(false)) => Stmt::Block(vec![]),
+            condition => Stmt::While {
+                condition,
+                consequent: fold_block(consequent),
+            },
+        },
+        stmt => stmt,
+    }
+}
+
+///
+///
+///
+fn fold_expr(expr: Expr) -> Expr {
+    match expr {
+        Expr::Array(entries) => Expr::Array(entries.into_iter().map(fold_expr).collect()),
+        Expr::Binary {
+            operator: operator @ (BinOp::Dot | BinOp::SafeDot),
+            lhs_operand,
+            rhs_operand,
+        } => Expr::Binary {
+            operator,
+            lhs_operand: Box::new(fold_expr(*lhs_operand)),
+            rhs_operand,
+        },
+        Expr::Binary {
+            operator: BinOp::Coalesce,
+            lhs_operand,
+            rhs_operand,
+        } => match fold_expr(*lhs_operand) {
+            Expr::Literal(Lit::Unit) => fold_expr(*rhs_operand),
+            Expr::Literal(literal) => Expr::Literal(literal),
+            lhs_operand => Expr::Binary {
+                operator: BinOp::Coalesce,
+                lhs_operand: Box::new(lhs_operand),
+                rhs_operand: Box::new(fold_expr(*rhs_operand)),
+            },
+        },
+        Expr::Binary {
+            operator,
+            lhs_operand,
+            rhs_operand,
+        } => {
+            let lhs_operand = fold_expr(*lhs_operand);
+            let rhs_operand = fold_expr(*rhs_operand);
+
+            if let (Expr::Literal(lhs), Expr::Literal(rhs)) = (&lhs_operand, &rhs_operand) {
+                if let Some(literal) = fold_binary(&operator, lhs, rhs) {
+                    return Expr::Literal(literal);
+                }
+            }
+
+            Expr::Binary {
+                operator,
+                lhs_operand: Box::new(lhs_operand),
+                rhs_operand: Box::new(rhs_operand),
+            }
+        }
+        Expr::Call { function, arguments } => Expr::Call {
+            function,
+            arguments: arguments.into_iter().map(fold_expr).collect(),
+        },
+        Expr::Index { array, index } => Expr::Index {
+            array: Box::new(fold_expr(*array)),
+            index: Box::new(fold_expr(*index)),
+        },
+        Expr::Instance { class, properties } => Expr::Instance {
+            class,
+            properties: properties.into_iter().map(fold_stmt).collect(),
+        },
+        Expr::Unary { operator, operand } => {
+            let operand = fold_expr(*operand);
+
+            if let Expr::Literal(literal) = &operand {
+                if let Some(literal) = fold_unary(&operator, literal) {
+                    return Expr::Literal(literal);
+                }
+            }
+
+            Expr::Unary {
+                operator,
+                operand: Box::new(operand),
+            }
+        }
+        expr => expr,
+    }
+}
+
+///
+///
+///
+fn fold_binary(
+    operator: &BinOp,
+    lhs: &Lit,
+    rhs: &Lit,
+) -> Option<Lit> {
+    // Mirrors the semantics of the VM, don't fold if the VM would fail (e.g. division by zero).
+    let literal = match (lhs, rhs) {
+        (Lit::Boolean(lhs), Lit::Boolean(rhs)) => match operator {
+            BinOp::And => Lit::Boolean(*lhs && *rhs),
+            BinOp::Or => Lit::Boolean(*lhs || *rhs),
+            BinOp::Eq => Lit::Boolean(lhs == rhs),
+            BinOp::Ne => Lit::Boolean(lhs != rhs),
+            _ => return None,
+        },
+        (Lit::Integer(lhs), Lit::Integer(rhs)) => match operator {
+            BinOp::Add => Lit::Integer(lhs.checked_add(*rhs)?),
+            BinOp::Sub => Lit::Integer(lhs.checked_sub(*rhs)?),
+            BinOp::Mul => Lit::Integer(lhs.checked_mul(*rhs)?),
+            BinOp::Div => Lit::Integer(lhs.checked_div(*rhs)?),
+            BinOp::Eq => Lit::Boolean(lhs == rhs),
+            BinOp::Ne => Lit::Boolean(lhs != rhs),
+            BinOp::Lt => Lit::Boolean(lhs < rhs),
+            BinOp::Gt => Lit::Boolean(lhs > rhs),
+            BinOp::Le => Lit::Boolean(lhs <= rhs),
+            BinOp::Ge => Lit::Boolean(lhs >= rhs),
+            _ => return None,
+        },
+        (Lit::Integer(_), Lit::Real(_)) | (Lit::Real(_), Lit::Integer(_)) | (Lit::Real(_), Lit::Real(_)) => {
+            let both_real = matches!((lhs, rhs), (Lit::Real(_), Lit::Real(_)));
+            let lhs = as_real(lhs)?;
+            let rhs = as_real(rhs)?;
+
+            match operator {
+                BinOp::Add => Lit::Real(lhs + rhs),
+                BinOp::Sub => Lit::Real(lhs - rhs),
+                BinOp::Mul => Lit::Real(lhs * rhs),
+                BinOp::Div => Lit::Real(lhs / rhs),
+                // The VM never considers an integer and a real equal.
+                BinOp::Eq => Lit::Boolean(both_real && lhs == rhs),
+                BinOp::Ne => Lit::Boolean(!(both_real && lhs == rhs)),
+                BinOp::Lt => Lit::Boolean(lhs < rhs),
+                BinOp::Gt => Lit::Boolean(lhs > rhs),
+                // Compiled as !(lhs > rhs) and !(lhs < rhs), respectively, so NaN compares true.
+                BinOp::Le => Lit::Boolean(!matches!(lhs.partial_cmp(&rhs), Some(Ordering::Greater))),
+                BinOp::Ge => Lit::Boolean(!matches!(lhs.partial_cmp(&rhs), Some(Ordering::Less))),
+                _ => return None,
+            }
+        }
+        (Lit::String(lhs), Lit::String(rhs)) => match operator {
+            BinOp::Add => Lit::String(format!("{}{}", lhs, rhs)),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(literal)
+}
+
+///
+///
+///
+fn fold_unary(
+    operator: &UnOp,
+    operand: &Lit,
+) -> Option<Lit> {
+    let literal = match (operator, operand) {
+        (UnOp::Neg, Lit::Integer(integer)) => Lit::Integer(integer.checked_neg()?),
+        (UnOp::Neg, Lit::Real(real)) => Lit::Real(-real),
+        (UnOp::Not, Lit::Boolean(boolean)) => Lit::Boolean(!boolean),
+        _ => return None,
+    };
+
+    Some(literal)
+}
+
+///
+///
+///
+fn as_real(literal: &Lit) -> Option<f64> {
+    match literal {
+        Lit::Integer(integer) => Some(*integer as f64),
+        Lit::Real(real) => Some(*real),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Instruction {
+    offset: usize,
+    opcode: u8,
+    operands: Vec<u8>,
+}
+
+///
+///
+///
+pub fn optimize_function(function: FunctionMut) -> FunctionMut {
+    let FunctionMut { arity, chunk, name } = function;
+
+    FunctionMut::new(name, arity, optimize_chunk(chunk))
+}
+
+///
+///
+///
+fn optimize_chunk(chunk: ChunkMut) -> ChunkMut {
+    let constants: Vec<Value> = chunk.constants.into_iter().map(optimize_constant).collect();
+
+    // Decode instructions, and note every offset that is the target of a jump.
+    let mut instructions = Vec::new();
+    let mut targets = HashSet::new();
+
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        let opcode = chunk.code[offset];
+        let operands = chunk.code[offset + 1..offset + 1 + operands_len(opcode)].to_vec();
+
+        if let Some(target) = jump_target(offset, opcode, &operands) {
+            targets.insert(target);
+        }
+
+        let length = 1 + operands.len();
+        instructions.push(Instruction {
+            offset,
+            opcode,
+            operands,
+        });
+
+        offset += length;
+    }
+
+    let mut optimized: Vec<Instruction> = Vec::with_capacity(instructions.len());
+    for mut instruction in instructions {
+        // Small integers have a dedicated opcode, without an operand.
+        let constant = match instruction.opcode {
+            OP_CONSTANT => Some(instruction.operands[0] as usize),
+            OP_CONSTANT_LONG => Some(u16::from_be_bytes([instruction.operands[0], instruction.operands[1]]) as usize),
+            _ => None,
+        };
+
+        if let Some(constant) = constant {
+            if let Some(Value::Integer(integer)) = constants.get(constant) {
+                let opcode = match *integer {
+                    -2 => Some(OP_CONST_MINUS_TWO),
+                    -1 => Some(OP_CONST_MINUS_ONE),
+                    0 => Some(OP_CONST_ZERO),
+                    1 => Some(OP_CONST_ONE),
+                    2 => Some(OP_CONST_TWO),
+                    _ => None,
+                };
+
+                if let Some(opcode) = opcode {
+                    instruction.opcode = opcode;
+                    instruction.operands.clear();
+                }
+            }
+        }
+
+        // Merge consecutive pops, unless a jump lands in between.
+        if !targets.contains(&instruction.offset) {
+            if let (Some(n), Some(previous)) = (pop_count(&instruction), optimized.last_mut()) {
+                if let Some(m) = pop_count(previous) {
+                    if n + m <= u8::MAX as usize {
+                        previous.opcode = OP_POP_N;
+                        previous.operands = vec![(n + m) as u8];
+                        continue;
+                    }
+                }
+            }
+        }
+
+        optimized.push(instruction);
+    }
+
+    // Assign new offsets, and re-encode (relocating jumps).
+    let mut relocations = HashMap::new();
+    let mut new_offset = 0;
+    for instruction in &optimized {
+        relocations.insert(instruction.offset, new_offset);
+        new_offset += 1 + instruction.operands.len();
+    }
+    relocations.insert(chunk.code.len(), new_offset);
+
+    let mut code = BytesMut::with_capacity(new_offset);
+    for instruction in optimized {
+        let start = relocations[&instruction.offset];
+
+        if let Some(target) = jump_target(instruction.offset, instruction.opcode, &instruction.operands) {
+            let target = relocations[&target];
+            let jump = if instruction.opcode == OP_JUMP_BACK {
+                (start + 3 - target) as u16
+            } else {
+                (target - start - 3) as u16
+            };
+
+            code.put_u8(instruction.opcode);
+            code.put_u16(jump);
+        } else {
+            code.put_u8(instruction.opcode);
+            code.put_slice(&instruction.operands);
+        }
+    }
+
+    ChunkMut::new(code, constants)
+}
+
+///
+///
+///
+fn optimize_constant(constant: Value) -> Value {
+    match constant {
+        Value::Function(function) => Value::Function(optimize_spec_function(function)),
+        Value::Class(mut class) => {
+            class.methods = class
+                .methods
+                .into_iter()
+                .map(|(name, method)| (name, optimize_spec_function(method)))
+                .collect();
+
+            Value::Class(class)
+        }
+        constant => constant,
+    }
+}
+
+///
+///
+///
+fn optimize_spec_function(function: SpecFunction) -> SpecFunction {
+    let function: FunctionMut = function.into();
+    optimize_function(function).into()
+}
+
+///
+///
+///
+fn jump_target(
+    offset: usize,
+    opcode: u8,
+    operands: &[u8],
+) -> Option<usize> {
+    let jump = || u16::from_be_bytes([operands[0], operands[1]]) as usize;
+
+    match opcode {
+        OP_JUMP | OP_JUMP_IF_FALSE | OP_JUMP_IF_UNIT | OP_TRY => Some(offset + 3 + jump()),
+        OP_JUMP_BACK => Some(offset + 3 - jump()),
+        _ => None,
+    }
+}
+
+///
+///
+///
+fn pop_count(instruction: &Instruction) -> Option<usize> {
+    match instruction.opcode {
+        OP_POP => Some(1),
+        OP_POP_N => Some(instruction.operands[0] as usize),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_arithmetic() {
+        let expr = Expr::Binary {
+            operator: BinOp::Add,
+            lhs_operand: Box::new(Expr::Literal(Lit::Integer(1))),
+            rhs_operand: Box::new(Expr::Binary {
+                operator: BinOp::Mul,
+                lhs_operand: Box::new(Expr::Literal(Lit::Integer(2))),
+                rhs_operand: Box::new(Expr::Literal(Lit::Real(1.5))),
+            }),
+        };
+
+        assert!(matches!(fold_expr(expr), Expr::Literal(Lit::Real(r)) if r == 4.0));
+    }
+
+    #[test]
+    fn test_fold_division_by_zero() {
+        let expr = Expr::Binary {
+            operator: BinOp::Div,
+            lhs_operand: Box::new(Expr::Literal(Lit::Integer(1))),
+            rhs_operand: Box::new(Expr::Literal(Lit::Integer(0))),
+        };
+
+        assert!(matches!(fold_expr(expr), Expr::Binary { .. }));
+    }
+
+    #[test]
+    fn test_fold_coalesce() {
+        let expr = Expr::Binary {
+            operator: BinOp::Coalesce,
+            lhs_operand: Box::new(Expr::Literal(Lit::Unit)),
+            rhs_operand: Box::new(Expr::Literal(Lit::Integer(1))),
+        };
+
+        assert!(matches!(fold_expr(expr), Expr::Literal(Lit::Integer(1))));
+    }
+
+    #[test]
+    fn test_merge_pops_across_jump() {
+        // if (x) {} followed by a pop: the jump target must not be merged.
+        let mut chunk = ChunkMut::default();
+        chunk.write(OP_TRUE);
+        chunk.write(OP_JUMP_IF_FALSE);
+        chunk.write_pair(0x00, 0x02);
+        chunk.write(OP_POP);
+        chunk.write(OP_POP);
+        chunk.write(OP_POP);
+        chunk.write(OP_POP);
+
+        let chunk = optimize_chunk(chunk);
+        assert_eq!(
+            &chunk.code[..],
+            &[OP_TRUE, OP_JUMP_IF_FALSE, 0x00, 0x02, OP_POP_N, 0x02, OP_POP_N, 0x02]
+        );
+    }
+}
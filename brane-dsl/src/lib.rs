@@ -6,6 +6,8 @@ extern crate log;
 mod errors;
 #[path = "generator/generator.rs"]
 mod generator;
+#[path = "generator/optimizer.rs"]
+mod optimizer;
 #[path = "parser/parser.rs"]
 mod parser;
 #[path = "scanner/scanner.rs"]
@@ -13,9 +15,11 @@ mod scanner;
 
 use crate::parser::{bakery, bscript};
 use crate::scanner::{Span, Tokens};
-use anyhow::Result;
+use anyhow::{Context, Result};
 use brane_bvm::bytecode::FunctionMut;
+use specifications::common::{Parameter, Value};
 use specifications::package::PackageIndex;
+use std::collections::HashMap;
 
 #[derive(Clone, Debug)]
 pub enum Lang {
@@ -26,6 +30,7 @@ pub enum Lang {
 #[derive(Clone, Debug)]
 pub struct CompilerOptions {
     pub lang: Lang,
+    pub optimize: bool,
 }
 
 impl CompilerOptions {
@@ -33,12 +38,21 @@ impl CompilerOptions {
     ///
     ///
     pub fn new(lang: Lang) -> Self {
-        CompilerOptions { lang }
+        CompilerOptions { lang, optimize: false }
+    }
+
+    ///
+    ///
+    ///
+    pub fn optimized(lang: Lang) -> Self {
+        CompilerOptions { lang, optimize: true }
     }
 }
 
 #[derive(Clone, Debug)]
-pub struct CompilerState {}
+pub struct CompilerState {
+    pub parameters: Vec<Parameter>,
+}
 
 impl Default for CompilerState {
     fn default() -> Self {
@@ -51,7 +65,9 @@ impl CompilerState {
     ///
     ///
     pub fn new() -> Self {
-        CompilerState {}
+        CompilerState {
+            parameters: Default::default(),
+        }
     }
 }
 
@@ -96,7 +112,16 @@ impl Compiler {
                     Lang::BraneScript => bscript::parse_ast(tokens),
                 };
 
+                // Parameters are specific to the program that was compiled last.
+                if let Ok((_, program)) = &program {
+                    self.state.parameters = generator::parameters(program)?;
+                }
+
                 match program {
+                    Ok((_, program)) if self.options.optimize => {
+                        let program = optimizer::optimize_program(program);
+                        generator::compile(program).map(optimizer::optimize_function)
+                    }
                     Ok((_, program)) => generator::compile(program),
                     Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
                         bail!("{}", errors::convert_parser_error(tokens, e));
@@ -110,4 +135,36 @@ impl Compiler {
             _ => bail!("Compiler error: Unkown error from scanner."),
         }
     }
+
+    ///
+    ///
+    ///
+    pub fn bind_parameters(
+        &self,
+        values: HashMap<String, Value>,
+    ) -> Result<HashMap<String, Value>> {
+        let mut values = values;
+        let mut bound = HashMap::new();
+
+        for parameter in &self.state.parameters {
+            let value = match values.remove(&parameter.name) {
+                Some(value) => value
+                    .coerce(&parameter.data_type)
+                    .with_context(|| format!("Invalid value for parameter '{}'.", parameter.name))?,
+                None => match &parameter.default {
+                    Some(default) => default.clone(),
+                    None if parameter.optional.unwrap_or_default() => Value::Unit,
+                    None => bail!("Missing value for parameter '{}'.", parameter.name),
+                },
+            };
+
+            bound.insert(parameter.name.clone(), value);
+        }
+
+        if let Some(name) = values.keys().next() {
+            bail!("Unknown parameter '{}'.", name);
+        }
+
+        Ok(bound)
+    }
 }
@@ -11,6 +11,7 @@ pub enum Stmt {
     DeclareClass {
         ident: Ident,
         properties: HashMap<Ident, Ident>,
+        defaults: HashMap<Ident, Expr>,
         methods: HashMap<Ident, Stmt>,
     },
     DeclareFunc {
@@ -18,6 +19,15 @@ pub enum Stmt {
         params: Vec<Ident>,
         body: Block,
     },
+    DeclareImpl {
+        ident: Ident,
+        methods: HashMap<Ident, Stmt>,
+    },
+    DeclareParam {
+        ident: Ident,
+        class: Ident,
+        default: Option<Expr>,
+    },
     Expr(Expr),
     For {
         initializer: Box<Stmt>,
@@ -46,8 +56,19 @@ pub enum Stmt {
     Property {
         ident: Ident,
         class: Ident,
+        default: Option<Expr>,
+    },
+    Retry {
+        retries: Expr,
+        backoff: Option<Expr>,
+        block: Block,
     },
     Return(Option<Expr>),
+    Try {
+        block: Block,
+        ident: Ident,
+        handler: Block,
+    },
     While {
         condition: Expr,
         consequent: Block,
@@ -126,6 +147,10 @@ pub enum BinOp {
     Div,
     /// The `.` operator (nesting)
     Dot,
+    /// The `?.` operator (safe nesting)
+    SafeDot,
+    /// The `??` operator (null-coalescing)
+    Coalesce,
     /// The `&&` operator (logical and)
     And,
     /// The `||` operator (logical or)
@@ -150,13 +175,14 @@ impl BinOp {
     ///
     pub fn binding_power(&self) -> (u8, u8) {
         match &self {
-            BinOp::And | BinOp::Or => (1, 2),   // Conditional
-            BinOp::Eq | BinOp::Ne => (3, 4),    // Equality
-            BinOp::Lt | BinOp::Gt => (5, 6),    // Comparison
-            BinOp::Le | BinOp::Ge => (5, 6),    // Comparison
-            BinOp::Add | BinOp::Sub => (7, 8),  // Terms
-            BinOp::Mul | BinOp::Div => (9, 10), // Factors
-            BinOp::Dot => (13, 14),             // Nesting
+            BinOp::And | BinOp::Or => (1, 2),        // Conditional
+            BinOp::Eq | BinOp::Ne => (3, 4),         // Equality
+            BinOp::Lt | BinOp::Gt => (5, 6),         // Comparison
+            BinOp::Le | BinOp::Ge => (5, 6),         // Comparison
+            BinOp::Coalesce => (7, 8),               // Null-coalescing
+            BinOp::Add | BinOp::Sub => (9, 10),      // Terms
+            BinOp::Mul | BinOp::Div => (11, 12),     // Factors
+            BinOp::Dot | BinOp::SafeDot => (15, 16), // Nesting
         }
     }
 }
@@ -180,9 +206,9 @@ impl UnOp {
     ///
     pub fn binding_power(&self) -> (u8, u8) {
         match &self {
-            UnOp::Not => (0, 11),
-            UnOp::Neg => (0, 11),
-            UnOp::Idx => (11, 0),
+            UnOp::Not => (0, 13),
+            UnOp::Neg => (0, 13),
+            UnOp::Idx => (13, 0),
             UnOp::Prio => (0, 0), // Handled seperatly by pratt parser.
         }
     }
@@ -1,7 +1,8 @@
-use super::ast::Stmt;
+use super::ast::{Expr, Ident, Stmt};
 use crate::parser::{expression, identifier};
 use crate::scanner::{Token, Tokens};
 use crate::tag_token;
+use brane_bvm::executor::{EXCLUDE_CLASS, LABELS_CLASS};
 use nom::error::{ContextError, ErrorKind, ParseError, VerboseError};
 use nom::{branch, combinator as comb, multi, sequence as seq};
 use nom::{IResult, Parser};
@@ -33,11 +34,15 @@ pub fn parse_stmt<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens<'a>>>(
         parallel_stmt,
         declare_class_stmt,
         declare_func_stmt,
+        declare_impl_stmt,
+        declare_param_stmt,
         expr_stmt,
         if_stmt,
         import_stmt,
         let_assign_stmt,
+        retry_stmt,
         return_stmt,
+        try_stmt,
         while_stmt,
     ))
     .parse(input)
@@ -86,7 +91,7 @@ pub fn on_stmt<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens<'a>>>(
 ) -> IResult<Tokens, Stmt, E> {
     comb::map(
         seq::pair(
-            seq::preceded(tag_token!(Token::On), comb::cut(expression::parse)),
+            seq::preceded(tag_token!(Token::On), comb::cut(location_constraint)),
             comb::cut(seq::delimited(
                 tag_token!(Token::LeftBrace),
                 multi::many0(parse_stmt),
@@ -98,6 +103,86 @@ pub fn on_stmt<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens<'a>>>(
     .parse(input)
 }
 
+///
+///
+///
+pub fn location_constraint<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens<'a>>>(
+    input: Tokens<'a>
+) -> IResult<Tokens, Expr, E> {
+    // Label selectors and exclusions are instances of built-in classes.
+    branch::alt((
+        comb::map(
+            seq::delimited(
+                tag_token!(Token::LeftBrace),
+                seq::pair(
+                    label_selector,
+                    multi::many0(seq::preceded(tag_token!(Token::Comma), label_selector)),
+                ),
+                tag_token!(Token::RightBrace),
+            ),
+            |(h, e)| Expr::Instance {
+                class: Ident(LABELS_CLASS.to_string()),
+                properties: [&[h], &e[..]].concat().to_vec(),
+            },
+        ),
+        comb::map(seq::preceded(tag_token!(Token::Not), expression::parse), |locations| {
+            Expr::Instance {
+                class: Ident(EXCLUDE_CLASS.to_string()),
+                properties: vec![Stmt::Assign(Ident(String::from("locations")), locations)],
+            }
+        }),
+        expression::parse,
+    ))
+    .parse(input)
+}
+
+///
+///
+///
+pub fn label_selector<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens<'a>>>(
+    input: Tokens<'a>
+) -> IResult<Tokens, Stmt, E> {
+    comb::map(
+        seq::separated_pair(identifier::parse, tag_token!(Token::Colon), expression::parse),
+        |(ident, expr)| Stmt::Assign(ident, expr),
+    )
+    .parse(input)
+}
+
+///
+///
+///
+pub fn retry_stmt<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens<'a>>>(
+    input: Tokens<'a>
+) -> IResult<Tokens, Stmt, E> {
+    comb::map(
+        seq::preceded(
+            tag_token!(Token::Retry),
+            comb::cut(seq::pair(
+                seq::delimited(
+                    tag_token!(Token::LeftParen),
+                    seq::pair(
+                        expression::parse,
+                        comb::opt(seq::preceded(tag_token!(Token::Comma), expression::parse)),
+                    ),
+                    tag_token!(Token::RightParen),
+                ),
+                seq::delimited(
+                    tag_token!(Token::LeftBrace),
+                    multi::many0(parse_stmt),
+                    tag_token!(Token::RightBrace),
+                ),
+            )),
+        ),
+        |((retries, backoff), block)| Stmt::Retry {
+            retries,
+            backoff,
+            block,
+        },
+    )
+    .parse(input)
+}
+
 ///
 ///
 ///
@@ -175,12 +260,16 @@ pub fn declare_class_stmt<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens<'a
         )),
         |(ident, body)| {
             let mut properties = HashMap::new();
+            let mut defaults = HashMap::new();
             let mut methods = HashMap::new();
 
             for stmt in body.iter() {
                 match stmt {
-                    Stmt::Property { ident, class } => {
+                    Stmt::Property { ident, class, default } => {
                         properties.insert(ident.clone(), class.clone());
+                        if let Some(default) = default {
+                            defaults.insert(ident.clone(), default.clone());
+                        }
                     }
                     Stmt::DeclareFunc { ident, .. } => {
                         methods.insert(ident.clone(), stmt.clone());
@@ -192,6 +281,7 @@ pub fn declare_class_stmt<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens<'a
             Stmt::DeclareClass {
                 ident,
                 properties,
+                defaults,
                 methods,
             }
         },
@@ -199,6 +289,36 @@ pub fn declare_class_stmt<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens<'a
     .parse(input)
 }
 
+///
+///
+///
+pub fn declare_impl_stmt<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens<'a>>>(
+    input: Tokens<'a>
+) -> IResult<Tokens, Stmt, E> {
+    comb::map(
+        seq::tuple((
+            seq::preceded(tag_token!(Token::Impl), comb::cut(identifier::parse)),
+            comb::cut(seq::delimited(
+                tag_token!(Token::LeftBrace),
+                multi::many0(declare_func_stmt),
+                tag_token!(Token::RightBrace),
+            )),
+        )),
+        |(ident, body)| {
+            let methods = body
+                .into_iter()
+                .map(|stmt| match &stmt {
+                    Stmt::DeclareFunc { ident, .. } => (ident.clone(), stmt),
+                    _ => unreachable!(),
+                })
+                .collect();
+
+            Stmt::DeclareImpl { ident, methods }
+        },
+    )
+    .parse(input)
+}
+
 ///
 ///
 ///
@@ -207,10 +327,53 @@ pub fn declare_property_stmt<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens
 ) -> IResult<Tokens, Stmt, E> {
     comb::map(
         seq::terminated(
-            seq::separated_pair(identifier::parse, tag_token!(Token::Colon), identifier::parse),
+            seq::tuple((
+                identifier::parse,
+                seq::preceded(tag_token!(Token::Colon), type_ident),
+                comb::opt(seq::preceded(tag_token!(Token::Assign), expression::parse)),
+            )),
             comb::cut(tag_token!(Token::Semicolon)),
         ),
-        |(ident, class)| Stmt::Property { ident, class },
+        |(ident, class, default)| Stmt::Property { ident, class, default },
+    )
+    .parse(input)
+}
+
+///
+///
+///
+pub fn declare_param_stmt<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens<'a>>>(
+    input: Tokens<'a>
+) -> IResult<Tokens, Stmt, E> {
+    comb::map(
+        seq::preceded(
+            tag_token!(Token::Param),
+            comb::cut(seq::terminated(
+                seq::tuple((
+                    identifier::parse,
+                    seq::preceded(tag_token!(Token::Colon), type_ident),
+                    comb::opt(seq::preceded(tag_token!(Token::Assign), expression::parse)),
+                )),
+                tag_token!(Token::Semicolon),
+            )),
+        ),
+        |(ident, class, default)| Stmt::DeclareParam { ident, class, default },
+    )
+    .parse(input)
+}
+
+///
+///
+///
+pub fn type_ident<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens<'a>>>(
+    input: Tokens<'a>
+) -> IResult<Tokens, Ident, E> {
+    comb::map(
+        seq::pair(identifier::parse, comb::opt(tag_token!(Token::Question))),
+        |(Ident(data_type), optional)| match optional {
+            Some(_) => Ident(format!("{}?", data_type)),
+            None => Ident(data_type),
+        },
     )
     .parse(input)
 }
@@ -296,6 +459,41 @@ pub fn if_stmt<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens<'a>>>(
     .parse(input)
 }
 
+///
+///
+///
+pub fn try_stmt<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens<'a>>>(
+    input: Tokens<'a>
+) -> IResult<Tokens, Stmt, E> {
+    comb::map(
+        seq::preceded(
+            tag_token!(Token::Try),
+            comb::cut(seq::tuple((
+                seq::delimited(
+                    tag_token!(Token::LeftBrace),
+                    multi::many0(parse_stmt),
+                    tag_token!(Token::RightBrace),
+                ),
+                seq::preceded(
+                    tag_token!(Token::Catch),
+                    seq::delimited(
+                        tag_token!(Token::LeftParen),
+                        identifier::parse,
+                        tag_token!(Token::RightParen),
+                    ),
+                ),
+                seq::delimited(
+                    tag_token!(Token::LeftBrace),
+                    multi::many0(parse_stmt),
+                    tag_token!(Token::RightBrace),
+                ),
+            ))),
+        ),
+        |(block, ident, handler)| Stmt::Try { block, ident, handler },
+    )
+    .parse(input)
+}
+
 ///
 ///
 ///
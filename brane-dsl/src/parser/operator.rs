@@ -39,6 +39,8 @@ pub fn binary_operator<'a, E: ParseError<Tokens<'a>> + ContextError<Tokens<'a>>>
         comb::map(tag_token!(Token::Slash), |_| BinOp::Div),
         comb::map(tag_token!(Token::Star), |_| BinOp::Mul),
         comb::map(tag_token!(Token::Dot), |_| BinOp::Dot),
+        comb::map(tag_token!(Token::SafeDot), |_| BinOp::SafeDot),
+        comb::map(tag_token!(Token::Coalesce), |_| BinOp::Coalesce),
     ))
     .parse(input)
 }
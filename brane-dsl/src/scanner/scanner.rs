@@ -42,23 +42,33 @@ fn scan_token<'a, E: ParseError<Span<'a>> + ContextError<Span<'a>>>(input: Span<
 ///
 ///
 fn keyword<'a, E: ParseError<Span<'a>> + ContextError<Span<'a>>>(input: Span<'a>) -> IResult<Span<'a>, Token, E> {
-    ws0(branch::alt((
-        comb::map(bc::tag("break"), Token::Break),
-        comb::map(bc::tag("class"), Token::Class),
-        comb::map(bc::tag("continue"), Token::Continue),
-        comb::map(bc::tag("else"), Token::Else),
-        comb::map(bc::tag("for"), Token::For),
-        comb::map(bc::tag("func"), Token::Function),
-        comb::map(bc::tag("if"), Token::If),
-        comb::map(bc::tag("import"), Token::Import),
-        comb::map(bc::tag("let"), Token::Let),
-        comb::map(bc::tag("new"), Token::New),
-        comb::map(bc::tag("on"), Token::On),
-        comb::map(bc::tag("parallel"), Token::Parallel),
-        comb::map(bc::tag("return"), Token::Return),
-        comb::map(bc::tag("unit"), Token::Unit),
-        comb::map(bc::tag("while"), Token::While),
-    )))
+    // Keywords shouldn't match the start of an identifier, e.g. `parameters`.
+    ws0(seq::terminated(
+        branch::alt((
+            comb::map(bc::tag("break"), Token::Break),
+            comb::map(bc::tag("catch"), Token::Catch),
+            comb::map(bc::tag("class"), Token::Class),
+            comb::map(bc::tag("continue"), Token::Continue),
+            comb::map(bc::tag("else"), Token::Else),
+            comb::map(bc::tag("for"), Token::For),
+            comb::map(bc::tag("func"), Token::Function),
+            comb::map(bc::tag("if"), Token::If),
+            comb::map(bc::tag("impl"), Token::Impl),
+            comb::map(bc::tag("import"), Token::Import),
+            comb::map(bc::tag("let"), Token::Let),
+            comb::map(bc::tag("new"), Token::New),
+            comb::map(bc::tag("not"), Token::Not),
+            comb::map(bc::tag("on"), Token::On),
+            comb::map(bc::tag("parallel"), Token::Parallel),
+            comb::map(bc::tag("param"), Token::Param),
+            comb::map(bc::tag("retry"), Token::Retry),
+            comb::map(bc::tag("return"), Token::Return),
+            comb::map(bc::tag("try"), Token::Try),
+            comb::map(bc::tag("unit"), Token::Unit),
+            comb::map(bc::tag("while"), Token::While),
+        )),
+        comb::not(branch::alt((cc::alphanumeric1, bc::tag("_")))),
+    ))
     .parse(input)
 }
 
@@ -73,11 +83,14 @@ fn operator<'a, E: ParseError<Span<'a>> + ContextError<Span<'a>>>(input: Span<'a
         comb::map(bc::tag(">="), Token::GreaterOrEqual),
         comb::map(bc::tag("<="), Token::LessOrEqual),
         comb::map(bc::tag("!="), Token::NotEqual),
+        comb::map(bc::tag("??"), Token::Coalesce),
+        comb::map(bc::tag("?."), Token::SafeDot),
         // One character token
         comb::map(bc::tag("!"), Token::Not),
         comb::map(bc::tag("&"), Token::And),
         comb::map(bc::tag("*"), Token::Star),
         comb::map(bc::tag("+"), Token::Plus),
+        comb::map(bc::tag("?"), Token::Question),
         comb::map(bc::tag("-"), Token::Minus),
         comb::map(bc::tag("/"), Token::Slash),
         comb::map(bc::tag("<"), Token::Less),
@@ -126,3 +139,28 @@ fn identifier<'a, E: ParseError<Span<'a>> + ContextError<Span<'a>>>(input: Span<
 pub fn ws0<'a, O, E: ParseError<Span<'a>>, F: Parser<Span<'a>, O, E>>(f: F) -> impl Parser<Span<'a>, O, E> {
     seq::delimited(cc::multispace0, f, cc::multispace0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyword_prefix_is_identifier() {
+        for input in &["parameters", "trying", "format", "newest", "on_start"] {
+            let (_, tokens) = scan_tokens(Span::new(input)).unwrap();
+
+            assert_eq!(tokens.len(), 1, "{}", input);
+            assert!(matches!(tokens[0], Token::Ident(_)), "{}", input);
+            assert_eq!(tokens[0].as_string(), *input);
+        }
+    }
+
+    #[test]
+    fn test_keyword_before_identifier() {
+        let (_, tokens) = scan_tokens(Span::new("param parameters")).unwrap();
+
+        assert_eq!(tokens.len(), 2);
+        assert!(matches!(tokens[0], Token::Param(_)));
+        assert!(matches!(tokens[1], Token::Ident(_)));
+    }
+}
@@ -13,6 +13,9 @@ pub enum Token<'a> {
     /// `break`
     Break(Span<'a>),
 
+    /// `catch`
+    Catch(Span<'a>),
+
     /// `class`
     Class(Span<'a>),
 
@@ -31,6 +34,9 @@ pub enum Token<'a> {
     /// `if`
     If(Span<'a>),
 
+    /// `impl`
+    Impl(Span<'a>),
+
     /// `import`
     Import(Span<'a>),
 
@@ -49,9 +55,18 @@ pub enum Token<'a> {
     /// `parallel`
     Parallel(Span<'a>),
 
+    /// `param`
+    Param(Span<'a>),
+
+    /// `retry`
+    Retry(Span<'a>),
+
     /// `return`
     Return(Span<'a>),
 
+    /// `try`
+    Try(Span<'a>),
+
     /// `unit`
     Unit(Span<'a>),
 
@@ -91,6 +106,9 @@ pub enum Token<'a> {
     /// :=
     Assign(Span<'a>),
 
+    /// ??
+    Coalesce(Span<'a>),
+
     /// =
     Equal(Span<'a>),
 
@@ -118,6 +136,12 @@ pub enum Token<'a> {
     /// +
     Plus(Span<'a>),
 
+    /// ?
+    Question(Span<'a>),
+
+    /// ?.
+    SafeDot(Span<'a>),
+
     /// /
     Slash(Span<'a>),
 
@@ -186,13 +210,15 @@ impl<'a> Token<'a> {
         use Token::*;
 
         match self {
-            And(span) | Break(span) | Class(span) | Continue(span) | Else(span) | For(span) | Function(span)
-            | If(span) | Import(span) | Let(span) | On(span) | Or(span) | Return(span) | Unit(span) | While(span)
-            | Dot(span) | Colon(span) | Comma(span) | LeftBrace(span) | LeftBracket(span) | LeftParen(span)
-            | Parallel(span) | RightBrace(span) | RightBracket(span) | RightParen(span) | Semicolon(span)
-            | Assign(span) | Equal(span) | Greater(span) | GreaterOrEqual(span) | Less(span) | LessOrEqual(span)
-            | Minus(span) | Not(span) | NotEqual(span) | Plus(span) | Slash(span) | Star(span) | Boolean(span)
-            | Integer(span) | Real(span) | SemVer(span) | String(span) | Ident(span) | New(span) => span,
+            And(span) | Break(span) | Catch(span) | Class(span) | Continue(span) | Else(span) | For(span)
+            | Function(span) | If(span) | Impl(span) | Import(span) | Let(span) | On(span) | Or(span) | Retry(span)
+            | Return(span) | Try(span) | Unit(span) | While(span) | Dot(span) | Colon(span) | Comma(span)
+            | LeftBrace(span) | LeftBracket(span) | LeftParen(span) | Parallel(span) | Param(span)
+            | RightBrace(span) | RightBracket(span) | RightParen(span) | Semicolon(span) | Assign(span)
+            | Coalesce(span) | Equal(span) | Greater(span) | GreaterOrEqual(span) | Less(span) | LessOrEqual(span)
+            | Minus(span) | Not(span) | NotEqual(span) | Plus(span) | Question(span) | SafeDot(span) | Slash(span)
+            | Star(span) | Boolean(span) | Integer(span) | Real(span) | SemVer(span) | String(span) | Ident(span)
+            | New(span) => span,
             // None should have been filtered out already.
             None => unreachable!(),
         }
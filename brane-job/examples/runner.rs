@@ -224,6 +224,9 @@ async fn execute_run_action(
         Some(action.image.clone()),
         action.command.clone(),
         None,
+        None,
+        None,
+        None,
     );
 
     let mut payload = BytesMut::with_capacity(64);
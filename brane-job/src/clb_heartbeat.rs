@@ -1,7 +1,93 @@
-use crate::interface::{Callback, Event};
+use crate::interface::{Callback, CallbackKind, Event, EventKind};
 use anyhow::Result;
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
 
+#[derive(Clone, Debug)]
+pub struct Heartbeat {
+    pub application: String,
+    pub location: String,
+    pub last_seen: Instant,
+}
+
+impl Heartbeat {
+    ///
+    ///
+    ///
+    pub fn new(
+        application: String,
+        location: String,
+    ) -> Self {
+        Heartbeat {
+            application,
+            location,
+            last_seen: Instant::now(),
+        }
+    }
+}
+
+///
+///
+///
 pub fn handle(callback: Callback) -> Result<Vec<(String, Event)>> {
+    // Heartbeats are tracked for every callback (see `track`), but not relayed as events.
     debug!("Received heartbeat callback: {:?}", callback);
     Ok(vec![])
 }
+
+///
+///
+///
+pub fn track(
+    callback: &Callback,
+    heartbeats: &DashMap<String, Heartbeat>,
+) {
+    // Only jobs that send heartbeats can miss them, other callbacks merely keep them alive.
+    if callback.kind == CallbackKind::Heartbeat as i32 {
+        let heartbeat = Heartbeat::new(callback.application.clone(), callback.location.clone());
+        heartbeats.insert(callback.job.clone(), heartbeat);
+    } else if let Some(mut heartbeat) = heartbeats.get_mut(&callback.job) {
+        heartbeat.last_seen = Instant::now();
+    }
+}
+
+///
+///
+///
+pub fn collect_lost(
+    heartbeats: &DashMap<String, Heartbeat>,
+    timeout: Duration,
+) -> Vec<(String, Event)> {
+    let lost: Vec<String> = heartbeats
+        .iter()
+        .filter(|entry| entry.value().last_seen.elapsed() > timeout)
+        .map(|entry| entry.key().clone())
+        .collect();
+
+    lost.into_iter()
+        .filter_map(|job_id| heartbeats.remove(&job_id))
+        .map(|(job_id, heartbeat)| {
+            warn!(
+                "Job '{}' at location '{}' missed its heartbeats, considering it lost.",
+                job_id, heartbeat.location
+            );
+
+            // Like STOPPED, a LOST event is always the last event of a job.
+            let order = i32::MAX as u32;
+            let key = format!("{}#{}", job_id, order);
+            let category = String::from("job");
+            let event = Event::new(
+                EventKind::Lost,
+                job_id,
+                heartbeat.application,
+                heartbeat.location,
+                category,
+                order,
+                None,
+                None,
+            );
+
+            (key, event)
+        })
+        .collect()
+}
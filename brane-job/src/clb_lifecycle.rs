@@ -19,6 +19,7 @@ pub fn handle(callback: Callback) -> Result<Vec<(String, Event)>> {
         CallbackKind::Finished => EventKind::Finished,
         CallbackKind::Stopped => EventKind::Stopped,
         CallbackKind::Failed => EventKind::Failed,
+        CallbackKind::Output => unreachable!(),
     };
 
     let key = format!("{}#{}", job_id, order);
@@ -0,0 +1,29 @@
+use crate::interface::{Callback, Event, EventKind};
+use anyhow::Result;
+
+///
+///
+///
+pub fn handle(callback: Callback) -> Result<Vec<(String, Event)>> {
+    debug!(
+        "Received output callback for job '{}' ({} bytes).",
+        callback.job,
+        callback.payload.len()
+    );
+
+    // The payload (a JSON-encoded batch of output lines) is relayed as-is.
+    let key = format!("{}#{}", callback.job, callback.order);
+    let category = String::from("job");
+    let event = Event::new(
+        EventKind::Output,
+        callback.job,
+        callback.application,
+        callback.location,
+        category,
+        callback.order as u32,
+        Some(callback.payload),
+        None,
+    );
+
+    Ok(vec![(key, event)])
+}
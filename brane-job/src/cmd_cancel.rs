@@ -1,6 +1,152 @@
-use crate::interface::{Command, Event};
-use anyhow::Result;
+use crate::cmd_create::{construct_k8s_config, docker_container_name, k8s_job_name};
+use crate::interface::{Command, Event, EventKind};
+use anyhow::{Context, Result};
+use bollard::container::StopContainerOptions;
+use bollard::Docker;
+use brane_cfg::infrastructure::{Location, LocationCredentials};
+use brane_cfg::{Infrastructure, Secrets};
+use dashmap::lock::RwLock;
+use dashmap::DashMap;
+use k8s_openapi::api::batch::v1::Job;
+use kube::api::{Api, DeleteParams};
+use kube::Client as KubeClient;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use xenon::compute::{Job as XenonJob, Scheduler};
 
-pub fn handle(_command: Command) -> Result<Vec<Event>> {
-    Ok(vec![])
+///
+///
+///
+pub async fn handle(
+    key: &str,
+    command: Command,
+    infra: Infrastructure,
+    secrets: Secrets,
+    xenon_schedulers: Arc<DashMap<String, Arc<RwLock<Scheduler>>>>,
+    xenon_jobs: Arc<DashMap<String, XenonJob>>,
+) -> Result<Vec<(String, Event)>> {
+    let context = || format!("STOP command failed or is invalid (key: {}).", key);
+
+    validate_command(&command).with_context(context)?;
+    let application = command.application.clone().unwrap();
+    let job_id = command.identifier.clone().unwrap();
+
+    // Retreive location metadata and credentials.
+    let location_id = command.location.clone().unwrap();
+    let location = infra.get_location_metadata(&location_id).with_context(context)?;
+
+    // Branch into specific handlers based on the location kind.
+    match location {
+        Location::Kube {
+            namespace, credentials, ..
+        } => {
+            let credentials = credentials.resolve_secrets(&secrets);
+            handle_k8s(&job_id, namespace, credentials).await
+        }
+        Location::Local { .. } => handle_local(&job_id).await,
+        Location::Slurm { .. } | Location::Vm { .. } => {
+            handle_xenon(&job_id, &location_id, xenon_schedulers, xenon_jobs).await
+        }
+    }
+    .with_context(context)?;
+
+    info!(
+        "Stopped job '{}' at location '{}' as part of application '{}'.",
+        job_id, location_id, application
+    );
+
+    // The job won't report back anymore, thus a STOPPED event is always the last.
+    let order = i32::MAX as u32;
+    let key = format!("{}#{}", job_id, order);
+    let category = String::from("job");
+    let event = Event::new(
+        EventKind::Stopped,
+        job_id,
+        application,
+        location_id,
+        category,
+        order,
+        None,
+        None,
+    );
+
+    Ok(vec![(key, event)])
+}
+
+///
+///
+///
+fn validate_command(command: &Command) -> Result<()> {
+    ensure!(command.identifier.is_some(), "Identifier is not specified");
+    ensure!(command.application.is_some(), "Application is not specified");
+    ensure!(command.location.is_some(), "Location is not specified");
+
+    Ok(())
+}
+
+///
+///
+///
+async fn handle_k8s(
+    job_id: &str,
+    namespace: String,
+    credentials: LocationCredentials,
+) -> Result<()> {
+    // Create Kubernetes client based on config credentials
+    let client = if let LocationCredentials::Config { file } = credentials {
+        let config = construct_k8s_config(file).await?;
+        KubeClient::try_from(config)?
+    } else {
+        bail!("Cannot create KubeClient from non-config credentials.");
+    };
+
+    // Deleting in the background also removes the job's pods.
+    let jobs: Api<Job> = Api::namespaced(client, &namespace);
+    jobs.delete(&k8s_job_name(job_id), &DeleteParams::background()).await?;
+
+    Ok(())
+}
+
+///
+///
+///
+async fn handle_local(job_id: &str) -> Result<()> {
+    let docker = Docker::connect_with_local_defaults()?;
+
+    let name = docker_container_name(job_id);
+
+    // Containers are created with `auto_remove`, so stopping is enough.
+    docker
+        .stop_container(&name, None::<StopContainerOptions>)
+        .await
+        .with_context(|| format!("Failed to stop container '{}'.", name))?;
+
+    Ok(())
+}
+
+///
+///
+///
+async fn handle_xenon(
+    job_id: &str,
+    location_id: &str,
+    xenon_schedulers: Arc<DashMap<String, Arc<RwLock<Scheduler>>>>,
+    xenon_jobs: Arc<DashMap<String, XenonJob>>,
+) -> Result<()> {
+    // Xenon jobs are only known to the brane-job instance that submitted them.
+    let job = xenon_jobs
+        .get(job_id)
+        .map(|j| j.value().clone())
+        .ok_or_else(|| anyhow!("No submitted Xenon job known for job '{}'.", job_id))?;
+
+    let scheduler = xenon_schedulers
+        .get(location_id)
+        .map(|s| s.value().clone())
+        .ok_or_else(|| anyhow!("No Xenon scheduler available for location '{}'.", location_id))?;
+
+    // The job is only forgotten once cancelled, so a failed STOP can be retried.
+    scheduler.write().cancel_job(job).await?;
+    xenon_jobs.remove(job_id);
+
+    Ok(())
 }
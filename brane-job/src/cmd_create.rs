@@ -1,10 +1,10 @@
-use crate::interface::{Command, Event, EventKind};
+use crate::interface::{Command, Event, EventKind, Mount, MountKind, Resources, Security};
 use anyhow::{Context, Result};
 use bollard::container::{Config, CreateContainerOptions, StartContainerOptions};
 use bollard::image::CreateImageOptions;
-use bollard::models::HostConfig;
+use bollard::models::{DeviceMapping, DeviceRequest, HostConfig};
 use bollard::Docker;
-use brane_cfg::infrastructure::{Location, LocationCredentials};
+use brane_cfg::infrastructure::{normalize_capability, Dataset, DatasetKind, Location, LocationCredentials, Storage};
 use brane_cfg::{Infrastructure, Secrets};
 use dashmap::lock::RwLock;
 use dashmap::DashMap;
@@ -20,8 +20,9 @@ use serde_json::{json, Value as JValue};
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::iter;
+use std::path::{Component, Path};
 use std::sync::Arc;
-use xenon::compute::{JobDescription, Scheduler};
+use xenon::compute::{Job as XenonJob, JobDescription, Scheduler};
 use xenon::credentials::{CertificateCredential, Credential};
 use xenon::storage::{FileSystem, FileSystemPath};
 
@@ -30,8 +31,16 @@ const BRANE_APPLICATION_ID: &str = "BRANE_APPLICATION_ID";
 const BRANE_LOCATION_ID: &str = "BRANE_LOCATION_ID";
 const BRANE_JOB_ID: &str = "BRANE_JOB_ID";
 const BRANE_CALLBACK_TO: &str = "BRANE_CALLBACK_TO";
+const BRANE_HEARTBEAT_INTERVAL: &str = "BRANE_HEARTBEAT_INTERVAL";
 const BRANE_PROXY_ADDRESS: &str = "BRANE_PROXY_ADDRESS";
 const BRANE_MOUNT_DFS: &str = "BRANE_MOUNT_DFS";
+const BRANE_STORAGE_ENDPOINT: &str = "BRANE_STORAGE_ENDPOINT";
+const BRANE_STORAGE_BUCKET: &str = "BRANE_STORAGE_BUCKET";
+const BRANE_STORAGE_REGION: &str = "BRANE_STORAGE_REGION";
+const BRANE_STORAGE_ACCESS_KEY: &str = "BRANE_STORAGE_ACCESS_KEY";
+const BRANE_STORAGE_SECRET_KEY: &str = "BRANE_STORAGE_SECRET_KEY";
+
+const FUSE_DEVICE: &str = "/dev/fuse";
 
 ///
 ///
@@ -43,6 +52,8 @@ pub async fn handle(
     secrets: Secrets,
     xenon_endpoint: String,
     xenon_schedulers: Arc<DashMap<String, Arc<RwLock<Scheduler>>>>,
+    xenon_jobs: Arc<DashMap<String, XenonJob>>,
+    heartbeat_interval: u64,
 ) -> Result<Vec<(String, Event)>> {
     let context = || format!("CREATE command failed or is invalid (key: {}).", key);
 
@@ -60,6 +71,53 @@ pub async fn handle(
     // Generate job identifier.
     let job_id = format!("{}-{}", correlation_id, get_random_identifier());
 
+    // Only grant what the package and the location's features require, if the location allows it.
+    let security = construct_security_profile(&command, &location);
+    let policy = location.get_security_policy();
+    let datasets = location.get_datasets();
+    let allowed = policy
+        .check(&security.capabilities, &security.devices, security.privileged)
+        .and_then(|_| check_mounts(&mut command.mounts, &datasets));
+
+    if let Err(error) = allowed {
+        warn!("Refused to create job '{}' at location '{}': {}", job_id, location_id, error);
+
+        let order = 0; // The job is never created, thus the FAILED event is the first.
+        let key = format!("{}#{}", job_id, order);
+        let category = String::from("job");
+        let payload = error.to_string().into_bytes();
+        let event = Event::new(
+            EventKind::Failed,
+            job_id,
+            application,
+            location_id,
+            category,
+            order,
+            Some(payload),
+            None,
+        );
+
+        return Ok(vec![(key, event)]);
+    }
+    command.security = Some(security);
+
+    // Datasets declared by the location are available to every job at that location.
+    let mut datasets: Vec<_> = datasets.into_iter().collect();
+    datasets.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (_, dataset) in datasets {
+        let kind = match dataset.kind {
+            DatasetKind::Bind => MountKind::Bind,
+            DatasetKind::Volume => MountKind::Volume,
+        };
+
+        command
+            .mounts
+            .push(Mount::new(kind, dataset.source, dataset.destination, dataset.read_only));
+    }
+
+    // Inputs and outputs are staged by branelet, if the location has an object storage.
+    let storage = location.get_storage().map(|storage| storage.resolve_secrets(&secrets));
+
     // Branch into specific handlers based on the location kind.
     match location {
         Location::Kube {
@@ -76,8 +134,10 @@ pub async fn handle(
                 &location_id,
                 &job_id,
                 &callback_to,
+                heartbeat_interval,
                 &proxy_address,
                 &mount_dfs,
+                &storage,
             )?;
             let credentials = credentials.resolve_secrets(&secrets);
 
@@ -95,10 +155,12 @@ pub async fn handle(
                 &location_id,
                 &job_id,
                 &callback_to,
+                heartbeat_interval,
                 &proxy_address,
                 &mount_dfs,
+                &storage,
             )?;
-            handle_local(command, &correlation_id, environment, network).await?
+            handle_local(command, &job_id, environment, network).await?
         }
         Location::Slurm {
             address,
@@ -114,8 +176,10 @@ pub async fn handle(
                 &location_id,
                 &job_id,
                 &callback_to,
+                heartbeat_interval,
                 &proxy_address,
                 &mount_dfs,
+                &storage,
             )?;
             let credentials = credentials.resolve_secrets(&secrets);
 
@@ -128,6 +192,7 @@ pub async fn handle(
                 credentials,
                 xenon_endpoint,
                 xenon_schedulers,
+                xenon_jobs,
             )
             .await?
         }
@@ -145,8 +210,10 @@ pub async fn handle(
                 &location_id,
                 &job_id,
                 &callback_to,
+                heartbeat_interval,
                 &proxy_address,
                 &mount_dfs,
+                &storage,
             )?;
             let credentials = credentials.resolve_secrets(&secrets);
 
@@ -159,6 +226,7 @@ pub async fn handle(
                 credentials,
                 xenon_endpoint,
                 xenon_schedulers,
+                xenon_jobs,
             )
             .await?
         }
@@ -187,6 +255,32 @@ pub async fn handle(
     Ok(vec![(key, event)])
 }
 
+///
+///
+///
+fn check_mounts(
+    mounts: &mut [Mount],
+    datasets: &HashMap<String, Dataset>,
+) -> Result<()> {
+    for mount in mounts.iter_mut() {
+        // Paths from arguments are only mounted if they're part of a dataset that the location binds.
+        let source = Path::new(&mount.source);
+        let normal = source.is_absolute() && !source.components().any(|c| c == Component::ParentDir);
+
+        let dataset = datasets
+            .values()
+            .filter(|d| normal && d.kind == DatasetKind::Bind && source.starts_with(&d.source))
+            .max_by_key(|d| Path::new(&d.source).components().count());
+
+        match dataset {
+            Some(dataset) => mount.read_only |= dataset.read_only,
+            None => bail!("Path '{}' is not part of a dataset at this location.", mount.source),
+        }
+    }
+
+    Ok(())
+}
+
 ///
 ///
 ///
@@ -199,6 +293,53 @@ fn validate_command(command: &Command) -> Result<()> {
     Ok(())
 }
 
+///
+///
+///
+fn construct_security_profile(
+    command: &Command,
+    location: &Location,
+) -> Security {
+    let mut security = command.security.clone().unwrap_or_default();
+    let (proxy_address, mount_dfs) = match location {
+        Location::Kube {
+            proxy_address, mount_dfs, ..
+        }
+        | Location::Local {
+            proxy_address, mount_dfs, ..
+        }
+        | Location::Vm {
+            proxy_address, mount_dfs, ..
+        }
+        | Location::Slurm {
+            proxy_address, mount_dfs, ..
+        } => (proxy_address.is_some(), mount_dfs.is_some()),
+    };
+
+    let mut add_capability = |capability: &str| {
+        let capabilities: Vec<String> = security.capabilities.iter().map(|c| normalize_capability(c)).collect();
+        if !capabilities.contains(&normalize_capability(capability)) {
+            security.capabilities.push(capability.to_string());
+        }
+    };
+
+    // The redirector (see brane-let) uses iptables to route traffic through the proxy.
+    if proxy_address {
+        add_capability("NET_ADMIN");
+        add_capability("NET_RAW");
+    }
+
+    // The DFS is mounted with FUSE from within the container.
+    if mount_dfs {
+        add_capability("SYS_ADMIN");
+        if !security.devices.iter().any(|d| d == FUSE_DEVICE) {
+            security.devices.push(FUSE_DEVICE.to_string());
+        }
+    }
+
+    security
+}
+
 ///
 ///
 ///
@@ -207,14 +348,18 @@ fn construct_environment<S: Into<String>>(
     location_id: S,
     job_id: S,
     callback_to: S,
+    heartbeat_interval: u64,
     proxy_address: &Option<String>,
     mount_dfs: &Option<String>,
+    storage: &Option<Storage>,
 ) -> Result<HashMap<String, String>> {
+    // Jobs send heartbeats as often as brane-job expects them, so they're not considered lost.
     let mut environment = hashmap! {
         BRANE_APPLICATION_ID.to_string() => application_id.into(),
         BRANE_LOCATION_ID.to_string() => location_id.into(),
         BRANE_JOB_ID.to_string() => job_id.into(),
         BRANE_CALLBACK_TO.to_string() => callback_to.into(),
+        BRANE_HEARTBEAT_INTERVAL.to_string() => heartbeat_interval.to_string(),
     };
 
     if let Some(proxy_address) = proxy_address {
@@ -225,6 +370,22 @@ fn construct_environment<S: Into<String>>(
         environment.insert(BRANE_MOUNT_DFS.to_string(), mount_dfs.clone());
     }
 
+    if let Some(storage) = storage {
+        environment.insert(BRANE_STORAGE_ENDPOINT.to_string(), storage.endpoint.clone());
+        environment.insert(BRANE_STORAGE_BUCKET.to_string(), storage.bucket.clone());
+
+        let optional = [
+            (BRANE_STORAGE_REGION, &storage.region),
+            (BRANE_STORAGE_ACCESS_KEY, &storage.access_key),
+            (BRANE_STORAGE_SECRET_KEY, &storage.secret_key),
+        ];
+        for (name, value) in optional.iter() {
+            if let Some(value) = value {
+                environment.insert(name.to_string(), value.clone());
+            }
+        }
+    }
+
     Ok(environment)
 }
 
@@ -284,7 +445,7 @@ async fn handle_k8s(
 ///
 ///
 ///
-async fn construct_k8s_config(config_file: String) -> Result<KubeConfig> {
+pub async fn construct_k8s_config(config_file: String) -> Result<KubeConfig> {
     let base64_symbols = ['+', '/', '='];
 
     // Remove any whitespace and/or newlines.
@@ -316,8 +477,43 @@ fn create_k8s_job_description(
         .map(|(k, v)| json!({ "name": k, "value": v }))
         .collect();
 
-    // Kubernetes jobs require lowercase names
-    let job_id = job_id.to_lowercase();
+    let job_id = k8s_job_name(job_id);
+
+    let resources = command.resources.clone().unwrap_or_default();
+    let limits = create_k8s_resource_limits(&resources);
+
+    let security = command.security.clone().unwrap_or_default();
+    let mut capabilities = vec![String::from("NET_BIND_SERVICE")];
+    capabilities.extend(security.capabilities.iter().map(|c| normalize_capability(c)));
+
+    // Devices are made available to the container through host paths.
+    let mut volumes: Vec<JValue> = security
+        .devices
+        .iter()
+        .enumerate()
+        .map(|(i, device)| json!({ "name": format!("device-{}", i), "hostPath": { "path": device } }))
+        .collect();
+    let mut volume_mounts: Vec<JValue> = security
+        .devices
+        .iter()
+        .enumerate()
+        .map(|(i, device)| json!({ "name": format!("device-{}", i), "mountPath": device }))
+        .collect();
+
+    // Bind mounts become host paths, named volumes refer to persistent volume claims.
+    for (i, mount) in command.mounts.iter().enumerate() {
+        let name = format!("mount-{}", i);
+        let volume = match mount.kind() {
+            MountKind::Bind => json!({ "name": name, "hostPath": { "path": mount.source } }),
+            MountKind::Volume => json!({
+                "name": name,
+                "persistentVolumeClaim": { "claimName": mount.source, "readOnly": mount.read_only }
+            }),
+        };
+
+        volumes.push(volume);
+        volume_mounts.push(json!({ "name": name, "mountPath": mount.destination, "readOnly": mount.read_only }));
+    }
 
     let job_description = serde_json::from_value(json!({
         "apiVersion": "batch/v1",
@@ -326,6 +522,7 @@ fn create_k8s_job_description(
             "name": job_id,
         },
         "spec": {
+            "activeDeadlineSeconds": resources.time,
             "backoffLimit": 3,
             "ttlSecondsAfterFinished": 120,
             "template": {
@@ -335,15 +532,21 @@ fn create_k8s_job_description(
                         "image": command.image.expect("unreachable!"),
                         "args": command.command,
                         "env": environment,
+                        "resources": {
+                            "requests": limits,
+                            "limits": limits,
+                        },
                         "securityContext": {
                             "capabilities": {
                                 "drop": ["all"],
-                                "add": ["NET_BIND_SERVICE", "NET_ADMIN", "SYS_ADMIN"]
+                                "add": capabilities,
                             },
-                            "privileged": true // Quickfix, needs to be dynamic based on capabilities/devices used.
-                        }
+                            "privileged": security.privileged,
+                        },
+                        "volumeMounts": volume_mounts,
                     }],
                     "restartPolicy": "Never",
+                    "volumes": volumes,
                 }
             }
         }
@@ -352,6 +555,25 @@ fn create_k8s_job_description(
     Ok(job_description)
 }
 
+///
+///
+///
+fn create_k8s_resource_limits(resources: &Resources) -> JValue {
+    let mut limits = serde_json::Map::new();
+
+    if let Some(cpus) = resources.cpus {
+        limits.insert(String::from("cpu"), json!(cpus.to_string()));
+    }
+    if let Some(memory) = resources.memory {
+        limits.insert(String::from("memory"), json!(format!("{}M", memory)));
+    }
+    if let Some(gpus) = resources.gpus {
+        limits.insert(String::from("nvidia.com/gpu"), json!(gpus.to_string()));
+    }
+
+    JValue::Object(limits)
+}
+
 ///
 ///
 ///
@@ -381,12 +603,52 @@ async fn handle_local(
     let image = command.image.expect("Empty `image` field on CREATE command.");
     ensure_image(&docker, &image).await?;
 
-    let create_options = CreateContainerOptions { name: job_id };
+    let name = docker_container_name(job_id);
+    let create_options = CreateContainerOptions { name: name.as_str() };
+
+    // Docker has no notion of wall-time, that's up to the driver's job timeout.
+    let resources = command.resources.clone().unwrap_or_default();
+    let device_requests = resources.gpus.map(|gpus| {
+        vec![DeviceRequest {
+            driver: None,
+            count: Some(gpus as i64),
+            device_ids: None,
+            capabilities: Some(vec![vec![String::from("gpu")]]),
+            options: None,
+        }]
+    });
+
+    let security = command.security.clone().unwrap_or_default();
+    let devices = security
+        .devices
+        .iter()
+        .map(|device| DeviceMapping {
+            path_on_host: Some(device.clone()),
+            path_in_container: Some(device.clone()),
+            cgroup_permissions: Some(String::from("rwm")),
+        })
+        .collect();
+
+    // AppArmor's default profile prevents FUSE mounts.
+    let security_opt = if security.devices.iter().any(|d| d == FUSE_DEVICE) {
+        Some(vec![String::from("apparmor:unconfined")])
+    } else {
+        None
+    };
+
+    let binds = command.mounts.iter().map(format_docker_mount).collect();
 
     let host_config = HostConfig {
         auto_remove: Some(true),
+        binds: Some(binds),
+        cap_add: Some(security.capabilities),
+        device_requests,
+        devices: Some(devices),
+        memory: resources.memory.map(|memory| (memory * 1024 * 1024) as i64),
+        nano_cpus: resources.cpus.map(|cpus| cpus as i64 * 1_000_000_000),
         network_mode: Some(network),
-        privileged: Some(true),
+        privileged: Some(security.privileged),
+        security_opt,
         ..Default::default()
     };
 
@@ -406,7 +668,7 @@ async fn handle_local(
     // Create and start container
     docker.create_container(Some(create_options), create_config).await?;
     docker
-        .start_container(job_id, None::<StartContainerOptions<String>>)
+        .start_container(&name, None::<StartContainerOptions<String>>)
         .await?;
 
     Ok(())
@@ -448,6 +710,7 @@ async fn handle_slurm(
     credentials: LocationCredentials,
     xenon_endpoint: String,
     xenon_schedulers: Arc<DashMap<String, Arc<RwLock<Scheduler>>>>,
+    xenon_jobs: Arc<DashMap<String, XenonJob>>,
 ) -> Result<()> {
     let credentials = match credentials {
         LocationCredentials::SshCertificate {
@@ -471,7 +734,7 @@ async fn handle_slurm(
         xenon_schedulers,
     )
     .await?;
-    handle_xenon(command, job_id, environment, runtime, scheduler).await
+    handle_xenon(command, job_id, environment, runtime, scheduler, xenon_jobs).await
 }
 
 ///
@@ -487,6 +750,7 @@ async fn handle_vm(
     credentials: LocationCredentials,
     xenon_endpoint: String,
     xenon_schedulers: Arc<DashMap<String, Arc<RwLock<Scheduler>>>>,
+    xenon_jobs: Arc<DashMap<String, XenonJob>>,
 ) -> Result<()> {
     let credentials = match credentials {
         LocationCredentials::SshCertificate {
@@ -510,7 +774,7 @@ async fn handle_vm(
         xenon_schedulers,
     )
     .await?;
-    handle_xenon(command, job_id, environment, runtime, scheduler).await
+    handle_xenon(command, job_id, environment, runtime, scheduler, xenon_jobs).await
 }
 
 ///
@@ -522,14 +786,24 @@ async fn handle_xenon(
     environment: HashMap<String, String>,
     runtime: String,
     scheduler: Arc<RwLock<Scheduler>>,
+    xenon_jobs: Arc<DashMap<String, XenonJob>>,
 ) -> Result<()> {
-    let job_description = match runtime.to_lowercase().as_str() {
+    let mut job_description = match runtime.to_lowercase().as_str() {
         "singularity" => create_singularity_job_description(&command, job_id, environment)?,
         "docker" => create_docker_job_description(&command, job_id, environment, None)?,
         _ => unreachable!(),
     };
 
-    let _job = scheduler.write().submit_batch_job(job_description).await?;
+    // Let the scheduler reserve the resources, the wall-time is rounded up to whole minutes.
+    if let Some(resources) = &command.resources {
+        job_description.cores_per_task = resources.cpus;
+        job_description.max_memory = resources.memory.map(|memory| memory as u32);
+        job_description.max_runtime = resources.time.map(|time| ((time + 59) / 60) as u32);
+    }
+
+    // Keep track of the Xenon job, so it can be cancelled by a STOP command.
+    let job = scheduler.write().submit_batch_job(job_description).await?;
+    xenon_jobs.insert(job_id.to_string(), job);
 
     Ok(())
 }
@@ -622,24 +896,25 @@ fn create_docker_job_description(
         String::from("--rm"),
         String::from("--name"),
         job_id.to_string(),
-        String::from("--privileged"),
-        // String::from("ALL"),
-        // String::from("--cap-add"),
-        // String::from("NET_ADMIN"),
-        // String::from("--cap-add"),
-        // String::from("NET_BIND_SERVICE"),
-        // String::from("--cap-add"),
-        // String::from("NET_RAW"),
     ];
 
-    // if environment.contains_key(BRANE_MOUNT_DFS) {
-    //     arguments.push(String::from("--cap-add"));
-    //     arguments.push(String::from("SYS_ADMIN"));
-    //     arguments.push(String::from("--device"));
-    //     arguments.push(String::from("/dev/fuse"));
-    //     arguments.push(String::from("--security-opt"));
-    //     arguments.push(String::from("apparmor:unconfined"));
-    // }
+    // Add security profile
+    let security = command.security.unwrap_or_default();
+    if security.privileged {
+        arguments.push(String::from("--privileged"));
+    }
+    for capability in &security.capabilities {
+        arguments.push(String::from("--cap-add"));
+        arguments.push(normalize_capability(capability));
+    }
+    for device in &security.devices {
+        arguments.push(String::from("--device"));
+        arguments.push(device.clone());
+    }
+    if security.devices.iter().any(|d| d == FUSE_DEVICE) {
+        arguments.push(String::from("--security-opt"));
+        arguments.push(String::from("apparmor:unconfined"));
+    }
 
     arguments.push(String::from("--network"));
     if let Some(network) = network {
@@ -657,9 +932,25 @@ fn create_docker_job_description(
     }
 
     // Add mount bindings
-    for mount in command.mounts {
+    for mount in &command.mounts {
         arguments.push(String::from("-v"));
-        arguments.push(format!("{}:{}", mount.source, mount.destination));
+        arguments.push(format_docker_mount(mount));
+    }
+
+    // Add resource limits
+    if let Some(resources) = command.resources {
+        if let Some(cpus) = resources.cpus {
+            arguments.push(String::from("--cpus"));
+            arguments.push(cpus.to_string());
+        }
+        if let Some(memory) = resources.memory {
+            arguments.push(String::from("--memory"));
+            arguments.push(format!("{}m", memory));
+        }
+        if let Some(gpus) = resources.gpus {
+            arguments.push(String::from("--gpus"));
+            arguments.push(gpus.to_string());
+        }
     }
 
     // Add image
@@ -684,6 +975,18 @@ fn create_docker_job_description(
     Ok(job_description)
 }
 
+///
+///
+///
+fn format_docker_mount(mount: &Mount) -> String {
+    // Docker uses the same syntax for bind mounts and named volumes.
+    if mount.read_only {
+        format!("{}:{}:ro", mount.source, mount.destination)
+    } else {
+        format!("{}:{}", mount.source, mount.destination)
+    }
+}
+
 ///
 ///
 ///
@@ -702,11 +1005,21 @@ fn create_singularity_job_description(
         String::from("--nohttps"),
     ];
 
-    if !environment.contains_key(BRANE_MOUNT_DFS) {
+    // Add security profile, devices are accessible by default.
+    let security = command.security.unwrap_or_default();
+    if !security.privileged {
+        let mut capabilities = vec![String::from("CAP_NET_BIND_SERVICE")];
+        capabilities.extend(
+            security
+                .capabilities
+                .iter()
+                .map(|c| format!("CAP_{}", normalize_capability(c))),
+        );
+
         arguments.push(String::from("--drop-caps"));
         arguments.push(String::from("ALL"));
         arguments.push(String::from("--add-caps"));
-        arguments.push(String::from("CAP_NET_ADMIN,CAP_NET_BIND_SERVICE,CAP_NET_RAW"));
+        arguments.push(capabilities.join(","));
     }
 
     // Add environment variables
@@ -716,9 +1029,21 @@ fn create_singularity_job_description(
     }
 
     // Add mount bindings
-    for mount in command.mounts {
-        arguments.push(String::from("-B"));
-        arguments.push(format!("{}:{}", mount.source, mount.destination));
+    for mount in &command.mounts {
+        ensure!(
+            mount.kind() == MountKind::Bind,
+            "Singularity does not support named volumes (volume: {}).",
+            mount.source
+        );
+
+        let options = if mount.read_only { ":ro" } else { "" };
+        arguments.push(String::from("--bind"));
+        arguments.push(format!("{}:{}{}", mount.source, mount.destination, options));
+    }
+
+    // Singularity exposes either all or no GPUs, the scheduler limits the rest.
+    if command.resources.and_then(|r| r.gpus).unwrap_or_default() > 0 {
+        arguments.push(String::from("--nv"));
     }
 
     // Add image
@@ -738,6 +1063,23 @@ fn create_singularity_job_description(
     Ok(job_description)
 }
 
+///
+///
+///
+pub fn k8s_job_name(job_id: &str) -> String {
+    // Kubernetes jobs require lowercase names
+    job_id.to_lowercase()
+}
+
+///
+///
+///
+pub fn docker_container_name(job_id: &str) -> String {
+    // Local containers are named after the correlation identifier, i.e., without the random suffix.
+    let (correlation_id, _) = job_id.rsplit_once('-').unwrap_or((job_id, ""));
+    correlation_id.to_string()
+}
+
 ///
 ///
 ///
@@ -7,6 +7,7 @@ extern crate maplit;
 
 pub mod clb_heartbeat;
 pub mod clb_lifecycle;
+pub mod clb_output;
 pub mod cmd_cancel;
 pub mod cmd_create;
 pub mod interface;
@@ -3,10 +3,11 @@ use std::sync::Arc;
 use anyhow::{bail, Context, Result};
 use brane_cfg::{Infrastructure, Secrets};
 use brane_job::{
-    clb_heartbeat, clb_lifecycle,
+    clb_heartbeat::{self, Heartbeat},
+    clb_lifecycle, clb_output,
     interface::{Callback, CallbackKind, Command, CommandKind},
 };
-use brane_job::{cmd_create, interface::Event};
+use brane_job::{cmd_cancel, cmd_create, interface::Event};
 use brane_shr::utilities;
 use bytes::BytesMut;
 use clap::Parser;
@@ -27,8 +28,9 @@ use rdkafka::{
     util::Timeout,
     Message as KafkaMesage, Offset, TopicPartitionList,
 };
+use std::time::Duration;
 use tokio::task::JoinHandle;
-use xenon::compute::Scheduler;
+use xenon::compute::{Job as XenonJob, Scheduler};
 
 #[derive(Parser)]
 #[clap(version = env!("CARGO_PKG_VERSION"))]
@@ -51,6 +53,12 @@ struct Opts {
     /// Consumer group id
     #[clap(short, long, default_value = "brane-job", env = "GROUP_ID")]
     group_id: String,
+    /// Seconds between heartbeats sent by jobs, zero disables them
+    #[clap(long, default_value = "10", env = "HEARTBEAT_INTERVAL")]
+    heartbeat_interval: u64,
+    /// Number of missed heartbeats after which a job is considered lost
+    #[clap(long, default_value = "3", env = "HEARTBEAT_MISSES")]
+    heartbeat_misses: u32,
     /// Infra metadata store
     #[clap(short, long, default_value = "./infra.yml", env = "INFRA")]
     infra: String,
@@ -94,7 +102,21 @@ async fn main() -> Result<()> {
     secrets.validate()?;
 
     let xenon_schedulers = Arc::new(DashMap::<String, Arc<RwLock<Scheduler>>>::new());
+    let xenon_jobs = Arc::new(DashMap::<String, XenonJob>::new());
     let xenon_endpoint = utilities::ensure_http_schema(&opts.xenon, !opts.debug)?;
+    let heartbeats = Arc::new(DashMap::<String, Heartbeat>::new());
+
+    // Periodically check for jobs that stopped sending heartbeats.
+    if opts.heartbeat_interval > 0 {
+        let interval = Duration::from_secs(opts.heartbeat_interval);
+        tokio::spawn(start_heartbeat_monitor(
+            opts.brokers.clone(),
+            opts.event_topic.clone(),
+            heartbeats.clone(),
+            interval,
+            interval * opts.heartbeat_misses.max(1),
+        ));
+    }
 
     // Spawn workers, using Tokio tasks and thread pool.
     let workers = (0..opts.num_workers)
@@ -109,6 +131,9 @@ async fn main() -> Result<()> {
                 secrets.clone(),
                 xenon_endpoint.clone(),
                 xenon_schedulers.clone(),
+                xenon_jobs.clone(),
+                heartbeats.clone(),
+                opts.heartbeat_interval,
             ));
 
             info!("Spawned asynchronous worker #{}.", i + 1);
@@ -180,6 +205,9 @@ async fn start_worker(
     secrets: Secrets,
     xenon_endpoint: String,
     xenon_schedulers: Arc<DashMap<String, Arc<RwLock<Scheduler>>>>,
+    xenon_jobs: Arc<DashMap<String, XenonJob>>,
+    heartbeats: Arc<DashMap<String, Heartbeat>>,
+    heartbeat_interval: u64,
 ) -> Result<()> {
     let output_topic = evt_topic.as_ref();
 
@@ -235,6 +263,8 @@ async fn start_worker(
         let owned_secrets = secrets.clone();
         let owned_xenon_endpoint = xenon_endpoint.clone();
         let owned_xenon_schedulers = xenon_schedulers.clone();
+        let owned_xenon_jobs = xenon_jobs.clone();
+        let owned_heartbeats = heartbeats.clone();
         let clb_topic = clb_topic.clone();
         let cmd_topic = cmd_topic.clone();
 
@@ -258,7 +288,7 @@ async fn start_worker(
 
             let topic = owned_message.topic();
             let events = if topic == clb_topic {
-                handle_clb_message(msg_key, msg_payload)
+                handle_clb_message(msg_key, msg_payload, owned_xenon_jobs, owned_heartbeats)
             } else if topic == cmd_topic {
                 handle_cmd_message(
                     msg_key,
@@ -267,6 +297,9 @@ async fn start_worker(
                     owned_secrets,
                     owned_xenon_endpoint,
                     owned_xenon_schedulers,
+                    owned_xenon_jobs,
+                    owned_heartbeats,
+                    heartbeat_interval,
                 )
                 .await
             } else {
@@ -300,12 +333,46 @@ async fn start_worker(
         .context("Stream processor did not run until completion.")
 }
 
+///
+///
+///
+async fn start_heartbeat_monitor(
+    brokers: String,
+    evt_topic: String,
+    heartbeats: Arc<DashMap<String, Heartbeat>>,
+    interval: Duration,
+    timeout: Duration,
+) -> Result<()> {
+    let producer: FutureProducer = ClientConfig::new()
+        .set("bootstrap.servers", &brokers)
+        .set("message.timeout.ms", "5000")
+        .create()
+        .context("Failed to create Kafka producer.")?;
+
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+
+        for (evt_key, event) in clb_heartbeat::collect_lost(&heartbeats, timeout) {
+            let mut payload = BytesMut::with_capacity(64);
+            event.encode(&mut payload).unwrap();
+
+            let message = FutureRecord::to(&evt_topic).key(&evt_key).payload(payload.to_bytes());
+            if let Err(error) = producer.send(message, Timeout::Never).await {
+                error!("Failed to send event (key: {}): {:?}", evt_key, error);
+            }
+        }
+    }
+}
+
 ///
 ///
 ///
 fn handle_clb_message(
     key: String,
     payload: &[u8],
+    xenon_jobs: Arc<DashMap<String, XenonJob>>,
+    heartbeats: Arc<DashMap<String, Heartbeat>>,
 ) -> Result<Vec<(String, Event)>> {
     // Decode payload into a callback message.
     let callback = Callback::decode(payload).unwrap();
@@ -320,8 +387,21 @@ fn handle_clb_message(
     info!("Received {} callback (key: {}).", kind, key);
     debug!("{:?}", callback);
 
+    // Finished jobs can no longer be cancelled or get lost, so forget about them.
+    // Any other callback is as good a sign of life as a heartbeat.
+    if matches!(
+        kind,
+        CallbackKind::Finished | CallbackKind::Stopped | CallbackKind::Failed
+    ) {
+        xenon_jobs.remove(&callback.job);
+        heartbeats.remove(&callback.job);
+    } else {
+        clb_heartbeat::track(&callback, &heartbeats);
+    }
+
     match kind {
         CallbackKind::Heartbeat => clb_heartbeat::handle(callback),
+        CallbackKind::Output => clb_output::handle(callback),
         _ => clb_lifecycle::handle(callback),
     }
 }
@@ -329,6 +409,7 @@ fn handle_clb_message(
 ///
 ///
 ///
+#[allow(clippy::too_many_arguments)]
 async fn handle_cmd_message(
     key: String,
     payload: &[u8],
@@ -336,6 +417,9 @@ async fn handle_cmd_message(
     secrets: Secrets,
     xenon_endpoint: String,
     xenon_schedulers: Arc<DashMap<String, Arc<RwLock<Scheduler>>>>,
+    xenon_jobs: Arc<DashMap<String, XenonJob>>,
+    heartbeats: Arc<DashMap<String, Heartbeat>>,
+    heartbeat_interval: u64,
 ) -> Result<Vec<(String, Event)>> {
     // Decode payload into a command message.
     let command = Command::decode(payload).unwrap();
@@ -353,9 +437,26 @@ async fn handle_cmd_message(
     // Dispatch command message to appropriate handlers.
     match kind {
         CommandKind::Create => {
-            cmd_create::handle(&key, command, infra, secrets, xenon_endpoint, xenon_schedulers).await
+            cmd_create::handle(
+                &key,
+                command,
+                infra,
+                secrets,
+                xenon_endpoint,
+                xenon_schedulers,
+                xenon_jobs,
+                heartbeat_interval,
+            )
+            .await
+        }
+        CommandKind::Stop => {
+            // A stopped job won't send any heartbeats anymore.
+            if let Some(job_id) = &command.identifier {
+                heartbeats.remove(job_id);
+            }
+
+            cmd_cancel::handle(&key, command, infra, secrets, xenon_schedulers, xenon_jobs).await
         }
-        CommandKind::Stop => unimplemented!(),
         CommandKind::Unknown => unreachable!(),
     }
 }
@@ -1,13 +1,17 @@
 use anyhow::Result;
 use brane_clb::grpc::{CallbackKind, CallbackRequest, CallbackServiceClient};
 use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
 use tonic::transport::Channel;
 
+#[derive(Clone)]
 pub struct Callback {
     application_id: String,
     location_id: String,
     job_id: String,
-    event_counter: AtomicI32,
+    event_counter: Arc<AtomicI32>,
     client: CallbackServiceClient<Channel>,
 }
 
@@ -30,7 +34,7 @@ impl Callback {
             application_id: application_id.into(),
             location_id: location_id.into(),
             job_id: job_id.into(),
-            event_counter: AtomicI32::new(1),
+            event_counter: Arc::new(AtomicI32::new(1)),
             client,
         })
     }
@@ -101,6 +105,29 @@ impl Callback {
         self.call(CallbackKind::Heartbeat, payload).await
     }
 
+    ///
+    ///
+    ///
+    pub fn start_heartbeat(
+        &self,
+        interval: Duration,
+    ) -> JoinHandle<()> {
+        // The clone shares the event counter, so heartbeats are ordered with the other callbacks.
+        let mut callback = self.clone();
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                if let Err(error) = callback.heartbeat(None).await {
+                    warn!("Failed to send heartbeat: {:?}", error);
+                }
+            }
+        })
+    }
+
     ///
     ///
     ///
@@ -121,6 +148,16 @@ impl Callback {
         self.call(CallbackKind::Stopped, payload).await
     }
 
+    ///
+    ///
+    ///
+    pub async fn output(
+        &mut self,
+        payload: Option<Vec<u8>>,
+    ) -> Result<()> {
+        self.call(CallbackKind::Output, payload).await
+    }
+
     ///
     ///
     ///
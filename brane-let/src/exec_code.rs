@@ -1,22 +1,57 @@
 use crate::callback::Callback;
+use crate::staging::{self, Storage};
 use anyhow::{Context, Result};
 use specifications::common::{Parameter, Type, Value};
-use specifications::container::{ActionCommand, ContainerInfo};
-use std::io::{BufRead, BufReader};
+use specifications::container::{ActionCommand, ContainerInfo, ReadinessProbe, SERVICE_READY_MARKER};
+use specifications::errors::{JobFailure, REASON_FAILED, REASON_KILLED, REASON_TIMEOUT};
+use specifications::output::{JobOutput, OutputStream};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use subprocess::{Exec, Redirection};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use subprocess::{Exec, ExitStatus, Redirection};
+use tokio::net::TcpStream;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task;
 use yaml_rust::{Yaml, YamlLoader};
 
 type Map<T> = std::collections::HashMap<String, T>;
 
+// Number of stderr lines reported when an action fails.
+const STDERR_TAIL_LINES: usize = 20;
+// Maximum number of output lines sent in a single callback.
+const OUTPUT_BATCH_LINES: usize = 100;
+// Time a terminated action gets to exit, before it's killed.
+const TERMINATION_GRACE_PERIOD: Duration = Duration::from_secs(10);
+// Interval at which the action is checked for exiting, timing out, or having to terminate.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+// Capture mode in which the output is read from a file, and the variable that holds its path.
+const CAPTURE_FILE: &str = "file";
+const OUTPUT_FILE_ENV: &str = "BRANE_OUTPUT";
+// Input modes in which the arguments are passed as a JSON document, and the variable that holds its path.
+const INPUT_ENV: &str = "env";
+const INPUT_FILE: &str = "file";
+const INPUT_STDIN: &str = "stdin";
+const INPUT_FILE_ENV: &str = "BRANE_INPUT";
+// Defaults for readiness probes of services, in seconds.
+const READINESS_INTERVAL: u64 = 1;
+const READINESS_TIMEOUT: u64 = 60;
+// Output types that are not represented by a string.
+const BUILTIN_TYPES: [&str; 5] = ["boolean", "integer", "real", "Directory", "File"];
+
 ///
 ///
 ///
 pub async fn handle(
     function: String,
-    arguments: Map<Value>,
+    mut arguments: Map<Value>,
     working_dir: PathBuf,
+    storage: Option<Storage>,
     callback: &mut Option<&mut Callback>,
 ) -> Result<Value> {
     debug!("Executing '{}' (code) using arguments:\n{:#?}", function, arguments);
@@ -34,6 +69,7 @@ pub async fn handle(
 
     // Perform initialization.
     initialize(&working_dir)?;
+    staging::stage_inputs(&mut arguments, &working_dir, &storage).await?;
     if let Some(callback) = callback {
         callback.initialized(None).await?;
     }
@@ -43,22 +79,114 @@ pub async fn handle(
     let command = function.command.clone().unwrap_or_else(|| ActionCommand {
         args: Default::default(),
         capture: None,
+        input: None,
+        success_codes: None,
+        timeout: None,
     });
 
-    if let Some(callback) = callback {
-        callback.started(None).await?;
+    // Services are only considered started once they're ready (see `await_readiness`).
+    let service = container_info.entrypoint.kind == *"service";
+    if !service {
+        if let Some(callback) = callback {
+            callback.started(None).await?;
+        }
     }
 
+    // Output lines are streamed back as they're printed, if there is a callback channel.
+    let (output_tx, forwarder) = match callback {
+        Some(callback) => {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            let forwarder = tokio::spawn(forward_output((*callback).clone(), receiver));
+
+            (Some(sender), Some(forwarder))
+        }
+        None => (None, None),
+    };
+
+    // Forward termination requests (e.g., from the container runtime) to the action.
+    let terminate = Arc::new(AtomicBool::new(false));
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let sigterm_listener = {
+        let terminate = terminate.clone();
+        tokio::spawn(async move {
+            if sigterm.recv().await.is_some() {
+                info!("Received SIGTERM, terminating action.");
+                terminate.store(true, Ordering::SeqCst);
+            }
+        })
+    };
+
+    let not_ready = Arc::new(AtomicBool::new(false));
+    let readiness = if service {
+        let probe = container_info.entrypoint.readiness.clone();
+        let delay = container_info.entrypoint.delay.unwrap_or_default();
+        let callback = callback.as_deref().cloned();
+
+        Some(tokio::spawn(await_readiness(
+            probe,
+            delay,
+            callback,
+            not_ready.clone(),
+            terminate.clone(),
+        )))
+    } else {
+        None
+    };
+
+    // With the `file` capture mode, the action writes its output to a file instead of stdout.
+    // The file is kept in a directory of its own, which is removed once the job is done.
+    let output_dir = if command.capture.as_deref() == Some(CAPTURE_FILE) {
+        Some(tempfile::Builder::new().prefix("brane-output-").tempdir()?)
+    } else {
+        None
+    };
+    let output_file = output_dir.as_ref().map(|d| d.path().join("output"));
+
     // Output variables are captured from the stdout
-    let stdout = execute(entrypoint, &command.args, &arguments, &working_dir)?;
-    let output = capture_output(stdout, &function_output, &command.capture, &container_info.types)?;
+    let stdout = task::block_in_place(|| {
+        execute(
+            entrypoint,
+            &command,
+            &arguments,
+            &working_dir,
+            output_file.as_deref(),
+            output_tx,
+            &terminate,
+        )
+    });
+    sigterm_listener.abort();
+    if let Some(readiness) = readiness {
+        readiness.abort();
+    }
+
+    // Make sure all output is sent before the final callback.
+    if let Some(forwarder) = forwarder {
+        forwarder.await?;
+    }
+
+    if not_ready.load(Ordering::SeqCst) {
+        let message = "Service did not become ready in time.";
+        return Err(JobFailure::new(REASON_TIMEOUT, message).into());
+    }
+
+    let stdout = stdout?;
+    let output = match output_file {
+        Some(output_file) => capture_file_output(&output_file, &function_output, &container_info.types)?,
+        None => capture_output(stdout, &function_output, &command.capture, &container_info.types)?,
+    };
 
     if let Some(parameter) = function_output.first() {
-        let value = output
+        let mut value = output
             .get(&parameter.name)
-            .with_context(|| format!("Output '{}' not found.", parameter.name))?;
+            .with_context(|| format!("Output '{}' not found.", parameter.name))?
+            .clone();
+
+        // Output files are uploaded, so that jobs at other locations can stage them as input.
+        if let Some(storage) = &storage {
+            staging::stage_output(&mut value, &parameter.name, &working_dir, storage).await?;
+        }
 
-        Ok(value.clone())
+        Ok(value)
     } else {
         Ok(Value::Unit)
     }
@@ -121,12 +249,15 @@ fn initialize(working_dir: &Path) -> Result<()> {
 ///
 fn execute(
     entrypoint: &str,
-    command_args: &[String],
+    command: &ActionCommand,
     arguments: &Map<Value>,
     working_dir: &Path,
+    output_file: Option<&Path>,
+    output_tx: Option<UnboundedSender<(OutputStream, String)>>,
+    terminate: &AtomicBool,
 ) -> Result<Vec<String>> {
     let entrypoint_path = working_dir.join(entrypoint).canonicalize()?;
-    let command = if entrypoint_path.is_file() {
+    let exec = if entrypoint_path.is_file() {
         Exec::cmd(entrypoint_path)
     } else {
         let segments = entrypoint.split_whitespace().collect::<Vec<&str>>();
@@ -135,32 +266,262 @@ fn execute(
         Exec::cmd(entrypoint_path).args(&segments[1..])
     };
 
-    let envs = construct_envs(arguments)?;
+    // Arguments are passed as environment variables, unless the action wants them as a JSON document.
+    let input_mode = command.input.as_deref().unwrap_or(INPUT_ENV);
+    // The input file is removed once the action is done, i.e., when it goes out of scope.
+    let (envs, input, _input_file) = match input_mode {
+        INPUT_ENV => (construct_envs(arguments)?, None, None),
+        INPUT_FILE => {
+            let input_file = tempfile::Builder::new()
+                .prefix("brane-input-")
+                .suffix(".json")
+                .tempfile()?;
+            fs::write(input_file.path(), construct_input(arguments)?)?;
+
+            let mut envs = Map::<String>::new();
+            envs.insert(INPUT_FILE_ENV.to_string(), input_file.path().display().to_string());
+
+            (envs, None, Some(input_file))
+        }
+        INPUT_STDIN => (Map::new(), Some(construct_input(arguments)?), None),
+        _ => bail!("Unknown input mode: {}", input_mode),
+    };
+
     debug!("Using environment variables:\n{:#?}", envs);
-    let envs: Vec<_> = envs.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    let mut envs: Vec<_> = envs.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    if let Some(output_file) = output_file {
+        envs.push((OUTPUT_FILE_ENV.to_string(), output_file.display().to_string()));
+    }
 
-    let process = command
-        .args(command_args)
+    let mut exec = exec
+        .args(&command.args)
         .env_extend(&envs)
-        .stderr(Redirection::Merge)
-        .stdout(Redirection::Pipe)
-        .stream_stdout()
-        .expect("Couldn't start subprocess.");
+        .stderr(Redirection::Pipe)
+        .stdout(Redirection::Pipe);
 
-    let br = BufReader::new(process);
-    let mut lines = Vec::new();
+    if input.is_some() {
+        exec = exec.stdin(Redirection::Pipe);
+    }
 
-    for line in br.lines().flatten() {
-        println!("{}", &line);
-        lines.push(line);
+    let mut process = exec.popen().context("Couldn't start subprocess.")?;
+
+    // Write the input on a separate thread, as the action may only read it partially, or not at all.
+    if let Some(input) = input {
+        let mut stdin = process.stdin.take().expect("unreachable!");
+        thread::spawn(move || {
+            if let Err(error) = stdin.write_all(input.as_bytes()) {
+                warn!("Failed to write input to action: {:?}", error);
+            }
+        });
     }
 
-    // TODO: is this possible with subprocess crate?
-    // ensure!(result.exit_status == ExitStatus::Exited(0), "Non-zero exit status for action");
+    // Read both pipes on separate threads, so the action can be watched in the meantime.
+    let stderr = process.stderr.take().expect("unreachable!");
+    let stderr = read_lines(stderr, OutputStream::Stderr, output_tx.clone());
+    let stdout = process.stdout.take().expect("unreachable!");
+    let stdout = read_lines(stdout, OutputStream::Stdout, output_tx);
+
+    let timeout = command.timeout.map(Duration::from_secs);
+    let started = Instant::now();
+    let mut timed_out = false;
+    let mut terminated = false;
+    let mut kill_at = None;
+
+    let status = loop {
+        if let Some(status) = process.wait_timeout(POLL_INTERVAL)? {
+            break status;
+        }
+
+        if !timed_out && timeout.map(|t| started.elapsed() >= t).unwrap_or_default() {
+            warn!("Action did not complete within {} seconds, terminating it.", timeout.unwrap().as_secs());
+            timed_out = true;
+        }
+
+        // Actions get a chance to exit gracefully, before they're killed.
+        if (timed_out || terminate.load(Ordering::SeqCst)) && !terminated {
+            process.terminate()?;
+            terminated = true;
+            kill_at = Some(Instant::now() + TERMINATION_GRACE_PERIOD);
+        }
+
+        if kill_at.map(|k| Instant::now() >= k).unwrap_or_default() {
+            warn!("Action did not exit after being terminated, killing it.");
+            process.kill()?;
+            kill_at = None;
+        }
+    };
+
+    let lines = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    let (exit_code, message) = match status {
+        ExitStatus::Exited(code) => (code as i32, format!("Action exited with code {}.", code)),
+        ExitStatus::Signaled(signal) => (128 + signal as i32, format!("Action was killed by signal {}.", signal)),
+        status => bail!("Action ended with an undetermined status: {:?}", status),
+    };
+
+    // An action that timed out fails, even if it handled the termination gracefully.
+    let success_codes = command.success_codes.clone().unwrap_or_else(|| vec![0]);
+    if timed_out || !success_codes.contains(&exit_code) {
+        let tail = stderr.len().saturating_sub(STDERR_TAIL_LINES);
+
+        let mut failure = if timed_out {
+            let message = format!("Action did not complete within {} seconds.", timeout.unwrap().as_secs());
+            JobFailure::new(REASON_TIMEOUT.to_string(), message)
+        } else if terminated || matches!(status, ExitStatus::Signaled(_)) {
+            JobFailure::new(REASON_KILLED.to_string(), message)
+        } else {
+            JobFailure::new(REASON_FAILED.to_string(), message)
+        };
+        failure.exit_code = Some(exit_code);
+        failure.stderr = Some(stderr[tail..].join("\n"));
+
+        return Err(failure.into());
+    }
 
     Ok(lines)
 }
 
+///
+///
+///
+async fn await_readiness(
+    probe: Option<ReadinessProbe>,
+    delay: u64,
+    callback: Option<Callback>,
+    not_ready: Arc<AtomicBool>,
+    terminate: Arc<AtomicBool>,
+) {
+    // Without a probe, the service is assumed to be ready after the (optional) delay.
+    let ready = match probe {
+        Some(probe) => probe_readiness(&probe).await,
+        None => {
+            tokio::time::sleep(Duration::from_secs(delay)).await;
+            Ok(true)
+        }
+    };
+
+    let ready = ready.unwrap_or_else(|error| {
+        warn!("Failed to probe service readiness: {:?}", error);
+        false
+    });
+
+    if !ready {
+        warn!("Service did not become ready in time, terminating it.");
+        not_ready.store(true, Ordering::SeqCst);
+        terminate.store(true, Ordering::SeqCst);
+        return;
+    }
+
+    info!("Service is ready.");
+    match callback {
+        Some(mut callback) => {
+            if let Err(error) = callback.started(None).await {
+                warn!("Failed to send STARTED callback: {:?}", error);
+            }
+        }
+        // Local runs have no callback channel, they watch stdout instead.
+        None => println!("{}", SERVICE_READY_MARKER),
+    }
+}
+
+///
+///
+///
+async fn probe_readiness(probe: &ReadinessProbe) -> Result<bool> {
+    let address = format!("127.0.0.1:{}", probe.port);
+    let interval = Duration::from_secs(probe.interval.unwrap_or(READINESS_INTERVAL));
+    let timeout = Duration::from_secs(probe.timeout.unwrap_or(READINESS_TIMEOUT));
+
+    let started = Instant::now();
+    while started.elapsed() < timeout {
+        let ready = match probe.kind.as_str() {
+            "tcp" => TcpStream::connect(&address).await.is_ok(),
+            "http" => {
+                let path = probe.path.clone().unwrap_or_else(|| String::from("/"));
+                let url = format!("http://{}{}", address, path);
+
+                reqwest::get(url)
+                    .await
+                    .map(|response| response.status().is_success())
+                    .unwrap_or_default()
+            }
+            kind => bail!("Unknown readiness probe kind: {}", kind),
+        };
+
+        if ready {
+            return Ok(true);
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+
+    Ok(false)
+}
+
+///
+///
+///
+fn read_lines<R: Read + Send + 'static>(
+    pipe: R,
+    stream: OutputStream,
+    output_tx: Option<UnboundedSender<(OutputStream, String)>>,
+) -> JoinHandle<Vec<String>> {
+    thread::spawn(move || {
+        let mut lines = Vec::new();
+        for line in BufReader::new(pipe).lines().flatten() {
+            match stream {
+                OutputStream::Stdout => println!("{}", &line),
+                OutputStream::Stderr => eprintln!("{}", &line),
+            }
+            if let Some(output_tx) = &output_tx {
+                let _ = output_tx.send((stream, line.clone()));
+            }
+            lines.push(line);
+        }
+
+        lines
+    })
+}
+
+///
+///
+///
+async fn forward_output(
+    mut callback: Callback,
+    mut receiver: UnboundedReceiver<(OutputStream, String)>,
+) {
+    let mut pending = receiver.recv().await;
+    while let Some((stream, line)) = pending.take() {
+        // Lines that are already available are sent along, as long as they're from the same stream.
+        let mut lines = vec![line];
+        while lines.len() < OUTPUT_BATCH_LINES {
+            match receiver.try_recv() {
+                Ok((next_stream, next_line)) if next_stream == stream => lines.push(next_line),
+                Ok(next) => {
+                    pending = Some(next);
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+
+        let output = JobOutput::new(stream, lines);
+        match serde_json::to_vec(&output) {
+            Ok(payload) => {
+                if let Err(error) = callback.output(Some(payload)).await {
+                    warn!("Failed to send output: {:?}", error);
+                }
+            }
+            Err(error) => warn!("Failed to serialize output: {:?}", error),
+        }
+
+        if pending.is_none() {
+            pending = receiver.recv().await;
+        }
+    }
+}
+
 ///
 ///
 ///
@@ -208,7 +569,10 @@ fn construct_envs(variables: &Map<Value>) -> Result<Map<String>> {
             Value::Unicode(value) => {
                 envs.insert(name, value.to_string());
             }
-            Value::Unit => unreachable!(),
+            Value::Unit => {
+                // Absent optional arguments are left unset.
+                continue;
+            }
             _ => todo!(),
         }
     }
@@ -217,6 +581,18 @@ fn construct_envs(variables: &Map<Value>) -> Result<Map<String>> {
     Ok(envs)
 }
 
+///
+///
+///
+fn construct_input(arguments: &Map<Value>) -> Result<String> {
+    let input: serde_json::Map<String, serde_json::Value> = arguments
+        .iter()
+        .map(|(name, value)| (name.clone(), value.as_json()))
+        .collect();
+
+    Ok(serde_json::to_string(&input)?)
+}
+
 ///
 ///
 ///
@@ -241,6 +617,7 @@ fn construct_struct_envs(
                 }
                 _ => unimplemented!(),
             },
+            Value::Unit => continue,
             _ => unreachable!(),
         };
 
@@ -264,46 +641,67 @@ fn capture_output(
     debug!("Capture output using mode: {:?}", mode);
 
     let stdout = preprocess_stdout(stdout, mode)?;
-    let docs = YamlLoader::load_from_str(&stdout)?;
+    let c_types = c_types.clone().unwrap_or_default();
+
+    parse_output(&stdout, parameters, &c_types)
+}
 
+///
+///
+///
+fn capture_file_output(
+    output_file: &Path,
+    parameters: &[Parameter],
+    c_types: &Option<Map<Type>>,
+) -> Result<Map<Value>> {
+    debug!("Capture output from file: {:?}", output_file);
+
+    // Actions without output don't have to write the file.
+    if parameters.is_empty() && !output_file.exists() {
+        return Ok(Map::new());
+    }
+
+    let output = fs::read_to_string(output_file)
+        .with_context(|| format!("Action didn't write its output to ${} ({:?}).", OUTPUT_FILE_ENV, output_file))?;
     let c_types = c_types.clone().unwrap_or_default();
-    let output = unwrap_yaml_hash(&docs[0], parameters, &c_types)?;
 
-    Ok(output)
+    // JSON is a subset of YAML, so both are accepted.
+    parse_output(&output, parameters, &c_types)
 }
 
 ///
 ///
 ///
-fn unwrap_yaml_hash(
-    value: &Yaml,
+fn parse_output(
+    output: &str,
     parameters: &[Parameter],
     types: &Map<Type>,
 ) -> Result<Map<Value>> {
-    let map = value.as_hash().unwrap();
+    if parameters.is_empty() {
+        return Ok(Map::new());
+    }
+
+    let docs = YamlLoader::load_from_str(output).context("Failed to parse output as YAML (or JSON).")?;
+    let document = match docs.first() {
+        Some(document @ Yaml::Hash(_)) => document,
+        _ => bail!("Expected output to be a mapping from output names to values."),
+    };
 
     let mut output = Map::<Value>::new();
     for p in parameters {
-        let key = Yaml::from_str(p.name.as_str());
-        let value = &map[&key];
-
-        let value = match value {
-            Yaml::Array(elements) => {
-                let n = p.data_type.find('[').unwrap(); // Number of array dimensions
-                let value_type: String = p.data_type.chars().take(n).collect();
-
-                let mut entries = vec![];
-                for element in elements.iter() {
-                    let variable = unwrap_yaml_value(element, &value_type)?;
-                    entries.push(variable);
-                }
+        let value = &document[p.name.as_str()];
 
-                let data_type = p.data_type.to_string();
-                Value::Array { data_type, entries }
-            }
-            Yaml::Hash(_) => unwrap_yaml_struct(value, &p.data_type, types)?,
-            _ => unwrap_yaml_value(&map[&key], &p.data_type)?,
-        };
+        // Absent optional outputs are represented as unit.
+        let (data_type, optional) = optional_type(&p.data_type, p.optional);
+        if is_absent(value) {
+            ensure!(optional, "Output '{}' is missing.", p.name);
+
+            output.insert(p.name.clone(), Value::Unit);
+            continue;
+        }
+
+        let value = unwrap_yaml_value(value, data_type, types)
+            .with_context(|| format!("Output '{}' is not a valid '{}'.", p.name, data_type))?;
 
         output.insert(p.name.clone(), value);
     }
@@ -311,62 +709,104 @@ fn unwrap_yaml_hash(
     Ok(output)
 }
 
+///
+///
+///
 fn unwrap_yaml_struct(
     value: &Yaml,
-    data_type: &str,
+    c_type: &Type,
     types: &Map<Type>,
 ) -> Result<Value> {
-    let arch_type = types
-        .get(data_type)
-        .unwrap_or_else(|| panic!("Missing type `{}`", data_type));
+    ensure!(
+        matches!(value, Yaml::Hash(_)),
+        "Expected a '{}' object, but got: {:?}",
+        c_type.name,
+        value
+    );
+
     let mut properties = Map::<Value>::new();
+    for p in &c_type.properties {
+        let prop_value = &value[p.name.as_str()];
+
+        let (data_type, optional) = optional_type(&p.data_type, p.optional);
+        if is_absent(prop_value) {
+            ensure!(optional, "Property '{}' is missing.", p.name);
+
+            properties.insert(p.name.to_string(), Value::Unit);
+            continue;
+        }
 
-    for p in &arch_type.properties {
-        let prop_value = value[p.name.as_str()].clone();
-        let prop = unwrap_yaml_value(&prop_value, &p.data_type)?;
+        let prop = unwrap_yaml_value(prop_value, data_type, types)
+            .with_context(|| format!("Property '{}' is not a valid '{}'.", p.name, data_type))?;
 
         properties.insert(p.name.to_string(), prop);
     }
 
     Ok(Value::Struct {
-        data_type: data_type.to_string(),
+        data_type: c_type.name.clone(),
         properties,
     })
 }
 
+///
+///
+///
+fn optional_type(
+    data_type: &str,
+    optional: Option<bool>,
+) -> (&str, bool) {
+    match data_type.strip_suffix('?') {
+        Some(data_type) => (data_type, true),
+        None => (data_type, optional.unwrap_or_default()),
+    }
+}
+
+///
+///
+///
+fn is_absent(value: &Yaml) -> bool {
+    matches!(value, Yaml::Null | Yaml::BadValue)
+}
+
 ///
 ///
 ///
 fn unwrap_yaml_value(
     value: &Yaml,
     data_type: &str,
+    types: &Map<Type>,
 ) -> Result<Value> {
     debug!("Unwrapping as {}: {:?} ", data_type, value);
 
-    let value = match data_type {
-        "boolean" => {
-            let value = value.as_bool().unwrap();
-            Value::Boolean(value)
-        }
-        "File[]" => {
-            if let Yaml::Array(elements) = value {
-                let mut entries = vec![];
-                for element in elements.iter() {
-                    let variable = unwrap_yaml_value(element, "File")?;
-                    entries.push(variable);
-                }
+    // Arrays may be nested, and may contain any other type.
+    if let Some(element_type) = data_type.strip_suffix("[]") {
+        let elements = match value {
+            Yaml::Array(elements) => elements,
+            _ => bail!("Expected an array, but got: {:?}", value),
+        };
 
-                Value::Array {
-                    data_type: data_type.to_string(),
-                    entries,
-                }
-            } else {
-                bail!("Expected an array, but it was not.");
-            }
-        }
-        "Directory" | "File" => {
-            let value = String::from(value.as_str().unwrap());
-            let url = Value::Unicode(value);
+        let entries = elements
+            .iter()
+            .enumerate()
+            .map(|(i, element)| {
+                unwrap_yaml_value(element, element_type, types)
+                    .with_context(|| format!("Element {} is not a valid '{}'.", i, element_type))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        return Ok(Value::Array {
+            data_type: data_type.to_string(),
+            entries,
+        });
+    }
+
+    let value = match (data_type, value) {
+        ("boolean", Yaml::Boolean(value)) => Value::Boolean(*value),
+        ("integer", Yaml::Integer(value)) => Value::Integer(*value),
+        ("real", Yaml::Integer(value)) => Value::Real(*value as f64),
+        ("real", Yaml::Real(_)) => Value::Real(value.as_f64().unwrap()),
+        ("Directory" | "File", Yaml::String(value)) => {
+            let url = Value::Unicode(value.clone());
 
             let mut properties: Map<Value> = Default::default();
             properties.insert(String::from("url"), url);
@@ -376,18 +816,15 @@ fn unwrap_yaml_value(
                 properties,
             }
         }
-        "integer" => {
-            let value = value.as_i64().unwrap();
-            Value::Integer(value)
-        }
-        "real" => {
-            let value = value.as_f64().unwrap();
-            Value::Real(value)
-        }
-        _ => {
-            let value = String::from(value.as_str().unwrap());
-            Value::Unicode(value)
-        }
+        (data_type, value) if types.contains_key(data_type) => unwrap_yaml_struct(value, &types[data_type], types)?,
+        ("string", Yaml::String(value)) => Value::Unicode(value.clone()),
+        // YAML may interpret unquoted strings as other scalars.
+        ("string", Yaml::Integer(value)) => Value::Unicode(value.to_string()),
+        ("string", Yaml::Real(value)) => Value::Unicode(value.clone()),
+        ("string", Yaml::Boolean(value)) => Value::Unicode(value.to_string()),
+        // Undeclared types are treated as strings.
+        (data_type, Yaml::String(value)) if !BUILTIN_TYPES.contains(&data_type) => Value::Unicode(value.clone()),
+        (data_type, value) => bail!("Expected a '{}', but got: {:?}", data_type, value),
     };
 
     Ok(value)
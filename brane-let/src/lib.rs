@@ -7,3 +7,4 @@ pub mod callback;
 pub mod exec_code;
 pub mod exec_oas;
 pub mod redirector;
+pub mod staging;
@@ -3,6 +3,7 @@ use brane_let::callback::Callback;
 use brane_let::exec_code;
 use brane_let::exec_oas;
 use brane_let::redirector;
+use brane_let::staging::Storage;
 use clap::Parser;
 use dotenv::dotenv;
 use log::LevelFilter;
@@ -10,8 +11,10 @@ use serde::de::DeserializeOwned;
 use socksx::socks6::options::MetadataOption;
 use socksx::socks6::options::SocksOption;
 use specifications::common::Value;
+use specifications::errors::{JobFailure, REASON_FAILED};
 use std::path::PathBuf;
 use std::process::Command;
+use std::time::Duration;
 use std::{future::Future, process};
 
 #[derive(Parser)]
@@ -29,6 +32,20 @@ struct Opts {
     proxy_address: Option<String>,
     #[clap(short, long, env = "BRANE_MOUNT_DFS")]
     mount_dfs: Option<String>,
+    /// Object storage (S3-compatible or `file://`) used to stage inputs and outputs
+    #[clap(long, env = "BRANE_STORAGE_ENDPOINT")]
+    storage_endpoint: Option<String>,
+    #[clap(long, env = "BRANE_STORAGE_BUCKET")]
+    storage_bucket: Option<String>,
+    #[clap(long, env = "BRANE_STORAGE_REGION")]
+    storage_region: Option<String>,
+    #[clap(long, env = "BRANE_STORAGE_ACCESS_KEY")]
+    storage_access_key: Option<String>,
+    #[clap(long, env = "BRANE_STORAGE_SECRET_KEY")]
+    storage_secret_key: Option<String>,
+    /// Seconds between heartbeats, zero disables them
+    #[clap(long, default_value = "10", env = "BRANE_HEARTBEAT_INTERVAL")]
+    heartbeat_interval: u64,
     /// Prints debug info
     #[clap(short, long, env = "DEBUG", takes_value = false)]
     debug: bool,
@@ -106,11 +123,36 @@ async fn main() -> Result<()> {
         redirector::start(proxy_address, options).await?;
     }
 
+    // Outputs are stored per application and job, so jobs never overwrite each other's outputs.
+    let storage = match opts.storage_endpoint {
+        Some(ref endpoint) => {
+            let bucket = opts
+                .storage_bucket
+                .clone()
+                .context("A storage endpoint requires a bucket.")?;
+            let prefix = format!("{}/{}", application_id, job_id);
+
+            Some(Storage::new(
+                endpoint,
+                bucket,
+                opts.storage_region.clone(),
+                opts.storage_access_key.clone(),
+                opts.storage_secret_key.clone(),
+                prefix,
+            )?)
+        }
+        None => None,
+    };
+
     // Callbacks may be called at any time of the execution.
     let callback = callback_to.map(|callback_to| Callback::new(application_id, location_id, job_id, callback_to));
 
+    let heartbeat_interval = Some(opts.heartbeat_interval)
+        .filter(|interval| *interval > 0)
+        .map(Duration::from_secs);
+
     // Wrap actual execution, so we can always log errors.
-    match run(opts.sub_command, callback).await {
+    match run(opts.sub_command, callback, heartbeat_interval, storage).await {
         Ok(_) => process::exit(0),
         Err(error) => {
             eprintln!("{:?}", error);
@@ -125,6 +167,8 @@ async fn main() -> Result<()> {
 async fn run(
     sub_command: SubCommand,
     callback: Option<impl Future<Output = Result<Callback>>>,
+    heartbeat_interval: Option<Duration>,
+    storage: Option<Storage>,
 ) -> Result<()> {
     // Setup callback channel (gRPC) if enabled.
     let mut callback: Option<Callback> = if let Some(callback) = callback {
@@ -136,12 +180,21 @@ async fn run(
         None
     };
 
+    // Let brane-job know that we're still alive while executing.
+    let heartbeat = callback
+        .as_ref()
+        .zip(heartbeat_interval)
+        .map(|(callback, interval)| callback.start_heartbeat(interval));
+
     let output = match sub_command {
         SubCommand::Code {
             function,
             arguments,
             working_dir,
-        } => exec_code::handle(function, decode_b64(arguments)?, working_dir, &mut callback.as_mut()).await,
+        } => {
+            let arguments = decode_b64(arguments)?;
+            exec_code::handle(function, arguments, working_dir, storage, &mut callback.as_mut()).await
+        }
         SubCommand::NoOp => {
             if let Some(callback) = &mut callback.as_mut() {
                 callback.initialized(None).await?;
@@ -157,6 +210,10 @@ async fn run(
         } => exec_oas::handle(function, decode_b64(arguments)?, working_dir, &mut callback.as_mut()).await,
     };
 
+    if let Some(heartbeat) = heartbeat {
+        heartbeat.abort();
+    }
+
     // Perform final FINISHED callback.
     match output {
         Ok(value) => {
@@ -174,7 +231,13 @@ async fn run(
         }
         Err(error) => {
             if let Some(callback) = &mut callback.as_mut() {
-                callback.failed(None).await?;
+                // Failed actions report their exit code and error output.
+                let failure = match error.downcast_ref::<JobFailure>() {
+                    Some(failure) => failure.clone(),
+                    None => JobFailure::new(REASON_FAILED.to_string(), format!("{:#}", error)),
+                };
+                let payload = serde_json::to_vec(&failure)?;
+                callback.failed(Some(payload)).await?;
             }
 
             Err(error)
@@ -164,6 +164,28 @@ async fn process_message(
                 value: bytes_ba.to_string(),
             });
         }
+        EventKind::Retrying => {
+            information.push(KeyValuePair {
+                key: String::from("attempt"),
+                value: event.order.to_string(),
+            });
+            information.push(KeyValuePair {
+                key: String::from("error"),
+                value: String::from_utf8(payload)?,
+            });
+        }
+        EventKind::Planned => {
+            information.push(KeyValuePair {
+                key: String::from("policy"),
+                value: String::from_utf8(payload)?,
+            });
+        }
+        EventKind::Output => {
+            information.push(KeyValuePair {
+                key: String::from("output"),
+                value: String::from_utf8(payload)?,
+            });
+        }
         _ => {}
     }
 
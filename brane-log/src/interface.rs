@@ -1,4 +1,5 @@
 use prost::{Enumeration, Message};
+use std::collections::HashMap;
 use std::fmt;
 use time::OffsetDateTime;
 
@@ -55,6 +56,7 @@ pub enum CallbackKind {
     Finished = 5,
     Stopped = 6,
     Failed = 7,
+    Output = 8,
 }
 
 impl fmt::Display for CallbackKind {
@@ -82,9 +84,16 @@ pub struct Command {
     pub command: Vec<String>,
     #[prost(tag = "7", repeated, message)]
     pub mounts: Vec<Mount>,
+    #[prost(tag = "8", optional, message)]
+    pub resources: Option<Resources>,
+    #[prost(tag = "9", optional, message)]
+    pub security: Option<Security>,
+    #[prost(tag = "10", optional, message)]
+    pub constraint: Option<Constraint>,
 }
 
 impl Command {
+    #[allow(clippy::too_many_arguments)]
     pub fn new<S: Into<String> + Clone>(
         kind: CommandKind,
         identifier: Option<S>,
@@ -93,6 +102,9 @@ impl Command {
         image: Option<S>,
         command: Vec<S>,
         mounts: Option<Vec<Mount>>,
+        resources: Option<Resources>,
+        security: Option<Security>,
+        constraint: Option<Constraint>,
     ) -> Self {
         Command {
             kind: kind as i32,
@@ -102,6 +114,9 @@ impl Command {
             image: image.map(S::into),
             command: command.iter().map(S::clone).map(S::into).collect(),
             mounts: mounts.unwrap_or_default(),
+            resources,
+            security,
+            constraint,
         }
     }
 }
@@ -185,6 +200,10 @@ pub enum EventKind {
     Failed = 7,
     Connected = 8,
     Disconnected = 9,
+    Lost = 10,
+    Retrying = 11,
+    Planned = 12,
+    Output = 13,
 }
 
 impl fmt::Display for EventKind {
@@ -202,16 +221,115 @@ pub struct Mount {
     pub source: String,
     #[prost(tag = "2", string)]
     pub destination: String,
+    #[prost(tag = "3", bool)]
+    pub read_only: bool,
+    #[prost(tag = "4", enumeration = "MountKind")]
+    pub kind: i32,
 }
 
 impl Mount {
     pub fn new<S: Into<String>>(
+        kind: MountKind,
         source: S,
         destination: S,
+        read_only: bool,
     ) -> Self {
         Mount {
             source: source.into(),
             destination: destination.into(),
+            read_only,
+            kind: kind as i32,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enumeration)]
+pub enum MountKind {
+    Bind = 0,
+    Volume = 1,
+}
+
+impl fmt::Display for MountKind {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_uppercase())
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Resources {
+    #[prost(tag = "1", optional, uint32)]
+    pub cpus: Option<u32>,
+    #[prost(tag = "2", optional, uint32)]
+    pub gpus: Option<u32>,
+    #[prost(tag = "3", optional, uint64)]
+    pub memory: Option<u64>,
+    #[prost(tag = "4", optional, uint64)]
+    pub time: Option<u64>,
+}
+
+impl Resources {
+    pub fn new(
+        cpus: Option<u32>,
+        gpus: Option<u32>,
+        memory: Option<u64>,
+        time: Option<u64>,
+    ) -> Self {
+        Resources {
+            cpus,
+            gpus,
+            memory,
+            time,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Security {
+    #[prost(tag = "1", repeated, string)]
+    pub capabilities: Vec<String>,
+    #[prost(tag = "2", repeated, string)]
+    pub devices: Vec<String>,
+    #[prost(tag = "3", bool)]
+    pub privileged: bool,
+}
+
+impl Security {
+    pub fn new(
+        capabilities: Vec<String>,
+        devices: Vec<String>,
+        privileged: bool,
+    ) -> Self {
+        Security {
+            capabilities,
+            devices,
+            privileged,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Constraint {
+    #[prost(tag = "1", repeated, string)]
+    pub locations: Vec<String>,
+    #[prost(tag = "2", map = "string, string")]
+    pub labels: HashMap<String, String>,
+    #[prost(tag = "3", repeated, string)]
+    pub excluded: Vec<String>,
+}
+
+impl Constraint {
+    pub fn new(
+        locations: Vec<String>,
+        labels: HashMap<String, String>,
+        excluded: Vec<String>,
+    ) -> Self {
+        Constraint {
+            locations,
+            labels,
+            excluded,
         }
     }
 }
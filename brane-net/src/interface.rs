@@ -64,4 +64,8 @@ pub enum EventKind {
     Failed = 7,
     Connected = 8,
     Disconnected = 9,
+    Lost = 10,
+    Retrying = 11,
+    Planned = 12,
+    Output = 13,
 }
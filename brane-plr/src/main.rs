@@ -1,14 +1,17 @@
-use anyhow::{bail, Context, Result};
+mod planner;
+
+use anyhow::{bail, ensure, Context, Result};
 use brane_cfg::infrastructure::Location;
 use brane_cfg::Infrastructure;
-use brane_job::interface::{Command, CommandKind};
+use brane_job::interface::{Command, CommandKind, Event, EventKind};
 use bytes::{Bytes, BytesMut};
 use clap::Parser;
 use dotenv::dotenv;
 use futures::stream::FuturesUnordered;
 use futures::{StreamExt, TryStreamExt};
 use log::LevelFilter;
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
+use planner::{JobLoad, Planner, Policy};
 use prost::Message;
 use rdkafka::{
     admin::{AdminClient, AdminOptions, NewTopic, TopicReplication},
@@ -20,6 +23,7 @@ use rdkafka::{
     util::Timeout,
     Message as KafkaMesage, Offset, TopicPartitionList,
 };
+use std::sync::Arc;
 use tokio::task::JoinHandle;
 
 #[derive(Parser)]
@@ -42,6 +46,9 @@ struct Opts {
     /// Topic to send commands to
     #[clap(short, long = "cmd-to-topic", default_value = "plr-cmd", env = "COMMAND_TO_TOPIC")]
     command_to_topic: String,
+    /// Topic to send and receive events on
+    #[clap(short, long = "evt-topic", default_value = "job-evt", env = "EVENT_TOPIC")]
+    event_topic: String,
     /// Consumer group id
     #[clap(short, long, default_value = "brane-job", env = "GROUP_ID")]
     group_id: String,
@@ -51,6 +58,9 @@ struct Opts {
     /// Number of workers
     #[clap(short = 'w', long, default_value = "1", env = "NUM_WORKERS")]
     num_workers: u8,
+    /// Planning policy (overrides the infra metadata store)
+    #[clap(short, long, env = "PLANNING_POLICY")]
+    policy: Option<String>,
 }
 
 #[tokio::main]
@@ -69,11 +79,32 @@ async fn main() -> Result<()> {
     }
 
     // Ensure that the input/output topics exists.
-    ensure_topics(vec![&opts.command_from_topic, &opts.command_to_topic], &opts.brokers).await?;
+    let topics = vec![&opts.command_from_topic, &opts.command_to_topic, &opts.event_topic];
+    ensure_topics(topics, &opts.brokers).await?;
 
     let infra = Infrastructure::new(opts.infra.clone())?;
     infra.validate()?;
 
+    // The planning policy can be set both on the command line and in the infra metadata store.
+    let policy = match opts.policy.clone().or(infra.get_planner()?) {
+        Some(policy) => policy.parse()?,
+        None => Policy::Random,
+    };
+    info!("Using the '{}' planning policy.", policy);
+
+    let load = Arc::new(JobLoad::default());
+    let planner: Arc<dyn Planner> = Arc::from(planner::create_planner(policy, load.clone()));
+
+    // Only the least-loaded policy needs to keep track of jobs.
+    if policy == Policy::LeastLoaded {
+        tokio::spawn(start_event_monitor(
+            opts.brokers.clone(),
+            opts.group_id.clone(),
+            opts.event_topic.clone(),
+            load,
+        ));
+    }
+
     // Spawn workers, using Tokio tasks and thread pool.
     let workers = (0..opts.num_workers)
         .map(|i| {
@@ -82,7 +113,9 @@ async fn main() -> Result<()> {
                 opts.group_id.clone(),
                 opts.command_from_topic.clone(),
                 opts.command_to_topic.clone(),
+                opts.event_topic.clone(),
                 infra.clone(),
+                planner.clone(),
             ));
 
             info!("Spawned asynchronous worker #{}.", i + 1);
@@ -148,7 +181,9 @@ async fn start_worker(
     group_id: String,
     cmd_from_topic: String,
     cmd_to_topic: String,
+    evt_topic: String,
     infra: Infrastructure,
+    planner: Arc<dyn Planner>,
 ) -> Result<()> {
     let producer: FutureProducer = ClientConfig::new()
         .set("bootstrap.servers", &brokers)
@@ -191,7 +226,9 @@ async fn start_worker(
         let owned_message = borrowed_message.detach();
         let producer = producer.clone();
         let infra = infra.clone();
+        let planner = planner.clone();
         let cmd_to_topic = cmd_to_topic.clone();
+        let evt_topic = evt_topic.clone();
 
         async move {
             let msg_key = owned_message
@@ -211,16 +248,29 @@ async fn start_worker(
                 return Ok(());
             }
 
-            let processing = process_cmd_message(msg_payload, infra).await;
+            let processing = process_cmd_message(msg_payload, infra, planner.as_ref()).await;
             match processing {
-                Ok(payload) => {
-                    // Send event on output topic
-                    let message = FutureRecord::to(&cmd_to_topic)
-                        .key(&msg_key)
-                        .payload(payload.to_bytes());
-
-                    if let Err(error) = producer.send(message, Timeout::Never).await {
-                        error!("Failed to send command (key: {}): {:?}", msg_key, error);
+                Ok((payload, events)) => {
+                    // Send command on output topic, unless it couldn't be planned.
+                    if let Some(payload) = payload {
+                        let message = FutureRecord::to(&cmd_to_topic)
+                            .key(&msg_key)
+                            .payload(payload.to_bytes());
+
+                        if let Err(error) = producer.send(message, Timeout::Never).await {
+                            error!("Failed to send command (key: {}): {:?}", msg_key, error);
+                        }
+                    }
+
+                    for (evt_key, event) in events {
+                        let mut payload = BytesMut::with_capacity(64);
+                        event.encode(&mut payload).unwrap();
+
+                        // Send event on event topic
+                        let message = FutureRecord::to(&evt_topic).key(&evt_key).payload(payload.to_bytes());
+                        if let Err(error) = producer.send(message, Timeout::Never).await {
+                            error!("Failed to send event (key: {}): {:?}", evt_key, error);
+                        }
                     }
                 }
                 Err(error) => error!("{:?}", error),
@@ -241,27 +291,85 @@ async fn start_worker(
 async fn process_cmd_message(
     payload: &[u8],
     infra: Infrastructure,
-) -> Result<Bytes> {
-    use rand::seq::SliceRandom;
-
+    planner: &dyn Planner,
+) -> Result<(Option<Bytes>, Vec<(String, Event)>)> {
     // Decode payload into a command message.
     let mut command = Command::decode(payload).unwrap();
     let kind = CommandKind::from_i32(command.kind).unwrap();
 
+    let mut events = vec![];
+
     // Returns an empty string if location is None.
     if command.location() == "" && kind == CommandKind::Create {
-        let locations = infra.get_locations()?;
+        let mut locations = infra.get_locations()?;
+        locations.sort();
+
+        let mut candidates = locations
+            .into_iter()
+            .map(|location| {
+                let metadata = infra.get_location_metadata(&location)?;
+                Ok((location, metadata))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Only locations that satisfy the constraints (see `on` statements) are considered.
+        if let Some(constraint) = &command.constraint {
+            let mut reasons: Vec<String> = constraint
+                .locations
+                .iter()
+                .filter(|l| !candidates.iter().any(|(name, _)| name == *l))
+                .map(|l| format!("'{}' is not a known location", l))
+                .collect();
+
+            candidates.retain(
+                |(name, location)| match planner::check_constraint(name, location, constraint) {
+                    Ok(_) => true,
+                    Err(reason) => {
+                        reasons.push(format!("'{}' {}", name, reason));
+                        false
+                    }
+                },
+            );
+
+            if candidates.is_empty() {
+                let message = format!(
+                    "No location satisfies the constraints of command '{}': {}.",
+                    command.identifier(),
+                    reasons.join("; ")
+                );
 
-        // Choose a random location
-        let location = locations.choose(&mut rand::thread_rng());
-        let location = location.unwrap().clone();
+                return Ok((None, vec![planning_failed(&command, message)]));
+            }
+        }
+
+        let location = match planner.plan(&command, &candidates) {
+            Ok(location) => location,
+            Err(error) => return Ok((None, vec![planning_failed(&command, error.to_string())])),
+        };
+        let policy = planner.policy();
 
         info!(
-            "Assigned command '{}' to location '{}'.",
+            "Assigned command '{}' to location '{}' ({} policy).",
             command.identifier(),
-            location
+            location,
+            policy
         );
 
+        // Record the placement decision, the job's events use the same identifier prefix.
+        let order = 0;
+        let key = format!("{}#{}", command.identifier(), order);
+        let event = Event::new(
+            EventKind::Planned,
+            command.identifier().to_string(),
+            command.application().to_string(),
+            location.clone(),
+            String::from("job"),
+            order,
+            Some(policy.to_string().into_bytes()),
+            None,
+        );
+        events.push((key, event));
+
         let metadata = infra.get_location_metadata(&location)?;
         command.location = Some(location);
 
@@ -276,9 +384,92 @@ async fn process_cmd_message(
         }
     }
 
+    // A STOP command is routed to wherever the job was created, so no planning is needed.
+    if kind == CommandKind::Stop {
+        ensure!(
+            command.location() != "",
+            "Cannot route STOP command '{}' without a location.",
+            command.identifier()
+        );
+
+        info!(
+            "Forwarding STOP command '{}' to location '{}'.",
+            command.identifier(),
+            command.location()
+        );
+    }
+
     // Encode command message into a payload.
     let mut payload = BytesMut::with_capacity(64);
     command.encode(&mut payload).unwrap();
 
-    Ok(Bytes::from(payload))
+    Ok((Some(Bytes::from(payload)), events))
+}
+
+///
+///
+///
+fn planning_failed(
+    command: &Command,
+    message: String,
+) -> (String, Event) {
+    warn!("{}", message);
+
+    // The job is never created, thus the FAILED event is the first.
+    let order = 0;
+    let key = format!("{}#{}", command.identifier(), order);
+    let event = Event::new(
+        EventKind::Failed,
+        command.identifier().to_string(),
+        command.application().to_string(),
+        String::new(),
+        String::from("job"),
+        order,
+        Some(message.into_bytes()),
+        None,
+    );
+
+    (key, event)
+}
+
+///
+///
+///
+async fn start_event_monitor(
+    brokers: String,
+    group_id: String,
+    evt_topic: String,
+    load: Arc<JobLoad>,
+) -> Result<()> {
+    let consumer: StreamConsumer = ClientConfig::new()
+        .set("group.id", &group_id)
+        .set("bootstrap.servers", &brokers)
+        .set("enable.partition.eof", "false")
+        .set("session.timeout.ms", "6000")
+        .set("enable.auto.commit", "false")
+        .create()
+        .context("Failed to create Kafka consumer.")?;
+
+    // Only jobs that are created from now on are taken into account.
+    let mut tpl = TopicPartitionList::new();
+    tpl.add_partition_offset(&evt_topic, 0, Offset::End)?;
+
+    consumer
+        .assign(&tpl)
+        .context("Failed to manually assign topic, partition, and/or offset to consumer.")?;
+
+    consumer
+        .stream()
+        .try_for_each(|borrowed_message| {
+            if let Some(payload) = borrowed_message.payload() {
+                match Event::decode(payload) {
+                    Ok(event) => load.update(&event),
+                    Err(error) => debug!("Failed to decode event: {:?}", error),
+                }
+            }
+
+            async { Ok(()) }
+        })
+        .await
+        .context("Event monitor did not run until completion.")
 }
@@ -0,0 +1,533 @@
+use anyhow::{anyhow, bail, ensure, Result};
+use brane_cfg::infrastructure::{DatasetKind, Location};
+use brane_job::interface::{Command, Constraint, Event, EventKind, MountKind};
+use dashmap::DashMap;
+use rand::seq::SliceRandom;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    Capabilities,
+    DataLocality,
+    LeastLoaded,
+    Random,
+    RoundRobin,
+}
+
+impl FromStr for Policy {
+    type Err = anyhow::Error;
+
+    fn from_str(policy: &str) -> Result<Self> {
+        let policy = match policy.to_lowercase().as_str() {
+            "capabilities" => Policy::Capabilities,
+            "data-locality" => Policy::DataLocality,
+            "least-loaded" => Policy::LeastLoaded,
+            "random" => Policy::Random,
+            "round-robin" => Policy::RoundRobin,
+            _ => bail!("Unknown planning policy: {}", policy),
+        };
+
+        Ok(policy)
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        let policy = match self {
+            Policy::Capabilities => "capabilities",
+            Policy::DataLocality => "data-locality",
+            Policy::LeastLoaded => "least-loaded",
+            Policy::Random => "random",
+            Policy::RoundRobin => "round-robin",
+        };
+
+        write!(f, "{}", policy)
+    }
+}
+
+pub trait Planner: Send + Sync {
+    ///
+    ///
+    ///
+    fn policy(&self) -> Policy;
+
+    ///
+    ///
+    ///
+    fn plan(
+        &self,
+        command: &Command,
+        candidates: &[(String, Location)],
+    ) -> Result<String>;
+}
+
+///
+///
+///
+pub fn create_planner(
+    policy: Policy,
+    load: Arc<JobLoad>,
+) -> Box<dyn Planner> {
+    match policy {
+        Policy::Capabilities => Box::new(CapabilitiesPlanner {}),
+        Policy::DataLocality => Box::new(DataLocalityPlanner {}),
+        Policy::LeastLoaded => Box::new(LeastLoadedPlanner { load }),
+        Policy::Random => Box::new(RandomPlanner {}),
+        Policy::RoundRobin => Box::new(RoundRobinPlanner {
+            next: AtomicUsize::new(0),
+        }),
+    }
+}
+
+/// Keeps track of the active jobs per location, based on job events.
+#[derive(Debug, Default)]
+pub struct JobLoad {
+    jobs: DashMap<String, String>,
+}
+
+impl JobLoad {
+    ///
+    ///
+    ///
+    pub fn assign(
+        &self,
+        correlation_id: &str,
+        location: &str,
+    ) {
+        self.jobs.insert(correlation_id.to_string(), location.to_string());
+    }
+
+    ///
+    ///
+    ///
+    pub fn count(
+        &self,
+        location: &str,
+    ) -> usize {
+        self.jobs.iter().filter(|entry| entry.value() == location).count()
+    }
+
+    ///
+    ///
+    ///
+    pub fn update(
+        &self,
+        event: &Event,
+    ) {
+        // Job identifiers are prefixed with the correlation identifier of the command.
+        let correlation_id = event.identifier.split('-').next().unwrap_or_default();
+
+        match EventKind::from_i32(event.kind) {
+            Some(EventKind::Created) => self.assign(correlation_id, &event.location),
+            Some(EventKind::Finished | EventKind::Failed | EventKind::Stopped | EventKind::Lost) => {
+                self.jobs.remove(correlation_id);
+            }
+            _ => {}
+        }
+    }
+}
+
+pub struct CapabilitiesPlanner {}
+
+impl Planner for CapabilitiesPlanner {
+    fn policy(&self) -> Policy {
+        Policy::Capabilities
+    }
+
+    fn plan(
+        &self,
+        command: &Command,
+        candidates: &[(String, Location)],
+    ) -> Result<String> {
+        let capable: Vec<_> = candidates
+            .iter()
+            .filter(|(_, location)| is_capable(command, location))
+            .cloned()
+            .collect();
+
+        if capable.is_empty() {
+            bail!(
+                "None of the locations is capable of running command '{}'.",
+                command.identifier()
+            );
+        }
+
+        choose_random(&capable)
+    }
+}
+
+pub struct DataLocalityPlanner {}
+
+impl Planner for DataLocalityPlanner {
+    fn policy(&self) -> Policy {
+        Policy::DataLocality
+    }
+
+    fn plan(
+        &self,
+        command: &Command,
+        candidates: &[(String, Location)],
+    ) -> Result<String> {
+        let scores: Vec<usize> = candidates
+            .iter()
+            .map(|(_, location)| count_local_mounts(command, location))
+            .collect();
+
+        // Without any local data, every location is as good as another.
+        let best = scores.iter().max().copied().unwrap_or_default();
+        let preferred: Vec<_> = candidates
+            .iter()
+            .zip(scores)
+            .filter(|(_, score)| *score == best)
+            .map(|(candidate, _)| candidate.clone())
+            .collect();
+
+        choose_random(&preferred)
+    }
+}
+
+pub struct LeastLoadedPlanner {
+    load: Arc<JobLoad>,
+}
+
+impl Planner for LeastLoadedPlanner {
+    fn policy(&self) -> Policy {
+        Policy::LeastLoaded
+    }
+
+    fn plan(
+        &self,
+        command: &Command,
+        candidates: &[(String, Location)],
+    ) -> Result<String> {
+        let location = candidates
+            .iter()
+            .min_by_key(|(name, _)| self.load.count(name))
+            .map(|(name, _)| name.clone())
+            .ok_or_else(|| anyhow!("No locations to choose from."))?;
+
+        // Count the job right away, it takes a while before its CREATED event arrives.
+        self.load.assign(command.identifier(), &location);
+
+        Ok(location)
+    }
+}
+
+pub struct RandomPlanner {}
+
+impl Planner for RandomPlanner {
+    fn policy(&self) -> Policy {
+        Policy::Random
+    }
+
+    fn plan(
+        &self,
+        _command: &Command,
+        candidates: &[(String, Location)],
+    ) -> Result<String> {
+        choose_random(candidates)
+    }
+}
+
+pub struct RoundRobinPlanner {
+    next: AtomicUsize,
+}
+
+impl Planner for RoundRobinPlanner {
+    fn policy(&self) -> Policy {
+        Policy::RoundRobin
+    }
+
+    fn plan(
+        &self,
+        _command: &Command,
+        candidates: &[(String, Location)],
+    ) -> Result<String> {
+        if candidates.is_empty() {
+            bail!("No locations to choose from.");
+        }
+
+        let next = self.next.fetch_add(1, Ordering::Relaxed);
+        Ok(candidates[next % candidates.len()].0.clone())
+    }
+}
+
+///
+///
+///
+fn choose_random(candidates: &[(String, Location)]) -> Result<String> {
+    candidates
+        .choose(&mut rand::thread_rng())
+        .map(|(name, _)| name.clone())
+        .ok_or_else(|| anyhow!("No locations to choose from."))
+}
+
+///
+///
+///
+pub fn check_constraint(
+    name: &str,
+    location: &Location,
+    constraint: &Constraint,
+) -> Result<()> {
+    ensure!(
+        constraint.locations.is_empty() || constraint.locations.iter().any(|l| l == name),
+        "is not one of the allowed locations"
+    );
+    ensure!(!constraint.excluded.iter().any(|l| l == name), "is excluded");
+
+    let labels = location.get_labels();
+    for (key, value) in &constraint.labels {
+        match labels.get(key) {
+            Some(actual) if actual == value => {}
+            Some(actual) => bail!("has label '{}: {}' instead of '{}: {}'", key, actual, key, value),
+            None => bail!("has no label '{}'", key),
+        }
+    }
+
+    Ok(())
+}
+
+///
+///
+///
+pub fn is_capable(
+    command: &Command,
+    location: &Location,
+) -> bool {
+    let gpus = command.resources.as_ref().and_then(|r| r.gpus).unwrap_or_default();
+    if gpus > location.get_gpus() {
+        return false;
+    }
+
+    // Singularity doesn't support named volumes (see brane-job).
+    let volumes = command.mounts.iter().any(|m| m.kind() == MountKind::Volume);
+    if volumes && location.get_runtime() == "singularity" {
+        return false;
+    }
+
+    if let Some(security) = &command.security {
+        let policy = location.get_security_policy();
+        if policy
+            .check(&security.capabilities, &security.devices, security.privileged)
+            .is_err()
+        {
+            return false;
+        }
+    }
+
+    true
+}
+
+///
+///
+///
+fn count_local_mounts(
+    command: &Command,
+    location: &Location,
+) -> usize {
+    let datasets = location.get_datasets();
+
+    command
+        .mounts
+        .iter()
+        .filter(|mount| {
+            datasets.values().any(|dataset| match (mount.kind(), dataset.kind) {
+                (MountKind::Bind, DatasetKind::Bind) => Path::new(&mount.source).starts_with(&dataset.source),
+                (MountKind::Volume, DatasetKind::Volume) => mount.source == dataset.source,
+                _ => false,
+            })
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use brane_job::interface::{CommandKind, Mount, Resources};
+    use std::collections::HashMap;
+
+    fn location(extra: &str) -> Location {
+        let yaml = format!(
+            "kind: local\ncallback_to: callback\nnetwork: brane\nregistry: registry\n{}",
+            extra
+        );
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    fn candidates(locations: Vec<(&str, &str)>) -> Vec<(String, Location)> {
+        locations
+            .into_iter()
+            .map(|(name, extra)| (name.to_string(), location(extra)))
+            .collect()
+    }
+
+    fn command(
+        mounts: Vec<Mount>,
+        resources: Option<Resources>,
+    ) -> Command {
+        Command::new(
+            CommandKind::Create,
+            Some("A1234abcdRxyz"),
+            Some("app"),
+            None,
+            Some("image"),
+            vec![],
+            Some(mounts),
+            resources,
+            None,
+            None,
+        )
+    }
+
+    fn plan_many(
+        planner: &dyn Planner,
+        command: &Command,
+        candidates: &[(String, Location)],
+    ) -> Vec<String> {
+        let mut planned: Vec<String> = (0..50).map(|_| planner.plan(command, candidates).unwrap()).collect();
+        planned.sort();
+        planned.dedup();
+        planned
+    }
+
+    #[test]
+    fn test_no_locations() {
+        let load = Arc::new(JobLoad::default());
+        let policies = [
+            Policy::Capabilities,
+            Policy::DataLocality,
+            Policy::LeastLoaded,
+            Policy::Random,
+            Policy::RoundRobin,
+        ];
+
+        for policy in policies.iter() {
+            let planner = create_planner(*policy, load.clone());
+            assert!(planner.plan(&command(vec![], None), &[]).is_err(), "{}", policy);
+        }
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let planner = CapabilitiesPlanner {};
+        let candidates = candidates(vec![("a", "gpus: 1"), ("b", ""), ("c", "gpus: 2")]);
+
+        // Locations that are equally capable are chosen at random.
+        let gpus = Some(Resources::new(None, Some(1), None, None));
+        let planned = plan_many(&planner, &command(vec![], gpus), &candidates);
+        assert_eq!(planned, vec!["a", "c"]);
+
+        let gpus = Some(Resources::new(None, Some(4), None, None));
+        assert!(planner.plan(&command(vec![], gpus), &candidates).is_err());
+    }
+
+    #[test]
+    fn test_data_locality() {
+        let planner = DataLocalityPlanner {};
+        let candidates = candidates(vec![
+            ("a", "datasets:\n  data:\n    source: /data\n    destination: /data"),
+            ("b", ""),
+            ("c", "datasets:\n  data:\n    source: /data/set\n    destination: /data"),
+        ]);
+
+        // The location with most of the data wins, ties are broken at random.
+        let mounts = vec![Mount::new(MountKind::Bind, "/data/set/file", "/input", true)];
+        let planned = plan_many(&planner, &command(mounts, None), &candidates);
+        assert_eq!(planned, vec!["a", "c"]);
+
+        let mounts = vec![Mount::new(MountKind::Bind, "/data/other", "/input", true)];
+        assert_eq!(planner.plan(&command(mounts, None), &candidates).unwrap(), "a");
+
+        // Without any local data, any location can be chosen.
+        let planned = plan_many(&planner, &command(vec![], None), &candidates);
+        assert_eq!(planned, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_least_loaded() {
+        let load = Arc::new(JobLoad::default());
+        let planner = LeastLoadedPlanner { load: load.clone() };
+        let candidates = candidates(vec![("a", ""), ("b", ""), ("c", "")]);
+
+        load.assign("A1", "a");
+        load.assign("A2", "a");
+        load.assign("A3", "c");
+
+        // Ties are broken by the order of the candidates, planned jobs count immediately.
+        let planned: Vec<String> = (0..4)
+            .map(|i| {
+                let mut command = command(vec![], None);
+                command.identifier = Some(format!("B{}", i));
+
+                planner.plan(&command, &candidates).unwrap()
+            })
+            .collect();
+        assert_eq!(planned, vec!["b", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn test_least_loaded_counts_events() {
+        let load = JobLoad::default();
+
+        let event = |kind, identifier| Event::new(kind, identifier, "app", "a", "job", 0, None, None);
+        load.update(&event(EventKind::Created, "A1-abc"));
+        load.update(&event(EventKind::Created, "A2-def"));
+        assert_eq!(load.count("a"), 2);
+
+        load.update(&event(EventKind::Finished, "A1-abc"));
+        assert_eq!(load.count("a"), 1);
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let planner = RoundRobinPlanner {
+            next: AtomicUsize::new(0),
+        };
+        let candidates = candidates(vec![("a", ""), ("b", "")]);
+
+        let planned: Vec<String> = (0..3)
+            .map(|_| planner.plan(&command(vec![], None), &candidates).unwrap())
+            .collect();
+        assert_eq!(planned, vec!["a", "b", "a"]);
+    }
+
+    #[test]
+    fn test_random() {
+        let planner = RandomPlanner {};
+        let candidates = candidates(vec![("a", ""), ("b", "")]);
+
+        let planned = plan_many(&planner, &command(vec![], None), &candidates);
+        assert_eq!(planned, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_check_constraint() {
+        let location = location("labels:\n  zone: eu");
+
+        let constraint = |locations: Vec<&str>, labels: Vec<(&str, &str)>, excluded: Vec<&str>| {
+            Constraint::new(
+                locations.into_iter().map(String::from).collect(),
+                labels
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect::<HashMap<_, _>>(),
+                excluded.into_iter().map(String::from).collect(),
+            )
+        };
+
+        assert!(check_constraint("a", &location, &constraint(vec![], vec![], vec![])).is_ok());
+        assert!(check_constraint("a", &location, &constraint(vec!["a"], vec![("zone", "eu")], vec![])).is_ok());
+        assert!(check_constraint("a", &location, &constraint(vec!["b"], vec![], vec![])).is_err());
+        assert!(check_constraint("a", &location, &constraint(vec![], vec![], vec!["a"])).is_err());
+        assert!(check_constraint("a", &location, &constraint(vec![], vec![("zone", "us")], vec![])).is_err());
+        assert!(check_constraint("a", &location, &constraint(vec![], vec![("tier", "gpu")], vec![])).is_err());
+    }
+}
@@ -1,5 +1,5 @@
 #[repr(u8)]
-#[derive(Debug, PartialEq, PartialOrd, FromPrimitive, ToPrimitive)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, FromPrimitive, ToPrimitive)]
 pub enum JobStatus {
     Unknown = 0,
     Created = 1,
@@ -9,4 +9,5 @@ pub enum JobStatus {
     Finished = 5,
     Stopped = 6,
     Failed = 7,
+    Lost = 8,
 }
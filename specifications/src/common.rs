@@ -47,7 +47,9 @@ impl Parameter {
 pub struct Function {
     pub parameters: Vec<Parameter>,
     pub pattern: Option<CallPattern>,
+    pub resources: Option<Resources>,
     pub return_type: String,
+    pub security: Option<Security>,
 }
 
 impl Function {
@@ -58,11 +60,76 @@ impl Function {
         parameters: Vec<Parameter>,
         pattern: Option<CallPattern>,
         return_type: String,
+        resources: Option<Resources>,
+        security: Option<Security>,
     ) -> Self {
         Function {
             parameters,
             pattern,
+            resources,
             return_type,
+            security,
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resources {
+    /// Number of CPU cores.
+    pub cpus: Option<u32>,
+    /// Number of GPUs.
+    pub gpus: Option<u32>,
+    /// Memory, in megabytes.
+    pub memory: Option<u64>,
+    /// Maximum wall-time, in seconds.
+    pub time: Option<u64>,
+}
+
+impl Resources {
+    ///
+    ///
+    ///
+    pub fn new(
+        cpus: Option<u32>,
+        gpus: Option<u32>,
+        memory: Option<u64>,
+        time: Option<u64>,
+    ) -> Self {
+        Resources {
+            cpus,
+            gpus,
+            memory,
+            time,
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Security {
+    /// Linux capabilities, e.g. `NET_ADMIN`.
+    pub capabilities: Option<Vec<String>>,
+    /// Host devices, e.g. `/dev/fuse`.
+    pub devices: Option<Vec<String>>,
+    pub privileged: Option<bool>,
+}
+
+impl Security {
+    ///
+    ///
+    ///
+    pub fn new(
+        capabilities: Option<Vec<String>>,
+        devices: Option<Vec<String>>,
+        privileged: Option<bool>,
+    ) -> Self {
+        Security {
+            capabilities,
+            devices,
+            privileged,
         }
     }
 }
@@ -175,6 +242,8 @@ impl Property {
 pub struct SpecClass {
     pub name: String,
     pub properties: HashMap<String, String>,
+    #[serde(default)]
+    pub defaults: HashMap<String, Value>,
     pub methods: HashMap<String, SpecFunction>,
 }
 
@@ -182,11 +251,13 @@ impl SpecClass {
     pub fn new(
         name: String,
         properties: HashMap<String, String>,
+        defaults: HashMap<String, Value>,
         methods: HashMap<String, SpecFunction>,
     ) -> Self {
         Self {
             name,
             properties,
+            defaults,
             methods,
         }
     }
@@ -240,6 +311,8 @@ pub struct FunctionExt {
     pub name: String,
     pub package: String,
     pub parameters: Vec<Parameter>,
+    pub resources: Option<Resources>,
+    pub security: Option<Security>,
     pub version: String,
 }
 
@@ -313,7 +386,9 @@ impl Value {
             JValue::String(s) => Value::Unicode(s.clone()),
             JValue::Array(a) => {
                 let entries: Vec<Value> = a.iter().map(|v| Value::from_json(v)).collect();
-                let data_type = format!("{}[]", entries.first().unwrap().data_type());
+                // Without entries, the element type is only known once the array is coerced.
+                let element_type = entries.first().map(Value::data_type).unwrap_or("unit");
+                let data_type = format!("{}[]", element_type);
 
                 Value::Array { data_type, entries }
             }
@@ -350,6 +425,99 @@ impl Value {
         }
     }
 
+    ///
+    ///
+    ///
+    pub fn coerce(
+        self,
+        data_type: &str,
+    ) -> Result<Value> {
+        use Value::*;
+
+        // Optional types also accept unit, which represents the absence of a value.
+        if let Some(data_type) = data_type.strip_suffix('?') {
+            return match self {
+                Unit => Ok(Unit),
+                value => value.coerce(data_type),
+            };
+        }
+
+        // Values from the command line are strings, arrays and objects are written as YAML.
+        if let Unicode(text) = &self {
+            let scalar = matches!(
+                data_type,
+                "boolean" | "integer" | "real" | "string" | "Directory" | "File"
+            );
+
+            if !scalar {
+                let value: JValue = serde_yaml::from_str(text)?;
+                return match Value::from_json(&value) {
+                    Unicode(_) => bail!("Expected a value of type '{}', but got 'string'.", data_type),
+                    value => value.coerce(data_type),
+                };
+            }
+        }
+
+        if let Some(element_type) = data_type.strip_suffix("[]") {
+            return match self {
+                Array { entries, .. } => {
+                    let entries = entries
+                        .into_iter()
+                        .map(|e| e.coerce(element_type))
+                        .collect::<Result<Vec<Value>>>()?;
+
+                    Ok(Array {
+                        data_type: data_type.to_string(),
+                        entries,
+                    })
+                }
+                value => bail!(
+                    "Expected a value of type '{}', but got '{}'.",
+                    data_type,
+                    value.data_type()
+                ),
+            };
+        }
+
+        // Values from the command line or a YAML file are parsed leniently.
+        let value = match (self, data_type) {
+            (Unicode(s), "boolean") => Boolean(s.parse()?),
+            (Unicode(s), "integer") => Integer(s.parse()?),
+            (Unicode(s), "real") => Real(s.parse()?),
+            (Integer(i), "real") => Real(i as f64),
+            (Boolean(b), "string") => Unicode(b.to_string()),
+            (Integer(i), "string") => Unicode(i.to_string()),
+            (Real(r), "string") => Unicode(r.to_string()),
+            (Unicode(url), "Directory") | (Unicode(url), "File") => {
+                let mut properties = Map::<Value>::new();
+                properties.insert(String::from("url"), Unicode(url));
+
+                Struct {
+                    data_type: data_type.to_string(),
+                    properties,
+                }
+            }
+            (
+                Struct {
+                    data_type: anonymous,
+                    properties,
+                },
+                data_type,
+            ) if anonymous == "anonymous" => Struct {
+                data_type: data_type.to_string(),
+                properties,
+            },
+            (value, _) if value.data_type() == data_type => value,
+            (value, _) => bail!(
+                "Expected a value of type '{}', but got '{}'.",
+                data_type,
+                value.data_type()
+            ),
+        };
+
+        Ok(value)
+    }
+
     ///
     ///
     ///
@@ -1,4 +1,4 @@
-use crate::common::{CallPattern, Parameter, Type};
+use crate::common::{CallPattern, Parameter, Resources, Security, Type};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
@@ -8,28 +8,51 @@ use std::path::PathBuf;
 
 type Map<T> = std::collections::HashMap<String, T>;
 
+/// Printed by branelet once a service is ready, if there is no callback channel to report it.
+pub const SERVICE_READY_MARKER: &str = "--> SERVICE READY";
+
 #[skip_serializing_none]
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ContainerInfo {
     pub actions: Map<Action>,
     pub base: Option<String>,
+    pub build: Option<BuildStage>,
+    pub capabilities: Option<Vec<String>>,
     pub contributors: Option<Vec<String>>,
     pub description: Option<String>,
+    pub devices: Option<Vec<String>>,
     pub entrypoint: Entrypoint,
     pub environment: Option<Map<String>>,
     pub dependencies: Option<Vec<String>>,
     pub files: Option<Vec<String>>,
     pub initialize: Option<Vec<String>>,
     pub install: Option<Vec<String>>,
+    /// Files that `install` commands need, which are added before them (default: all files, after them).
+    pub install_files: Option<Vec<String>>,
     pub kind: String,
     pub name: String,
+    /// One of: apk, apt, dnf, or micromamba (default: based on the base image).
+    pub package_manager: Option<String>,
+    pub privileged: Option<bool>,
     pub types: Option<Map<Type>>,
     pub version: String,
 }
 
 #[allow(unused)]
 impl ContainerInfo {
+    pub fn security(&self) -> Option<Security> {
+        if self.capabilities.is_none() && self.devices.is_none() && self.privileged.is_none() {
+            return None;
+        }
+
+        Some(Security::new(
+            self.capabilities.clone(),
+            self.devices.clone(),
+            self.privileged,
+        ))
+    }
+
     pub fn from_path(path: PathBuf) -> Result<ContainerInfo> {
         let contents = fs::read_to_string(path)?;
 
@@ -57,6 +80,7 @@ pub struct Action {
     pub pattern: Option<CallPattern>,
     pub input: Option<Vec<Parameter>>,
     pub output: Option<Vec<Parameter>>,
+    pub resources: Option<Resources>,
 }
 
 #[skip_serializing_none]
@@ -65,6 +89,10 @@ pub struct Action {
 pub struct ActionCommand {
     pub args: Vec<String>,
     pub capture: Option<String>,
+    pub input: Option<String>,
+    pub success_codes: Option<Vec<i32>>,
+    /// Maximum duration of the command, in seconds.
+    pub timeout: Option<u64>,
 }
 
 #[skip_serializing_none]
@@ -75,6 +103,17 @@ pub struct ActionEndpoint {
     pub path: String,
 }
 
+#[skip_serializing_none]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildStage {
+    /// Paths, relative to the working directory, that are copied into the package.
+    pub artifacts: Vec<String>,
+    pub base: Option<String>,
+    pub commands: Vec<String>,
+    pub dependencies: Option<Vec<String>>,
+}
+
 #[skip_serializing_none]
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -83,4 +122,16 @@ pub struct Entrypoint {
     pub exec: String,
     pub content: Option<String>,
     pub delay: Option<u64>,
+    pub readiness: Option<ReadinessProbe>,
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadinessProbe {
+    pub kind: String,
+    pub port: u16,
+    pub path: Option<String>,
+    pub interval: Option<u64>,
+    pub timeout: Option<u64>,
 }
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::error::Error;
+use std::fmt;
+
+/// Reason used when a job failed for an unspecified reason.
+pub const REASON_FAILED: &str = "failed";
+/// Reason used when a job was stopped before it finished.
+pub const REASON_STOPPED: &str = "stopped";
+/// Reason used when a job did not finish in time.
+pub const REASON_TIMEOUT: &str = "timeout";
+/// Reason used when a job stopped sending heartbeats.
+pub const REASON_LOST: &str = "lost";
+/// Reason used when a job was terminated by a signal it didn't send itself.
+pub const REASON_KILLED: &str = "killed";
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobFailure {
+    pub reason: String,
+    pub message: String,
+    pub exit_code: Option<i32>,
+    pub stderr: Option<String>,
+}
+
+impl JobFailure {
+    ///
+    ///
+    ///
+    pub fn new<S: Into<String>>(
+        reason: S,
+        message: S,
+    ) -> Self {
+        JobFailure {
+            reason: reason.into(),
+            message: message.into(),
+            exit_code: None,
+            stderr: None,
+        }
+    }
+
+    ///
+    ///
+    ///
+    pub fn from_payload(payload: &[u8]) -> Self {
+        if let Ok(failure) = serde_json::from_slice(payload) {
+            return failure;
+        }
+
+        // Older branelets don't send a structured payload, if any.
+        let message = String::from_utf8_lossy(payload).trim().to_string();
+        if message.is_empty() {
+            JobFailure::new(REASON_FAILED, "Job failed without further details.")
+        } else {
+            JobFailure::new(REASON_FAILED.to_string(), message)
+        }
+    }
+}
+
+impl fmt::Display for JobFailure {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self.exit_code {
+            Some(code) => write!(f, "{} (exit code: {})", self.message, code),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Error for JobFailure {}
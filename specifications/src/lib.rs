@@ -3,5 +3,7 @@ extern crate anyhow;
 
 pub mod common;
 pub mod container;
+pub mod errors;
+pub mod output;
 pub mod package;
 pub mod status;